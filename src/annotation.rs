@@ -0,0 +1,268 @@
+use std::os::unix::ffi::OsStrExt;
+
+use futures_lite::StreamExt;
+use iroh::{client::docs::Doc, docs::store::Query};
+
+use crate::{prelude::*, LisError};
+
+/// Start of the keys of `name`'s annotations in its directory's doc. Names can't be empty or
+/// have a NUL, so these never clash with the keys of files and directories (see
+/// `is_annotation_key`)
+fn annotation_prefix(name: &[u8]) -> Vec<u8> {
+    let mut prefix = vec![0];
+    prefix.extend_from_slice(name);
+    prefix.push(0);
+    prefix
+}
+
+impl Lis {
+    /// Attaches the annotation `key` (e.g. `user.rating`) with `value` to `full_path`, replacing
+    /// any it had
+    /// Annotations are kept next to the entry in its directory's doc, so they sync to peers
+    /// along with it and last across remounts. The FUSE mount shows them as `user.*` xattrs.
+    /// They belong to the name, not the inode: hard links don't share them
+    pub async fn set_annotation(
+        &mut self,
+        full_path: &Path,
+        key: &str,
+        value: &[u8],
+    ) -> Result<()> {
+        self.check_writable(full_path)?;
+        let (doc, prefix) = self.existing_annotation_doc(full_path).await?;
+        if key.is_empty() || key.contains('\0') {
+            return Err(anyhow!("Invalid annotation key {key:?}"));
+        }
+        let author = self.iroh_node.authors().default().await?;
+        // stored as JSON, so an empty value isn't an empty entry (a deletion)
+        doc.set_bytes(
+            author,
+            [prefix, key.into()].concat(),
+            serde_json::to_vec(value)?,
+        )
+        .await?;
+        self.touch_metadata(full_path)
+    }
+
+    /// Value of the annotation `key` on `full_path`, if it has one
+    pub async fn get_annotation(&self, full_path: &Path, key: &str) -> Result<Option<Vec<u8>>> {
+        let (doc, prefix) = self.existing_annotation_doc(full_path).await?;
+        let query = Query::key_exact([prefix, key.into()].concat());
+        let Some(entry) = doc.get_one(query).await? else {
+            return Ok(None);
+        };
+        let content = entry.content_bytes(&doc).await?;
+        Ok(Some(serde_json::from_slice(&content)?))
+    }
+
+    /// All annotations on `full_path`, by key
+    pub async fn annotations(&self, full_path: &Path) -> Result<BTreeMap<String, Vec<u8>>> {
+        let (doc, prefix) = self.existing_annotation_doc(full_path).await?;
+        let mut entries = doc.get_many(Query::key_prefix(&prefix)).await?;
+        let mut annotations = BTreeMap::new();
+        while let Some(entry) = entries.next().await {
+            let entry = entry?;
+            let key = String::from_utf8(entry.key()[prefix.len()..].to_vec())?;
+            let content = entry.content_bytes(&doc).await?;
+            annotations.insert(key, serde_json::from_slice(&content)?);
+        }
+        Ok(annotations)
+    }
+
+    /// Removes the annotation `key` from `full_path`. `false` if it didn't have it
+    pub async fn remove_annotation(&mut self, full_path: &Path, key: &str) -> Result<bool> {
+        self.check_writable(full_path)?;
+        let (doc, prefix) = self.existing_annotation_doc(full_path).await?;
+        let key = [prefix, key.into()].concat();
+        if doc.get_one(Query::key_exact(&key)).await?.is_none() {
+            return Ok(false);
+        }
+        let author = self.iroh_node.authors().default().await?;
+        doc.del(author, key).await?;
+        self.touch_metadata(full_path)?;
+        Ok(true)
+    }
+
+    /// Removes every annotation of `full_path`, which is going away
+    pub(crate) async fn drop_annotations(&self, full_path: &Path) -> Result<()> {
+        let (doc, prefix) = self.annotation_doc(full_path).await?;
+        if doc.get_one(Query::key_prefix(&prefix)).await?.is_some() {
+            let author = self.iroh_node.authors().default().await?;
+            doc.del(author, prefix).await?;
+        }
+        Ok(())
+    }
+
+    /// Moves the annotations of `from` to `to`, when it's renamed. What `to` had is dropped
+    pub(crate) async fn move_annotations(&self, from: &Path, to: &Path) -> Result<()> {
+        let annotations = self.annotations(from).await?;
+        self.drop_annotations(from).await?;
+        self.drop_annotations(to).await?;
+        self.put_annotations(to, annotations).await
+    }
+
+    /// Swaps the annotations of `a` and `b`, when they're exchanged
+    pub(crate) async fn swap_annotations(&self, a: &Path, b: &Path) -> Result<()> {
+        let a_annotations = self.annotations(a).await?;
+        let b_annotations = self.annotations(b).await?;
+        self.drop_annotations(a).await?;
+        self.drop_annotations(b).await?;
+        self.put_annotations(a, b_annotations).await?;
+        self.put_annotations(b, a_annotations).await
+    }
+
+    /// Adds `annotations` to `full_path`
+    async fn put_annotations(
+        &self,
+        full_path: &Path,
+        annotations: BTreeMap<String, Vec<u8>>,
+    ) -> Result<()> {
+        let (doc, prefix) = self.annotation_doc(full_path).await?;
+        let author = self.iroh_node.authors().default().await?;
+        for (key, value) in annotations {
+            doc.set_bytes(
+                author,
+                [&prefix[..], key.as_bytes()].concat(),
+                serde_json::to_vec(&value)?,
+            )
+            .await?;
+        }
+        Ok(())
+    }
+
+    /// `annotation_doc` of `full_path`, if it exists
+    async fn existing_annotation_doc(&self, full_path: &Path) -> Result<(Doc, Vec<u8>)> {
+        if self.obj_from_path(full_path).is_none() {
+            return Err(LisError::NotFound(full_path.to_path_buf()).into());
+        }
+        self.annotation_doc(full_path).await
+    }
+
+    /// Doc holding the annotations of `full_path`, and the prefix of their keys. The root dir's
+    /// are in its own doc, under an empty name
+    async fn annotation_doc(&self, full_path: &Path) -> Result<(Doc, Vec<u8>)> {
+        let Some(name) = full_path.file_name() else {
+            return Ok((self.root_doc.clone(), annotation_prefix(b"")));
+        };
+        let parent = full_path
+            .parent()
+            .ok_or_else(|| LisError::NotFound(full_path.to_path_buf()))?;
+        let doc = self.find_dir_doc(parent).await?;
+        Ok((doc, annotation_prefix(name.as_bytes())))
+    }
+
+    /// Bumps the ctime of `full_path` after its annotations changed
+    fn touch_metadata(&mut self, full_path: &Path) -> Result<()> {
+        let obj = self
+            .obj_from_path(full_path)
+            .ok_or_else(|| LisError::NotFound(full_path.to_path_buf()))?;
+        let mut attrs = obj.attrs.clone();
+        attrs.last_metadata_changed = SystemTime::now();
+        self.write_inode(&attrs)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[tokio::test]
+    async fn annotations_follow_the_name() {
+        let tmp_dir = TempDir::new().unwrap();
+        let mut lis = Lis::new(&tmp_dir.path().to_path_buf(), true).await.unwrap();
+
+        let (a, b) = (Path::new("/a.txt"), Path::new("/b.txt"));
+        lis.touch(a, None, None, None).await.unwrap();
+        lis.set_annotation(a, "user.rating", b"5").await.unwrap();
+        lis.set_annotation(a, "user.empty", b"").await.unwrap();
+        lis.set_annotation(Path::new("/"), "user.owner", b"me")
+            .await
+            .unwrap();
+        assert_eq!(
+            lis.get_annotation(a, "user.rating").await.unwrap().unwrap(),
+            b"5"
+        );
+        assert_eq!(
+            lis.annotations(a).await.unwrap(),
+            BTreeMap::from([
+                ("user.empty".to_string(), Vec::new()),
+                ("user.rating".to_string(), b"5".to_vec())
+            ])
+        );
+        // not entries of the directory
+        assert_eq!(lis.list(Path::new("/")).await.unwrap().len(), 1);
+        let e = lis
+            .set_annotation(b, "user.rating", b"1")
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            e.downcast_ref::<LisError>(),
+            Some(LisError::NotFound(_))
+        ));
+
+        lis.rename(a, b, false).await.unwrap();
+        assert_eq!(lis.annotations(b).await.unwrap().len(), 2);
+        assert!(lis.remove_annotation(b, "user.empty").await.unwrap());
+        assert!(!lis.remove_annotation(b, "user.empty").await.unwrap());
+
+        lis.remove(b).await.unwrap();
+        lis.touch(b, None, None, None).await.unwrap();
+        assert_eq!(lis.get_annotation(b, "user.rating").await.unwrap(), None);
+        assert_eq!(
+            lis.get_annotation(Path::new("/"), "user.owner")
+                .await
+                .unwrap()
+                .unwrap(),
+            b"me"
+        );
+    }
+
+    #[tokio::test]
+    async fn annotations_sync_to_peers() {
+        let tmp_dir = TempDir::new().unwrap();
+        let mut shared = Lis::new(&tmp_dir.path().join("shared"), true)
+            .await
+            .unwrap();
+        let file_path = Path::new("/song.mp3");
+        shared.touch(file_path, None, None, None).await.unwrap();
+        shared
+            .set_annotation(file_path, "user.genre", b"jazz")
+            .await
+            .unwrap();
+
+        let ticket = shared.create_invite(false).await.unwrap();
+        let joined = Lis::join(&tmp_dir.path().join("joined"), &ticket, |_| {})
+            .await
+            .unwrap();
+        assert!(joined.exists(file_path));
+        assert_eq!(
+            joined
+                .get_annotation(file_path, "user.genre")
+                .await
+                .unwrap()
+                .unwrap(),
+            b"jazz"
+        );
+
+        // set after joining, comes with the live sync
+        shared
+            .set_annotation(file_path, "user.rating", b"5")
+            .await
+            .unwrap();
+        let rating = tokio::time::timeout(Duration::from_secs(10), async {
+            loop {
+                if let Some(rating) = joined
+                    .get_annotation(file_path, "user.rating")
+                    .await
+                    .unwrap()
+                {
+                    return rating;
+                }
+                tokio::time::sleep(Duration::from_millis(100)).await;
+            }
+        })
+        .await
+        .unwrap();
+        assert_eq!(rating, b"5");
+    }
+}
@@ -0,0 +1,310 @@
+use std::{fmt, ops::RangeBounds, str::FromStr};
+
+use futures_lite::StreamExt;
+use iroh::{
+    blobs::Hash,
+    docs::{store::Query, NamespaceId},
+};
+
+use crate::{prelude::*, LisError};
+
+/// Mutating operation recorded in the audit journal
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditOp {
+    Create,
+    Mkdir,
+    Write,
+    Import,
+    Remove,
+    Rmdir,
+    Rename { to: PathBuf },
+    Exchange { with: PathBuf },
+}
+
+impl fmt::Display for AuditOp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AuditOp::Create => write!(f, "create"),
+            AuditOp::Mkdir => write!(f, "mkdir"),
+            AuditOp::Write => write!(f, "write"),
+            AuditOp::Import => write!(f, "import"),
+            AuditOp::Remove => write!(f, "remove"),
+            AuditOp::Rmdir => write!(f, "rmdir"),
+            AuditOp::Rename { to } => write!(f, "rename -> {}", to.display()),
+            AuditOp::Exchange { with } => write!(f, "exchange <-> {}", with.display()),
+        }
+    }
+}
+
+/// The part of an entry its hash is computed over
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct AuditRecord {
+    seq: u64,
+    timestamp: SystemTime,
+    actor: String,
+    op: AuditOp,
+    path: PathBuf,
+    prev_hash: String,
+}
+
+impl AuditRecord {
+    fn hash(&self) -> Result<String> {
+        Ok(Hash::new(serde_json::to_vec(self)?).to_hex())
+    }
+}
+
+/// One journal entry. `hash` covers every other field, including the previous entry's hash,
+/// so changing an entry breaks the chain from there on
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AuditEntry {
+    pub seq: u64,
+    pub timestamp: SystemTime,
+    /// Node id of the node that did the operation
+    pub actor: String,
+    pub op: AuditOp,
+    pub path: PathBuf,
+    pub prev_hash: String,
+    pub hash: String,
+}
+
+impl AuditEntry {
+    fn record(&self) -> AuditRecord {
+        AuditRecord {
+            seq: self.seq,
+            timestamp: self.timestamp,
+            actor: self.actor.clone(),
+            op: self.op.clone(),
+            path: self.path.clone(),
+            prev_hash: self.prev_hash.clone(),
+        }
+    }
+}
+
+impl fmt::Display for AuditEntry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let secs = self
+            .timestamp
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        write!(
+            f,
+            "{} {secs} {} {} {}",
+            self.seq,
+            &self.actor[..self.actor.len().min(10)],
+            self.op,
+            self.path.display()
+        )
+    }
+}
+
+/// Where the journal lives and the entry new ones chain from, kept in the manifest
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuditHead {
+    pub doc_id: String,
+    pub seq: u64,
+    pub hash: String,
+}
+
+/// Key of entry `seq` in the journal doc, zero-padded so keys sort in journal order
+fn audit_key(seq: u64) -> String {
+    format!("{seq:020}")
+}
+
+impl Lis {
+    /// Starts recording mutating operations in a hash-chained journal stored in its own doc
+    /// Metadata-only changes (modes, owners, times) are not recorded
+    pub async fn enable_audit_log(&mut self) -> Result<()> {
+        if self.manifest.audit.is_some() {
+            return Ok(());
+        }
+        let doc = self.iroh_node.docs().create().await?;
+        self.manifest.audit = Some(AuditHead {
+            doc_id: doc.id().to_string(),
+            seq: 0,
+            hash: String::new(),
+        });
+        self.manifest.save()
+    }
+
+    /// Appends an entry to the journal, if it's enabled
+    pub(crate) async fn audit(&mut self, op: AuditOp, full_path: &Path) -> Result<()> {
+        let Some(head) = self.manifest.audit.clone() else {
+            return Ok(());
+        };
+
+        let record = AuditRecord {
+            seq: head.seq + 1,
+            timestamp: SystemTime::now(),
+            actor: self.iroh_node.node_id().to_string(),
+            op,
+            path: full_path.to_path_buf(),
+            prev_hash: head.hash,
+        };
+        let hash = record.hash()?;
+        let entry = AuditEntry {
+            seq: record.seq,
+            timestamp: record.timestamp,
+            actor: record.actor,
+            op: record.op,
+            path: record.path,
+            prev_hash: record.prev_hash,
+            hash: hash.clone(),
+        };
+
+        let doc = self.audit_doc(&head.doc_id).await?;
+        let author = self.iroh_node.authors().default().await?;
+        doc.set_bytes(author, audit_key(entry.seq), serde_json::to_vec(&entry)?)
+            .await?;
+
+        self.manifest.audit = Some(AuditHead {
+            doc_id: head.doc_id,
+            seq: entry.seq,
+            hash,
+        });
+        self.manifest.save()
+    }
+
+    async fn audit_doc(&self, doc_id: &str) -> Result<iroh::client::docs::Doc> {
+        self.iroh_node
+            .docs()
+            .open(NamespaceId::from_str(doc_id)?)
+            .await?
+            .ok_or_else(|| anyhow!("audit journal doc {doc_id} not found"))
+    }
+
+    async fn audit_entries(&self) -> Result<Vec<AuditEntry>> {
+        let Some(head) = &self.manifest.audit else {
+            return Err(anyhow!("the audit journal is not enabled"));
+        };
+        let doc = self.audit_doc(&head.doc_id).await?;
+
+        let mut entries = Vec::new();
+        let mut stream = doc.get_many(Query::all().build()).await?;
+        while let Some(entry) = stream.next().await {
+            let content = entry?.content_bytes(self.iroh_node.client()).await?;
+            entries.push(serde_json::from_slice::<AuditEntry>(&content)?);
+        }
+        entries.sort_by_key(|entry| entry.seq);
+        Ok(entries)
+    }
+
+    /// Journal entries about `path` (or anything below it) within `time_range`, oldest first
+    pub async fn audit_log(
+        &self,
+        path: &Path,
+        time_range: impl RangeBounds<SystemTime>,
+    ) -> Result<Vec<AuditEntry>> {
+        Ok(self
+            .audit_entries()
+            .await?
+            .into_iter()
+            .filter(|entry| {
+                let renamed_into = match &entry.op {
+                    AuditOp::Rename { to } | AuditOp::Exchange { with: to } => to.starts_with(path),
+                    _ => false,
+                };
+                (entry.path.starts_with(path) || renamed_into)
+                    && time_range.contains(&entry.timestamp)
+            })
+            .collect())
+    }
+
+    /// Checks the whole journal: every entry's hash, that each one links to the previous one,
+    /// that no entry is missing and that the last one is the head the manifest knows about
+    pub async fn verify_audit_log(&self) -> Result<()> {
+        let entries = self.audit_entries().await?;
+        let mut prev_hash = String::new();
+        for (i, entry) in entries.iter().enumerate() {
+            let seq = i as u64 + 1;
+            if entry.seq != seq {
+                return Err(anyhow!("audit journal entry {seq} is missing"));
+            }
+            if entry.prev_hash != prev_hash || entry.record().hash()? != entry.hash {
+                return Err(anyhow!(
+                    "audit journal entry {seq} ({}) was altered",
+                    entry.path.display()
+                ));
+            }
+            prev_hash = entry.hash.clone();
+        }
+
+        let head = self
+            .manifest
+            .audit
+            .as_ref()
+            .ok_or_else(|| LisError::NotFound(PathBuf::from("audit journal")))?;
+        if head.seq != entries.len() as u64 || head.hash != prev_hash {
+            return Err(anyhow!(
+                "audit journal ends at entry {} but {} were recorded",
+                entries.len(),
+                head.seq
+            ));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[tokio::test]
+    async fn audit_log_hash_chain() {
+        let tmp_dir = TempDir::new().unwrap();
+        let mut lis = Lis::new(&tmp_dir.path().to_path_buf(), true).await.unwrap();
+        lis.enable_audit_log().await.unwrap();
+
+        let dir = Path::new("/dir");
+        let a = Path::new("/dir/a.txt");
+        let b = Path::new("/dir/b.txt");
+        lis.mkdir(dir, None, None, None, false).await.unwrap();
+        lis.touch(a, None, None, None).await.unwrap();
+        lis.write(a, b"hello", 0).await.unwrap();
+        lis.rename(a, b, false).await.unwrap();
+        lis.remove(b).await.unwrap();
+
+        let ops: Vec<_> = lis
+            .audit_log(Path::new("/"), ..)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|entry| (entry.op, entry.path))
+            .collect();
+        assert_eq!(
+            ops,
+            vec![
+                (AuditOp::Mkdir, dir.to_path_buf()),
+                (AuditOp::Create, a.to_path_buf()),
+                (AuditOp::Write, a.to_path_buf()),
+                (
+                    AuditOp::Rename {
+                        to: b.to_path_buf()
+                    },
+                    a.to_path_buf()
+                ),
+                (AuditOp::Remove, b.to_path_buf()),
+            ]
+        );
+        assert_eq!(lis.audit_log(b, ..).await.unwrap().len(), 2);
+        lis.verify_audit_log().await.unwrap();
+
+        // rewrite the 3rd entry to point at another file
+        let head = lis.manifest.audit.clone().unwrap();
+        let doc = lis.audit_doc(&head.doc_id).await.unwrap();
+        let mut entries = lis.audit_entries().await.unwrap();
+        entries[2].path = PathBuf::from("/dir/other.txt");
+        let author = lis.iroh_node.authors().default().await.unwrap();
+        doc.set_bytes(
+            author,
+            audit_key(3),
+            serde_json::to_vec(&entries[2]).unwrap(),
+        )
+        .await
+        .unwrap();
+
+        assert!(lis.verify_audit_log().await.is_err());
+    }
+}
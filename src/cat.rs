@@ -0,0 +1,189 @@
+use std::{fmt, str::FromStr};
+
+use bytes::Bytes;
+use iroh::docs::store::Query;
+
+use crate::{prelude::*, LisError};
+
+/// How much is read at a time while looking for newlines
+const SCAN_CHUNK: usize = 64 * 1024;
+
+/// A `start:end` slice of a file, in bytes or lines, like Python's: indices start at 0, `end` is
+/// excluded, negative ones count from the end and either side can be left out. `-10:` is the
+/// last ten, `:10` the first ten
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Span {
+    pub start: Option<i64>,
+    pub end: Option<i64>,
+}
+
+impl FromStr for Span {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (start, end) = s
+            .split_once(':')
+            .ok_or_else(|| anyhow!("{s} is not a start:end range"))?;
+        let index = |i: &str| -> Result<Option<i64>> {
+            if i.is_empty() {
+                return Ok(None);
+            }
+            i.parse()
+                .map(Some)
+                .map_err(|e| anyhow!("{i} is not a valid index: {e}"))
+        };
+        Ok(Span {
+            start: index(start)?,
+            end: index(end)?,
+        })
+    }
+}
+
+impl fmt::Display for Span {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(start) = self.start {
+            write!(f, "{start}")?;
+        }
+        write!(f, ":")?;
+        if let Some(end) = self.end {
+            write!(f, "{end}")?;
+        }
+        Ok(())
+    }
+}
+
+impl Lis {
+    /// Reads the bytes of `full_path` that `span` selects
+    pub async fn read_bytes(&self, full_path: &Path, span: Span) -> Result<Bytes> {
+        let size = self.file_size(full_path).await?;
+        let resolve = |i: i64| {
+            if i < 0 {
+                size.saturating_sub(i.unsigned_abs())
+            } else {
+                size.min(i as u64)
+            }
+        };
+        let start = span.start.map_or(0, resolve);
+        let end = span.end.map_or(size, resolve);
+        if end <= start {
+            return Ok(Bytes::new());
+        }
+        self.read_range(full_path, start, (end - start) as usize)
+            .await
+    }
+
+    /// Reads the lines of `full_path` that `span` selects, newlines included
+    /// Only the chunks up to the last line wanted are read, or from the first one wanted when
+    /// counting from the end, so the head or tail of a large file is cheap
+    pub async fn read_lines(&self, full_path: &Path, span: Span) -> Result<Bytes> {
+        let size = self.file_size(full_path).await?;
+        let start = match span.start {
+            Some(i) => self.line_offset(full_path, size, i).await?,
+            None => 0,
+        };
+        let end = match span.end {
+            Some(i) => self.line_offset(full_path, size, i).await?,
+            None => size,
+        };
+        if end <= start {
+            return Ok(Bytes::new());
+        }
+        self.read_range(full_path, start, (end - start) as usize)
+            .await
+    }
+
+    /// Size of the file at `full_path`
+    pub async fn file_size(&self, full_path: &Path) -> Result<u64> {
+        if self.is_dir(full_path) {
+            return Err(LisError::IsADirectory(full_path.to_path_buf()).into());
+        }
+        let (doc, key) = self.doc_and_key(full_path).await?;
+        let entry = doc
+            .get_one(Query::key_exact(key))
+            .await?
+            .ok_or_else(|| LisError::NotFound(full_path.to_path_buf()))?;
+        Ok(self.logical_size(full_path, entry.content_len()))
+    }
+
+    /// Byte offset where line `line` starts, counting from the end if negative. Past either end
+    /// it's clamped to the file
+    async fn line_offset(&self, full_path: &Path, size: u64, line: i64) -> Result<u64> {
+        if line >= 0 {
+            let mut newlines = 0;
+            let mut offset = 0;
+            while newlines < line && offset < size {
+                let chunk = self.read_range(full_path, offset, SCAN_CHUNK).await?;
+                for (i, byte) in chunk.iter().enumerate() {
+                    if *byte == b'\n' {
+                        newlines += 1;
+                        if newlines == line {
+                            return Ok(offset + i as u64 + 1);
+                        }
+                    }
+                }
+                offset += chunk.len() as u64;
+            }
+            return Ok(offset.min(size));
+        }
+
+        // the newline ending the file doesn't start another line
+        let mut end = size;
+        if end > 0 && self.read_range(full_path, end - 1, 1).await?[..] == *b"\n" {
+            end -= 1;
+        }
+        let wanted = line.unsigned_abs();
+        let mut newlines = 0;
+        while end > 0 {
+            let start = end.saturating_sub(SCAN_CHUNK as u64);
+            let chunk = self
+                .read_range(full_path, start, (end - start) as usize)
+                .await?;
+            for (i, byte) in chunk.iter().enumerate().rev() {
+                if *byte == b'\n' {
+                    newlines += 1;
+                    if newlines == wanted {
+                        return Ok(start + i as u64 + 1);
+                    }
+                }
+            }
+            end = start;
+        }
+        Ok(0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[tokio::test]
+    async fn read_lines_and_bytes() {
+        let tmp_dir = TempDir::new().unwrap();
+        let mut lis = Lis::new(&tmp_dir.path().to_path_buf(), true).await.unwrap();
+
+        let file_path = Path::new("/log.txt");
+        lis.touch(file_path, None, None, None).await.unwrap();
+        lis.write(file_path, b"one\ntwo\nthree\nfour\n", 0)
+            .await
+            .unwrap();
+
+        let lines = |span: &str| lis.read_lines(file_path, span.parse().unwrap());
+        assert_eq!(lines("1:3").await.unwrap(), "two\nthree\n");
+        assert_eq!(lines("-2:").await.unwrap(), "three\nfour\n");
+        assert_eq!(lines(":-3").await.unwrap(), "one\n");
+        assert_eq!(lines("-10:1").await.unwrap(), "one\n");
+        assert_eq!(lines("3:10").await.unwrap(), "four\n");
+        assert_eq!(lines("3:1").await.unwrap(), "");
+
+        let bytes = |span: &str| lis.read_bytes(file_path, span.parse().unwrap());
+        assert_eq!(bytes("4:7").await.unwrap(), "two");
+        assert_eq!(bytes("-5:").await.unwrap(), "four\n");
+        assert_eq!(bytes("100:").await.unwrap(), "");
+
+        assert!("12".parse::<Span>().is_err());
+        assert!("a:b".parse::<Span>().is_err());
+        assert_eq!(":".parse::<Span>().unwrap(), Span::default());
+        assert_eq!("-3:".parse::<Span>().unwrap().to_string(), "-3:");
+    }
+}
@@ -1,7 +1,11 @@
+use std::io::Write;
 use std::path::PathBuf;
 
-use clap::{ArgAction, Parser, Subcommand};
-use iroh::net::ticket::NodeTicket;
+use clap::{ArgAction, CommandFactory, Parser, Subcommand, ValueEnum};
+use clap_complete::Shell;
+use iroh::docs::DocTicket;
+
+use crate::{ImportMtime, Span};
 
 #[derive(Parser)]
 #[command(name = "lis", version, about, long_about = None)]
@@ -10,6 +14,7 @@ pub struct Cli {
     #[arg(required = true, value_parser)]
     pub root: PathBuf,
 
+    /// Start a new node in root, removing the old one but keeping its lis.toml
     #[arg(short, long)]
     pub overwrite: bool,
 
@@ -17,9 +22,9 @@ pub struct Cli {
     #[arg(short, action = ArgAction::Count)]
     pub verbosity: u8,
 
-    // /// Sets a custom config file
-    // #[arg(short, long, value_name = "FILE")]
-    // config: Option<PathBuf>,
+    /// Output format for errors and listings
+    #[arg(long, value_enum, default_value_t = Format::Text)]
+    pub format: Format,
 
     // /// Turn debugging information on
     // #[arg(short, long, action = clap::ArgAction::Count)]
@@ -28,30 +33,164 @@ pub struct Cli {
     pub command: Commands,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Format {
+    /// Human readable text
+    Text,
+    /// `{ "error": { "kind": ..., "message": ..., "path": ... } }` on stderr, and `list` prints
+    /// `[{ "name": ..., "metadata": { "kind": ..., "size": ..., ... } }]`
+    Json,
+}
+
 #[derive(Subcommand)]
 pub enum Commands {
-    /// Imports existing files into node
-    /// Paths that don't exist or aren't accessible are ignored
-    ImportFile { paths: Vec<PathBuf> },
+    /// Imports existing files into node, under the root dir
+    /// Directories are imported with everything in them
+    ImportFile {
+        paths: Vec<PathBuf>,
+        /// Replace files and reuse directories that already exist in the node
+        #[arg(long)]
+        overwrite: bool,
+        /// Modification time the files get. `epoch` and `content` are the same on every import
+        /// of the same files
+        #[arg(long, value_enum, default_value_t = ImportMtime::Now)]
+        mtime: ImportMtime,
+    },
+    /// Downloads a URL into the node at `dst`
+    ImportUrl {
+        url: String,
+        dst: PathBuf,
+        /// Replace the file at `dst` if it already exists
+        #[arg(long)]
+        overwrite: bool,
+    },
     /// Creates new top-level directory (e.g. `/foo` or `/bar`)
-    Mkdir { path: PathBuf },
+    Mkdir {
+        path: PathBuf,
+        /// No error if the directory exists, make parent directories as needed
+        #[arg(short, long)]
+        parents: bool,
+    },
     /// List files on filesystem
     /// Paths that don't exist or aren't accessible are ignored
     #[command(alias = "ls")]
-    List { path: Option<PathBuf> },
-    /// Reads files that are not currently locally accessible
-    /// Paths that don't exist or aren't accessible are ignored
-    Read { paths: Vec<PathBuf> },
+    List {
+        path: Option<PathBuf>,
+        /// Everything below `path` too, as full paths
+        #[arg(short = 'R', long)]
+        recursive: bool,
+    },
+    /// Shows the metadata of a path, without reading it
+    Stat { path: PathBuf },
+    /// Writes the contents of files to stdout, one after the other like `cat`
+    Read {
+        paths: Vec<PathBuf>,
+        /// Only these lines of each file, as `start:end` counting from 0 with `end` excluded.
+        /// Negative indices count from the end, so `-10:` is the last ten
+        #[arg(long, allow_hyphen_values = true, conflicts_with = "bytes")]
+        lines: Option<Span>,
+        /// Only these bytes of each file, as `start:end` like `--lines`
+        #[arg(long, allow_hyphen_values = true)]
+        bytes: Option<Span>,
+    },
+    /// Writes the last lines of a file to stdout, like `tail`
+    Tail {
+        path: PathBuf,
+        /// How many lines
+        #[arg(short = 'n', long, default_value_t = 10)]
+        lines: u32,
+        /// Keep printing what's appended, here or by peers, until Ctrl-C
+        #[arg(short, long)]
+        follow: bool,
+    },
     /// Removes files in filesystem
-    /// Paths that don't exist or aren't accessible are ignored
+    /// Each path is tried, failures are reported at the end. Directories need rmdir
     Rm { paths: Vec<PathBuf> },
-    /// Removes dirs in filesystem
-    /// Paths that don't exist or aren't accessible are ignored
+    /// Removes empty dirs in filesystem
+    /// Each path is tried, failures are reported at the end
     Rmdir { paths: Vec<PathBuf> },
+    /// Moves or renames files and dirs, like `mv`
+    /// With several sources, or an existing directory as the last path, the sources are moved
+    /// into that directory keeping their names
+    Mv {
+        #[arg(required = true, num_args = 2..)]
+        paths: Vec<PathBuf>,
+    },
     /// Joins a network using the given ticket
-    Join { ticket: NodeTicket },
-    /// Generates a ticket for joining a network with Join
-    Invite {},
-    /// Mounts path and keeps mounted while cli is running
-    Mount { mountpoint: PathBuf },
+    /// Syncs a copy of the filesystem shared by an Invite ticket into the root dir, which must
+    /// not hold a node yet
+    Join { ticket: DocTicket },
+    /// Generates a ticket for joining a network with Join, then serves the node to peers that
+    /// use it until Ctrl-C
+    Invite {
+        /// Peers that join can read but not change anything
+        #[arg(long)]
+        read_only: bool,
+    },
+    /// Keeps the node online to serve its content to peers, without mounting it
+    /// Stops on Ctrl-C
+    Serve {},
+    /// Mounts the node at mountpoint, in the background unless --foreground
+    /// Unmount with `umount`, or Ctrl-C when in the foreground
+    Mount {
+        mountpoint: PathBuf,
+        /// Stay attached to the terminal until unmounted
+        #[arg(short, long)]
+        foreground: bool,
+        /// Let other users access the mount, needs `user_allow_other` in /etc/fuse.conf unless
+        /// mounting as root
+        #[arg(long)]
+        allow_other: bool,
+        /// Bypass the kernel page cache on every open (default)
+        #[arg(long, conflicts_with = "cached")]
+        direct_io: bool,
+        /// Let the kernel cache file contents across opens, good for read-heavy workloads
+        #[arg(long)]
+        cached: bool,
+        /// Never update access times on read (default is relatime)
+        #[arg(long)]
+        noatime: bool,
+        /// Move removed files and dirs to /.trash instead of deleting them
+        #[arg(long)]
+        trash: bool,
+        /// Most files open at once, opening more fails with ENFILE
+        #[arg(long)]
+        max_open_files: Option<u64>,
+    },
+    /// Checks the node for common problems and suggests fixes
+    Doctor {},
+    /// Prints the audit journal entries for path (or everything), then verifies the journal
+    Audit {
+        path: Option<PathBuf>,
+        /// Start recording operations in the journal
+        #[arg(long)]
+        enable: bool,
+    },
+    /// Prints the effective config (lis.toml merged with command line flags)
+    Config {},
+    /// Prints a shell completion script to stdout
+    Completions { shell: Shell },
+}
+
+/// Writes the completion script for `shell` to `buf`
+pub fn write_completions(shell: Shell, buf: &mut dyn Write) {
+    let mut cmd = Cli::command();
+    let name = cmd.get_name().to_string();
+    clap_complete::generate(shell, &mut cmd, name, buf);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bash_completions() {
+        let mut buf = Vec::new();
+        write_completions(Shell::Bash, &mut buf);
+        let script = String::from_utf8(buf).unwrap();
+
+        assert!(script.contains("mkdir"));
+        assert!(script.contains("mount"));
+        assert!(script.contains("import-file"));
+    }
 }
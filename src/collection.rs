@@ -0,0 +1,138 @@
+use bytes::Bytes;
+use iroh::{
+    blobs::{format::collection::Collection, util::SetTagOption, Hash},
+    docs::store::Query,
+};
+
+use crate::{fuse::FileKind, prelude::*, LisError};
+
+impl Lis {
+    /// Packages the whole tree into one Iroh collection and returns its hash
+    /// Each file is an entry named by its path without the leading `/`. Directories are kept as
+    /// `path/` entries pointing to an empty blob, so empty ones survive the round trip. Only
+    /// names and content are exported, not modes or owners
+    pub async fn export_collection(&self) -> Result<Hash> {
+        self.export_collection_tagged(SetTagOption::Auto).await
+    }
+
+    /// `export_collection`, keeping the collection and its blobs under `tag`
+    pub(crate) async fn export_collection_tagged(&self, tag: SetTagOption) -> Result<Hash> {
+        let blobs = self.iroh_node.blobs();
+        let empty = blobs.add_bytes(Bytes::new()).await?;
+
+        let mut collection = Collection::default();
+        for (path, ino) in &self.manifest.inodes {
+            let Some(obj) = self.manifest.objects.get(ino) else {
+                continue;
+            };
+            let Ok(relpath) = path.strip_prefix("/") else {
+                continue;
+            };
+            if relpath.as_os_str().is_empty() {
+                continue;
+            }
+            let name = relpath.to_string_lossy().into_owned();
+
+            match obj.attrs.kind {
+                FileKind::Directory => collection.push(format!("{name}/"), empty.hash),
+                FileKind::File => {
+                    let (doc, key) = self.doc_and_key(path).await?;
+                    let entry = doc
+                        .get_one(Query::key_exact(key))
+                        .await?
+                        .ok_or_else(|| LisError::NotFound(path.clone()))?;
+                    collection.push(name, entry.content_hash());
+                }
+                FileKind::Symlink => {}
+            }
+        }
+
+        // the collection's tag keeps the empty blob too
+        let (hash, _tag) = blobs
+            .create_collection(collection, tag, vec![empty.tag])
+            .await?;
+        Ok(hash)
+    }
+
+    /// Recreates the tree of a collection made by `export_collection`
+    /// The collection and its blobs must already be in the local store (e.g. fetched with
+    /// `blobs().download_hash_seq`). Existing files at the same paths are replaced
+    pub async fn import_collection(&mut self, hash: Hash) -> Result<()> {
+        let collection = self.iroh_node.blobs().get_collection(hash).await?;
+
+        for (name, blob_hash) in collection {
+            let full_path = Path::new("/").join(name.trim_end_matches('/'));
+            self.mkdir_parents(&full_path).await?;
+
+            if name.ends_with('/') {
+                self.mkdir(&full_path, None, None, None, true).await?;
+            } else {
+                let size = self.iroh_node.blobs().read(blob_hash).await?.size();
+                self.put_hash(&full_path, blob_hash, size).await?;
+            }
+        }
+        self.manifest.save()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    /// Paths and file contents of the whole tree
+    async fn tree(lis: &mut Lis) -> Vec<(PathBuf, Option<Bytes>)> {
+        let objects: Vec<_> = lis
+            .manifest
+            .inodes
+            .keys()
+            .map(|path| (path.clone(), lis.is_dir(path)))
+            .collect();
+        let mut tree = Vec::new();
+        for (path, is_dir) in objects {
+            let content = if is_dir {
+                None
+            } else {
+                Some(lis.read(&path).await.unwrap())
+            };
+            tree.push((path, content));
+        }
+        tree
+    }
+
+    #[tokio::test]
+    async fn export_import_collection() {
+        let src_dir = TempDir::new().unwrap();
+        let mut src = Lis::new(&src_dir.path().to_path_buf(), true).await.unwrap();
+        src.mkdir(Path::new("/docs"), None, None, None, false)
+            .await
+            .unwrap();
+        src.mkdir(Path::new("/empty"), None, None, None, false)
+            .await
+            .unwrap();
+        for (path, content) in [("/a.txt", "a"), ("/docs/b.txt", "b")] {
+            src.touch(Path::new(path), None, None, None).await.unwrap();
+            src.write(Path::new(path), content.as_bytes(), 0)
+                .await
+                .unwrap();
+        }
+
+        let hash = src.export_collection().await.unwrap();
+
+        // fetch the collection into a fresh store, then rebuild the tree from it
+        let dst_dir = TempDir::new().unwrap();
+        let mut dst = Lis::new(&dst_dir.path().to_path_buf(), true).await.unwrap();
+        let src_addr = src.iroh_node.net().node_addr().await.unwrap();
+        dst.iroh_node
+            .blobs()
+            .download_hash_seq(hash, src_addr)
+            .await
+            .unwrap()
+            .finish()
+            .await
+            .unwrap();
+        dst.import_collection(hash).await.unwrap();
+
+        assert_eq!(tree(&mut dst).await, tree(&mut src).await);
+    }
+}
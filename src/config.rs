@@ -0,0 +1,177 @@
+use std::{env, fs};
+
+use fuser::MountOption;
+
+use crate::{
+    cli::{Cli, Commands},
+    prelude::*,
+};
+
+pub const CONFIG_FILE_NAME: &str = "lis.toml";
+
+/// Options read from `lis.toml`. Flags passed on the command line take precedence
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// Log verbosity, same scale as `-v` (0 = errors only, 4 = trace)
+    pub verbosity: u8,
+    /// Bypass the kernel page cache when mounting (see `mount --cached`)
+    pub direct_io: bool,
+    /// Most entries a single directory may hold before creating more fails
+    pub max_dir_entries: u64,
+    /// Most files open at once through the mount (see `mount --max-open-files`)
+    pub max_open_files: u64,
+    /// Never update access times on read (see `mount --noatime`)
+    pub noatime: bool,
+    /// Move removed entries to `/.trash` instead of deleting them (see `mount --trash`)
+    pub trash: bool,
+    /// Let other users access the mount (see `mount --allow-other`)
+    pub allow_other: bool,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            verbosity: 0,
+            direct_io: true,
+            max_dir_entries: DEFAULT_MAX_DIR_ENTRIES,
+            max_open_files: DEFAULT_MAX_OPEN_FILES,
+            noatime: false,
+            trash: false,
+            allow_other: false,
+        }
+    }
+}
+
+impl Config {
+    /// Loads the first `lis.toml` found in `root`, then `$XDG_CONFIG_HOME/lis` (or
+    /// `~/.config/lis`). Returns the default config if there is none
+    pub fn load(root: &Path) -> Result<Self> {
+        for dir in Self::search_dirs(root) {
+            let path = dir.join(CONFIG_FILE_NAME);
+            if path.exists() {
+                let content = fs::read_to_string(&path)?;
+                return toml::from_str(&content)
+                    .map_err(|e| anyhow!("could not parse {}: {e}", path.display()));
+            }
+        }
+        Ok(Config::default())
+    }
+
+    fn search_dirs(root: &Path) -> Vec<PathBuf> {
+        let mut dirs = vec![root.to_path_buf()];
+        if let Some(config_home) = env::var_os("XDG_CONFIG_HOME") {
+            dirs.push(PathBuf::from(config_home).join("lis"));
+        } else if let Some(home) = env::var_os("HOME") {
+            dirs.push(PathBuf::from(home).join(".config").join("lis"));
+        }
+        dirs
+    }
+
+    /// Applies the flags that were actually passed on the command line
+    pub fn merge_cli(&mut self, cli: &Cli) {
+        if cli.verbosity > 0 {
+            self.verbosity = cli.verbosity;
+        }
+        if let Commands::Mount {
+            direct_io,
+            cached,
+            noatime,
+            trash,
+            max_open_files,
+            allow_other,
+            ..
+        } = cli.command
+        {
+            if let Some(max_open_files) = max_open_files {
+                self.max_open_files = max_open_files;
+            }
+            if noatime {
+                self.noatime = true;
+            }
+            if trash {
+                self.trash = true;
+            }
+            if allow_other {
+                self.allow_other = true;
+            }
+            if direct_io {
+                self.direct_io = true;
+            }
+            if cached {
+                self.direct_io = false;
+            }
+        }
+    }
+
+    /// Options to mount with. The mount goes away when lis exits, even if it's killed
+    pub fn mount_options(&self) -> Vec<MountOption> {
+        let mut options = vec![
+            MountOption::RW,
+            MountOption::FSName("lis".to_string()),
+            MountOption::AutoUnmount,
+        ];
+        if self.allow_other {
+            options.push(MountOption::AllowOther);
+        }
+        options
+    }
+
+    pub fn to_toml(&self) -> Result<String> {
+        Ok(toml::to_string(self)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+    use tempfile::TempDir;
+
+    #[test]
+    fn config_file_and_cli_override() {
+        let tmp_dir = TempDir::new().unwrap();
+        fs::write(
+            tmp_dir.path().join(CONFIG_FILE_NAME),
+            "verbosity = 2\ndirect_io = false\nmax_dir_entries = 10\n",
+        )
+        .unwrap();
+
+        // values from the file are picked up
+        let mut config = Config::load(tmp_dir.path()).unwrap();
+        assert_eq!(
+            config,
+            Config {
+                verbosity: 2,
+                direct_io: false,
+                max_dir_entries: 10,
+                max_open_files: DEFAULT_MAX_OPEN_FILES,
+                noatime: false,
+                trash: false,
+                allow_other: false,
+            }
+        );
+
+        // flags override the file
+        let root = tmp_dir.path().to_str().unwrap();
+        let cli = Cli::parse_from(["lis", root, "-v", "mount", "/mnt", "--direct-io"]);
+        config.merge_cli(&cli);
+        assert!(!config.mount_options().contains(&MountOption::AllowOther));
+        assert_eq!(
+            config,
+            Config {
+                verbosity: 1,
+                direct_io: true,
+                max_dir_entries: 10,
+                max_open_files: DEFAULT_MAX_OPEN_FILES,
+                noatime: false,
+                trash: false,
+                allow_other: false,
+            }
+        );
+
+        let cli = Cli::parse_from(["lis", root, "mount", "/mnt", "--allow-other"]);
+        config.merge_cli(&cli);
+        assert!(config.mount_options().contains(&MountOption::AllowOther));
+    }
+}
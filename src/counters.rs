@@ -0,0 +1,28 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// How many times the FUSE callbacks that go to the store ran. Shared with `Arc` so it can still
+/// be read once `Lis` has been handed to the mount
+#[derive(Debug, Default)]
+pub struct FuseCounters {
+    pub lookups: AtomicU64,
+    pub getattrs: AtomicU64,
+    pub reads: AtomicU64,
+}
+
+impl FuseCounters {
+    pub(crate) fn bump(counter: &AtomicU64) {
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn lookups(&self) -> u64 {
+        self.lookups.load(Ordering::Relaxed)
+    }
+
+    pub fn getattrs(&self) -> u64 {
+        self.getattrs.load(Ordering::Relaxed)
+    }
+
+    pub fn reads(&self) -> u64 {
+        self.reads.load(Ordering::Relaxed)
+    }
+}
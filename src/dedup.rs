@@ -0,0 +1,99 @@
+use iroh::docs::store::Query;
+
+use crate::{fuse::FileKind, prelude::*};
+
+/// How much storage files share because they have the same content
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DedupStats {
+    /// Distinct blobs the files point to
+    pub unique_blocks: u64,
+    /// Files pointing to a blob. Hard links count once, they're the same file
+    pub referenced_blocks: u64,
+    /// What storing each file's content separately would have taken on top
+    pub bytes_saved: u64,
+}
+
+impl Lis {
+    /// Counts how the files share content
+    /// Iroh stores content by its BLAKE3 hash, so writing or importing content that's already
+    /// in the store adds a reference to the existing blob instead of a copy. Its GC only deletes
+    /// blobs no doc entry points to anymore, which is the refcount dropping to zero
+    pub async fn dedup_stats(&self) -> Result<DedupStats> {
+        let files: Vec<_> = self
+            .manifest
+            .objects
+            .values()
+            .filter(|obj| matches!(obj.attrs.kind, FileKind::File))
+            .map(|obj| obj.full_path.clone())
+            .collect();
+
+        // hash -> (size, references)
+        let mut blobs = BTreeMap::new();
+        for full_path in files {
+            let (doc, key) = self.doc_and_key(&full_path).await?;
+            let Some(entry) = doc.get_one(Query::key_exact(key)).await? else {
+                continue;
+            };
+            let (_, references) = blobs
+                .entry(entry.content_hash())
+                .or_insert((entry.content_len(), 0));
+            *references += 1;
+        }
+
+        let mut stats = DedupStats::default();
+        for (size, references) in blobs.into_values() {
+            stats.unique_blocks += 1;
+            stats.referenced_blocks += references;
+            stats.bytes_saved += size * (references - 1);
+        }
+        Ok(stats)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures_lite::StreamExt;
+    use tempfile::TempDir;
+
+    #[tokio::test]
+    async fn same_content_is_stored_once() {
+        let tmp_dir = TempDir::new().unwrap();
+        let mut lis = Lis::new(&tmp_dir.path().to_path_buf(), true).await.unwrap();
+
+        let buffer: Vec<u8> = (0..64 * 1024).map(|i| (i % 251) as u8).collect();
+        for path in ["/a.bin", "/b.bin"] {
+            lis.touch(Path::new(path), None, None, None).await.unwrap();
+            lis.write(Path::new(path), &buffer, 0).await.unwrap();
+        }
+        assert_eq!(
+            lis.dedup_stats().await.unwrap(),
+            DedupStats {
+                unique_blocks: 1,
+                referenced_blocks: 2,
+                bytes_saved: 64 * 1024,
+            }
+        );
+
+        let mut stored = 0;
+        let mut blobs = lis.iroh_node.blobs().list().await.unwrap();
+        while let Some(blob) = blobs.next().await {
+            if blob.unwrap().size == buffer.len() as u64 {
+                stored += 1;
+            }
+        }
+        assert_eq!(stored, 1);
+
+        // the blob stays for the file still pointing to it
+        lis.remove(Path::new("/a.bin")).await.unwrap();
+        assert_eq!(
+            lis.dedup_stats().await.unwrap(),
+            DedupStats {
+                unique_blocks: 1,
+                referenced_blocks: 1,
+                bytes_saved: 0,
+            }
+        );
+        assert_eq!(lis.read(Path::new("/b.bin")).await.unwrap(), buffer);
+    }
+}
@@ -0,0 +1,247 @@
+use iroh::blobs::Hash;
+
+use crate::{prelude::*, LisError};
+
+/// rsync's weak checksum: two 16-bit sums that can be rolled one byte at a time, so a receiver
+/// can slide a window over its copy and only compute strong hashes where the weak one matches
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RollingChecksum {
+    a: u16,
+    b: u16,
+    len: usize,
+}
+
+impl RollingChecksum {
+    pub fn new(window: &[u8]) -> Self {
+        let mut a: u16 = 0;
+        let mut b: u16 = 0;
+        for (i, byte) in window.iter().enumerate() {
+            a = a.wrapping_add(*byte as u16);
+            b = b.wrapping_add(((window.len() - i) as u16).wrapping_mul(*byte as u16));
+        }
+        RollingChecksum {
+            a,
+            b,
+            len: window.len(),
+        }
+    }
+
+    /// Slides the window one byte forward: `out` leaves at the front, `inp` enters at the back
+    pub fn roll(&mut self, out: u8, inp: u8) {
+        self.a = self.a.wrapping_sub(out as u16).wrapping_add(inp as u16);
+        self.b = self
+            .b
+            .wrapping_sub((self.len as u16).wrapping_mul(out as u16))
+            .wrapping_add(self.a);
+    }
+
+    pub fn value(&self) -> u32 {
+        ((self.b as u32) << 16) | self.a as u32
+    }
+}
+
+/// Checksums of one window of a file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WindowHash {
+    pub offset: u64,
+    pub weak: u32,
+    pub strong: Hash,
+}
+
+/// Offsets of the windows in `new` that don't match the window at the same offset in `old`,
+/// including windows past the end of `old`
+pub fn changed_windows(old: &[WindowHash], new: &[WindowHash]) -> Vec<u64> {
+    let old: BTreeMap<u64, &WindowHash> = old.iter().map(|w| (w.offset, w)).collect();
+    new.iter()
+        .filter(|window| old.get(&window.offset) != Some(window))
+        .map(|window| window.offset)
+        .collect()
+}
+
+/// A piece of the new content, in order: a window the old copy already has, or bytes it has to
+/// fetch
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeltaOp {
+    /// `len` bytes at `offset` in the new content are the old window at `old_offset`
+    Copy {
+        offset: u64,
+        old_offset: u64,
+        len: u64,
+    },
+    /// `len` bytes at `offset` in the new content match nothing in the old copy
+    Literal { offset: u64, len: u64 },
+}
+
+/// Matches `new` against the window hashes of an old copy, rsync-style: the weak checksum is
+/// rolled one byte at a time over `new`, and a window is only reused when its strong hash agrees
+/// too. Windows that moved, e.g. after an insert, are still found
+pub fn delta(old: &[WindowHash], new: &[u8], window: usize) -> Vec<DeltaOp> {
+    let mut by_weak: BTreeMap<u32, Vec<&WindowHash>> = BTreeMap::new();
+    for hash in old {
+        by_weak.entry(hash.weak).or_default().push(hash);
+    }
+    let find = |weak: u32, bytes: &[u8]| {
+        by_weak.get(&weak).and_then(|candidates| {
+            let strong = Hash::new(bytes);
+            candidates
+                .iter()
+                .find(|hash| hash.strong == strong)
+                .copied()
+        })
+    };
+    let literal = |ops: &mut Vec<DeltaOp>, start: usize, end: usize| {
+        if end > start {
+            ops.push(DeltaOp::Literal {
+                offset: start as u64,
+                len: (end - start) as u64,
+            });
+        }
+    };
+
+    let mut ops = Vec::new();
+    let mut literal_start = 0;
+    let mut pos = 0;
+    let mut rolling =
+        (window > 0 && new.len() >= window).then(|| RollingChecksum::new(&new[..window]));
+    while let Some(checksum) = rolling.as_mut() {
+        if let Some(hash) = find(checksum.value(), &new[pos..pos + window]) {
+            literal(&mut ops, literal_start, pos);
+            ops.push(DeltaOp::Copy {
+                offset: pos as u64,
+                old_offset: hash.offset,
+                len: window as u64,
+            });
+            pos += window;
+            literal_start = pos;
+            rolling =
+                (pos + window <= new.len()).then(|| RollingChecksum::new(&new[pos..pos + window]));
+        } else if pos + window < new.len() {
+            checksum.roll(new[pos], new[pos + window]);
+            pos += 1;
+        } else {
+            rolling = None;
+        }
+    }
+    literal(&mut ops, literal_start, new.len());
+    ops
+}
+
+impl Lis {
+    /// Splits the file into `window`-byte blocks (the last one may be shorter) and returns a weak
+    /// rolling checksum and a strong BLAKE3 hash for each. A peer holding an older copy can
+    /// compare these against its own, or run `delta` over the new content, to fetch only the
+    /// blocks that changed. Only one window is in memory at a time
+    pub async fn rolling_hashes(&self, full_path: &Path, window: usize) -> Result<Vec<WindowHash>> {
+        if window == 0 {
+            return Err(anyhow!("window size must be greater than 0"));
+        }
+        let size = self
+            .obj_from_path(full_path)
+            .ok_or_else(|| LisError::NotFound(full_path.to_path_buf()))?
+            .attrs
+            .size;
+
+        let mut hashes = Vec::new();
+        let mut offset = 0;
+        while offset < size {
+            let chunk = self.read_range(full_path, offset, window).await?;
+            if chunk.is_empty() {
+                break;
+            }
+            hashes.push(WindowHash {
+                offset,
+                weak: RollingChecksum::new(&chunk).value(),
+                strong: Hash::new(&chunk),
+            });
+            offset += chunk.len() as u64;
+        }
+        Ok(hashes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[tokio::test]
+    async fn rolling_hashes_find_changed_window() {
+        let tmp_dir = TempDir::new().unwrap();
+        let mut lis = Lis::new(&tmp_dir.path().to_path_buf(), true).await.unwrap();
+        let file_path = Path::new("/disk.img");
+        let window = 4096;
+        let content: Vec<u8> = (0..window * 16).map(|i| (i % 251) as u8).collect();
+        lis.touch(file_path, None, None, None).await.unwrap();
+        lis.write(file_path, &content, 0).await.unwrap();
+        let before = lis.rolling_hashes(file_path, window).await.unwrap();
+        assert_eq!(before.len(), 16);
+
+        // change a few bytes inside the 6th window
+        lis.write(file_path, b"changed", window * 5 + 100)
+            .await
+            .unwrap();
+        let after = lis.rolling_hashes(file_path, window).await.unwrap();
+
+        assert_eq!(changed_windows(&before, &after), vec![(window * 5) as u64]);
+
+        // rolling the checksum over one byte matches computing it from scratch
+        let mut rolling = RollingChecksum::new(&content[0..window]);
+        rolling.roll(content[0], content[window]);
+        assert_eq!(rolling, RollingChecksum::new(&content[1..window + 1]));
+    }
+
+    #[tokio::test]
+    async fn delta_finds_shifted_windows() {
+        let tmp_dir = TempDir::new().unwrap();
+        let mut lis = Lis::new(&tmp_dir.path().to_path_buf(), true).await.unwrap();
+        let file_path = Path::new("/disk.img");
+        let window = 4096;
+        // not periodic, so each window only matches itself
+        let mut state: u32 = 1;
+        let content: Vec<u8> = (0..window * 16)
+            .map(|_| {
+                state = state.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+                (state >> 16) as u8
+            })
+            .collect();
+        lis.touch(file_path, None, None, None).await.unwrap();
+        lis.write(file_path, &content, 0).await.unwrap();
+        let before = lis.rolling_hashes(file_path, window).await.unwrap();
+
+        // insert one byte inside the 6th window, shifting everything after it
+        let insert_at = window * 5 + 100;
+        let mut new = content.clone();
+        new.insert(insert_at, 0xaa);
+
+        // same-offset comparison sees every later window as changed
+        lis.write(file_path, &new, 0).await.unwrap();
+        let after = lis.rolling_hashes(file_path, window).await.unwrap();
+        assert_eq!(changed_windows(&before, &after).len(), 12);
+
+        // rolling finds them one byte further on, only the broken window is sent
+        let ops = delta(&before, &new, window);
+        let literals: Vec<_> = ops
+            .iter()
+            .filter_map(|op| match op {
+                DeltaOp::Literal { offset, len } => Some((*offset, *len)),
+                DeltaOp::Copy { .. } => None,
+            })
+            .collect();
+        assert_eq!(literals, vec![((window * 5) as u64, window as u64 + 1)]);
+
+        // and the ops rebuild the new content from the old one
+        let mut rebuilt = Vec::new();
+        for op in ops {
+            match op {
+                DeltaOp::Copy {
+                    old_offset, len, ..
+                } => rebuilt
+                    .extend_from_slice(&content[old_offset as usize..(old_offset + len) as usize]),
+                DeltaOp::Literal { offset, len } => {
+                    rebuilt.extend_from_slice(&new[offset as usize..(offset + len) as usize])
+                }
+            }
+        }
+        assert_eq!(rebuilt, new);
+    }
+}
@@ -0,0 +1,361 @@
+use std::{collections::BTreeSet, fmt, str::FromStr};
+
+use futures_lite::StreamExt;
+use iroh::{
+    client::docs::Doc,
+    docs::{store::Query, AuthorId, NamespaceId},
+};
+
+use crate::{
+    fuse::FileKind,
+    manifest::Manifest,
+    prelude::*,
+    util::{is_annotation_key, key_to_string},
+};
+
+/// How far ahead of local time a peer's entries can be before its clock counts as drifted
+const MAX_CLOCK_DRIFT: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CheckStatus {
+    Pass,
+    Warn,
+    Fail,
+}
+
+impl fmt::Display for CheckStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CheckStatus::Pass => write!(f, "PASS"),
+            CheckStatus::Warn => write!(f, "WARN"),
+            CheckStatus::Fail => write!(f, "FAIL"),
+        }
+    }
+}
+
+/// Result of a single `lis doctor` check
+#[derive(Debug, Clone)]
+pub struct Check {
+    pub name: &'static str,
+    pub status: CheckStatus,
+    /// What was found, or how to fix it when the check didn't pass
+    pub detail: String,
+}
+
+impl Check {
+    fn pass(name: &'static str, detail: impl Into<String>) -> Self {
+        Check {
+            name,
+            status: CheckStatus::Pass,
+            detail: detail.into(),
+        }
+    }
+    fn warn(name: &'static str, detail: impl Into<String>) -> Self {
+        Check {
+            name,
+            status: CheckStatus::Warn,
+            detail: detail.into(),
+        }
+    }
+    fn fail(name: &'static str, detail: impl Into<String>) -> Self {
+        Check {
+            name,
+            status: CheckStatus::Fail,
+            detail: detail.into(),
+        }
+    }
+}
+
+impl fmt::Display for Check {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "[{}] {}: {}", self.status, self.name, self.detail)
+    }
+}
+
+impl Lis {
+    /// Runs a set of diagnostics against the node and returns one `Check` per diagnostic
+    pub async fn doctor(&self) -> Vec<Check> {
+        vec![
+            self.check_manifest(),
+            self.check_root_doc().await,
+            self.check_iroh_node().await,
+            self.check_tree().await,
+            self.check_blob_roundtrip().await,
+            self.check_clock_drift().await,
+        ]
+    }
+
+    fn check_manifest(&self) -> Check {
+        let name = "manifest";
+        let path = self.manifest.path();
+        let e = match Manifest::read(path) {
+            Ok(_) => return Check::pass(name, format!("{} parses", path.display())),
+            Err(e) => e,
+        };
+        match Manifest::load(path) {
+            Ok(Some(_)) => Check::warn(
+                name,
+                format!(
+                    "{} can't be read ({e}); the backup next to it is loaded instead, and replaces it on the next save",
+                    path.display()
+                ),
+            ),
+            Ok(None) => Check::fail(
+                name,
+                format!(
+                    "{} is missing; re-create the node with --overwrite",
+                    path.display()
+                ),
+            ),
+            Err(e) => Check::fail(
+                name,
+                format!(
+                    "{} does not parse ({e}); restore it from a backup or re-create the node with --overwrite",
+                    path.display()
+                ),
+            ),
+        }
+    }
+
+    async fn check_root_doc(&self) -> Check {
+        let name = "root doc";
+        let id = match NamespaceId::from_str(&self.manifest.root_doc_id) {
+            Ok(id) => id,
+            Err(e) => {
+                return Check::fail(
+                    name,
+                    format!("invalid root doc id in manifest ({e}); the manifest is damaged"),
+                )
+            }
+        };
+        match self.iroh_node.docs().open(id).await {
+            Ok(Some(_)) => Check::pass(name, format!("{} resolves", id.fmt_short())),
+            Ok(None) => Check::fail(
+                name,
+                format!(
+                    "{} not found in the Iroh store; the manifest points to a doc this node doesn't have",
+                    id.fmt_short()
+                ),
+            ),
+            Err(e) => Check::fail(name, format!("could not open {}: {e}", id.fmt_short())),
+        }
+    }
+
+    async fn check_iroh_node(&self) -> Check {
+        let name = "iroh node";
+        match self.iroh_node.status().await {
+            Ok(status) => Check::pass(
+                name,
+                format!("running as {}", status.addr.node_id.fmt_short()),
+            ),
+            Err(e) => Check::fail(
+                name,
+                format!("node is not responding ({e}); check that no other lis process holds the root dir"),
+            ),
+        }
+    }
+
+    async fn check_tree(&self) -> Check {
+        let name = "tree";
+        let mut visited = BTreeSet::new();
+        let mut problems = Vec::new();
+        visited.insert(PathBuf::from("/"));
+        if let Err(e) = self
+            .check_dir(&self.root_doc, Path::new("/"), &mut visited, &mut problems)
+            .await
+        {
+            return Check::fail(name, format!("could not walk the tree: {e}"));
+        }
+
+        // objects the manifest knows about but that aren't reachable from the root doc
+        for (ino, obj) in &self.manifest.objects {
+            if self.manifest.inodes.get(&obj.full_path) != Some(ino) {
+                problems.push(format!(
+                    "inode {ino} ({}) is not mapped by its path",
+                    obj.full_path.display()
+                ));
+            } else if !visited.contains(&obj.full_path) {
+                problems.push(format!(
+                    "{} (ino={ino}) is in the manifest but not in the tree",
+                    obj.full_path.display()
+                ));
+            }
+        }
+
+        if problems.is_empty() {
+            Check::pass(
+                name,
+                format!("{} objects, no dangling entries", visited.len()),
+            )
+        } else {
+            Check::warn(
+                name,
+                format!(
+                    "{} dangling entries ({}); remove and re-add the affected paths",
+                    problems.len(),
+                    problems.join(", ")
+                ),
+            )
+        }
+    }
+
+    async fn check_dir(
+        &self,
+        doc: &Doc,
+        dir_path: &Path,
+        visited: &mut BTreeSet<PathBuf>,
+        problems: &mut Vec<String>,
+    ) -> Result<()> {
+        let mut entries = doc.get_many(Query::all().build()).await?;
+        while let Some(entry) = entries.next().await {
+            let entry = entry?;
+            if is_annotation_key(entry.key()) {
+                continue;
+            }
+            let relpath = PathBuf::from(key_to_string(entry.key().to_vec().into())?);
+            let full_path = dir_path.join(&relpath);
+
+            let Some(obj) = self.obj_from_path(&full_path) else {
+                problems.push(format!("{} has no inode", full_path.display()));
+                continue;
+            };
+            visited.insert(full_path.clone());
+
+            if let FileKind::Directory = obj.attrs.kind {
+                match self.next_doc(doc, &relpath).await? {
+                    Some(next_doc) => {
+                        Box::pin(self.check_dir(&next_doc, &full_path, visited, problems)).await?
+                    }
+                    None => problems.push(format!("{} has no doc", full_path.display())),
+                }
+            }
+        }
+        Ok(())
+    }
+
+    async fn check_blob_roundtrip(&self) -> Check {
+        let name = "blob round-trip";
+        // unique content so we never delete a blob that belongs to a file
+        let content = format!(
+            "lis doctor {}",
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_nanos()
+        );
+        let blobs = self.iroh_node.blobs();
+        let outcome = match blobs.add_bytes(content.clone()).await {
+            Ok(outcome) => outcome,
+            Err(e) => {
+                return Check::fail(
+                    name,
+                    format!("could not store a blob ({e}); check free disk space and permissions on the root dir"),
+                )
+            }
+        };
+        let read_back = blobs.read_to_bytes(outcome.hash).await;
+        let _ = blobs.delete_blob(outcome.hash).await;
+        match read_back {
+            Ok(bytes) if bytes == content.as_bytes() => {
+                Check::pass(name, "blob stored and read back")
+            }
+            Ok(_) => Check::fail(
+                name,
+                "blob read back with different content; the blob store may be corrupt",
+            ),
+            Err(e) => Check::fail(name, format!("could not read back blob: {e}")),
+        }
+    }
+
+    async fn check_clock_drift(&self) -> Check {
+        let name = "clock drift";
+        match self.root_doc.get_sync_peers().await {
+            Ok(Some(peers)) if !peers.is_empty() => {}
+            Ok(_) => return Check::warn(name, "no peers, nothing to compare against"),
+            Err(e) => return Check::fail(name, format!("could not list peers: {e}")),
+        }
+        let local_authors: BTreeSet<AuthorId> = match self.iroh_node.authors().list().await {
+            Ok(authors) => match authors.try_collect().await {
+                Ok(authors) => authors,
+                Err(e) => return Check::fail(name, format!("could not list authors: {e}")),
+            },
+            Err(e) => return Check::fail(name, format!("could not list authors: {e}")),
+        };
+        let mut entries = match self.root_doc.get_many(Query::all().build()).await {
+            Ok(entries) => entries,
+            Err(e) => return Check::fail(name, format!("could not read the root doc: {e}")),
+        };
+
+        // entry timestamps come from the writer's clock; one in the future means it runs ahead
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_micros() as u64;
+        let mut remote = 0;
+        let mut ahead = 0;
+        while let Some(entry) = entries.next().await {
+            let Ok(entry) = entry else {
+                continue;
+            };
+            if local_authors.contains(&entry.author()) {
+                continue;
+            }
+            remote += 1;
+            ahead = ahead.max(entry.timestamp().saturating_sub(now));
+        }
+
+        let ahead = Duration::from_micros(ahead);
+        if remote == 0 {
+            Check::warn(
+                name,
+                "no entries from peers yet, nothing to compare against",
+            )
+        } else if ahead > MAX_CLOCK_DRIFT {
+            Check::warn(
+                name,
+                format!(
+                    "a peer's entries are {}s ahead of local time; check that NTP runs on this node and its peers",
+                    ahead.as_secs()
+                ),
+            )
+        } else {
+            Check::pass(
+                name,
+                format!(
+                    "{remote} peer entries, none ahead of local time by more than {}s",
+                    MAX_CLOCK_DRIFT.as_secs()
+                ),
+            )
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[tokio::test]
+    async fn doctor_healthy_store() {
+        let tmp_dir = TempDir::new().unwrap();
+        let mut lis = Lis::new(&tmp_dir.path().to_path_buf(), true).await.unwrap();
+
+        lis.mkdir(Path::new("/1"), None, None, None, false)
+            .await
+            .unwrap();
+        lis.touch(Path::new("/1/myfile.txt"), None, None, None)
+            .await
+            .unwrap();
+
+        let checks = lis.doctor().await;
+        for check in &checks {
+            // a lone node has no peer clocks to compare
+            if check.name == "clock drift" {
+                assert_eq!(check.status, CheckStatus::Warn, "{check}");
+                assert!(check.detail.contains("no peers"), "{check}");
+            } else {
+                assert_eq!(check.status, CheckStatus::Pass, "{check}");
+            }
+        }
+    }
+}
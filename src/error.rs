@@ -0,0 +1,122 @@
+use std::{fmt, os::raw::c_int};
+
+use crate::prelude::*;
+
+/// Errors callers may want to tell apart. They travel inside `anyhow::Error`; use
+/// `downcast_ref::<LisError>()` to match on them
+#[derive(Debug)]
+pub enum LisError {
+    /// Path doesn't exist
+    NotFound(PathBuf),
+    /// Path already exists
+    AlreadyExists(PathBuf),
+    /// Directory still has entries
+    NotEmpty(PathBuf),
+    /// Path is a directory but the operation needs something else
+    IsADirectory(PathBuf),
+    /// Path is not a directory but the operation needs one
+    NotADirectory(PathBuf),
+    /// Path is not a symlink but the operation needs one
+    NotASymlink(PathBuf),
+    /// Path has a component that can't be used as a name (empty, `.`, `..`, too long, NUL)
+    InvalidName(PathBuf),
+    /// Directory reached the configured entry limit (`max_dir_entries`)
+    DirectoryFull(PathBuf),
+    /// Opening path would go over the configured open file limit (`max_open_files`)
+    TooManyOpenFiles(PathBuf),
+    /// Node was joined with a read-only ticket
+    ReadOnly(PathBuf),
+    /// Content isn't what a compare-and-write expected, someone else changed it first
+    Conflict(PathBuf),
+}
+
+impl LisError {
+    /// Short machine-readable name of the error kind
+    pub fn kind(&self) -> &'static str {
+        match self {
+            LisError::NotFound(_) => "not_found",
+            LisError::AlreadyExists(_) => "already_exists",
+            LisError::NotEmpty(_) => "not_empty",
+            LisError::IsADirectory(_) => "is_a_directory",
+            LisError::NotADirectory(_) => "not_a_directory",
+            LisError::NotASymlink(_) => "not_a_symlink",
+            LisError::InvalidName(_) => "invalid_name",
+            LisError::DirectoryFull(_) => "directory_full",
+            LisError::TooManyOpenFiles(_) => "too_many_open_files",
+            LisError::ReadOnly(_) => "read_only",
+            LisError::Conflict(_) => "conflict",
+        }
+    }
+
+    /// Path the error is about
+    pub fn path(&self) -> Option<&Path> {
+        match self {
+            LisError::NotFound(path)
+            | LisError::AlreadyExists(path)
+            | LisError::NotEmpty(path)
+            | LisError::IsADirectory(path)
+            | LisError::NotADirectory(path)
+            | LisError::NotASymlink(path)
+            | LisError::InvalidName(path)
+            | LisError::DirectoryFull(path)
+            | LisError::TooManyOpenFiles(path)
+            | LisError::ReadOnly(path)
+            | LisError::Conflict(path) => Some(path),
+        }
+    }
+
+    /// errno to report to FUSE
+    pub fn errno(&self) -> c_int {
+        match self {
+            LisError::NotFound(_) => libc::ENOENT,
+            LisError::AlreadyExists(_) => libc::EEXIST,
+            LisError::NotEmpty(_) => libc::ENOTEMPTY,
+            LisError::IsADirectory(_) => libc::EISDIR,
+            LisError::NotADirectory(_) => libc::ENOTDIR,
+            // what readlink(2) reports
+            LisError::NotASymlink(_) => libc::EINVAL,
+            LisError::InvalidName(_) => libc::EINVAL,
+            // what other filesystems report when a directory index can't grow
+            LisError::DirectoryFull(_) => libc::ENOSPC,
+            // the limit is for the whole mount, not per process (EMFILE)
+            LisError::TooManyOpenFiles(_) => libc::ENFILE,
+            LisError::ReadOnly(_) => libc::EROFS,
+            // read it again and retry
+            LisError::Conflict(_) => libc::EAGAIN,
+        }
+    }
+}
+
+impl fmt::Display for LisError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LisError::NotFound(path) => write!(f, "No such file or directory: {}", path.display()),
+            LisError::AlreadyExists(path) => write!(f, "{} already exists", path.display()),
+            LisError::NotEmpty(path) => write!(f, "Directory not empty: {}", path.display()),
+            LisError::IsADirectory(path) => write!(f, "{} is a directory", path.display()),
+            LisError::NotADirectory(path) => write!(f, "{} is not a directory", path.display()),
+            LisError::NotASymlink(path) => write!(f, "{} is not a symlink", path.display()),
+            LisError::InvalidName(path) => write!(f, "Invalid name in path: {}", path.display()),
+            LisError::DirectoryFull(path) => write!(
+                f,
+                "Directory full: {} (raise max_dir_entries in lis.toml, or split it into subdirectories)",
+                path.display()
+            ),
+            LisError::TooManyOpenFiles(path) => write!(
+                f,
+                "Too many open files, can't open {} (raise max_open_files in lis.toml)",
+                path.display()
+            ),
+            LisError::ReadOnly(path) => write!(
+                f,
+                "Can't change {}, joined with a read-only ticket",
+                path.display()
+            ),
+            LisError::Conflict(path) => {
+                write!(f, "{} changed since it was last read", path.display())
+            }
+        }
+    }
+}
+
+impl std::error::Error for LisError {}
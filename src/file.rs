@@ -0,0 +1,195 @@
+use std::io::{self, Read, Seek, SeekFrom, Write};
+
+use crate::prelude::*;
+
+/// Blocking `Read + Seek` handle over a file in Lis, for libraries that want `std::io` types
+/// Each `read` fetches only the range it asks for. Async calls are driven with
+/// `lis.rt.block_on`, the same way the FUSE callbacks do it, so this needs a Tokio runtime
+/// context and must not be used from inside an async task (wrap it in `spawn_blocking`)
+pub struct LisFileReader<'a> {
+    lis: &'a Lis,
+    path: PathBuf,
+    pos: u64,
+}
+
+impl<'a> LisFileReader<'a> {
+    pub fn new(lis: &'a Lis, path: &Path) -> Self {
+        LisFileReader {
+            lis,
+            path: path.to_path_buf(),
+            pos: 0,
+        }
+    }
+}
+
+impl Read for LisFileReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let handle = self.lis.rt.clone();
+        let chunk = handle
+            .block_on(self.lis.read_range(&self.path, self.pos, buf.len()))
+            .map_err(io::Error::other)?;
+        buf[..chunk.len()].copy_from_slice(&chunk);
+        self.pos += chunk.len() as u64;
+        Ok(chunk.len())
+    }
+}
+
+impl Seek for LisFileReader<'_> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let new_pos = match pos {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::Current(offset) => self.pos.checked_add_signed(offset),
+            SeekFrom::End(offset) => {
+                let len = self
+                    .lis
+                    .obj_from_path(&self.path)
+                    .ok_or_else(|| io::Error::from(io::ErrorKind::NotFound))?
+                    .attrs
+                    .size;
+                len.checked_add_signed(offset)
+            }
+        };
+        self.pos = new_pos.ok_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidInput, "seek to a negative position")
+        })?;
+        Ok(self.pos)
+    }
+}
+
+/// Blocking `Write + Seek` handle over an existing file in Lis
+/// Contiguous writes are buffered and stored on `flush`, `seek` or drop. Same runtime
+/// requirements as `LisFileReader`
+pub struct LisFileWriter<'a> {
+    lis: &'a mut Lis,
+    path: PathBuf,
+    pos: u64,
+    pending: Vec<u8>,
+    pending_offset: u64,
+}
+
+impl<'a> LisFileWriter<'a> {
+    pub fn new(lis: &'a mut Lis, path: &Path) -> Self {
+        LisFileWriter {
+            lis,
+            path: path.to_path_buf(),
+            pos: 0,
+            pending: Vec::new(),
+            pending_offset: 0,
+        }
+    }
+
+    fn flush_pending(&mut self) -> io::Result<()> {
+        if self.pending.is_empty() {
+            return Ok(());
+        }
+
+        let handle = self.lis.rt.clone();
+        handle
+            .block_on(
+                self.lis
+                    .write(&self.path, &self.pending, self.pending_offset as usize),
+            )
+            .map_err(io::Error::other)?;
+
+        self.pending.clear();
+        Ok(())
+    }
+}
+
+impl Write for LisFileWriter<'_> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.pending_offset + self.pending.len() as u64 != self.pos {
+            self.flush_pending()?;
+            self.pending_offset = self.pos;
+        }
+        self.pending.extend_from_slice(buf);
+        self.pos += buf.len() as u64;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.flush_pending()
+    }
+}
+
+impl Seek for LisFileWriter<'_> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.flush_pending()?;
+        let new_pos = match pos {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::Current(offset) => self.pos.checked_add_signed(offset),
+            SeekFrom::End(offset) => {
+                let len = self
+                    .lis
+                    .obj_from_path(&self.path)
+                    .ok_or_else(|| io::Error::from(io::ErrorKind::NotFound))?
+                    .attrs
+                    .size;
+                len.checked_add_signed(offset)
+            }
+        };
+        self.pos = new_pos.ok_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidInput, "seek to a negative position")
+        })?;
+        Ok(self.pos)
+    }
+}
+
+impl Drop for LisFileWriter<'_> {
+    fn drop(&mut self) {
+        if let Err(e) = self.flush_pending() {
+            error!("Could not flush {}: {e}", self.path.display());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader};
+    use tempfile::TempDir;
+
+    #[tokio::test]
+    async fn read_write_through_std_io() {
+        let tmp_dir = TempDir::new().unwrap();
+        let mut lis = Lis::new(&tmp_dir.path().to_path_buf(), true).await.unwrap();
+        let file_path = Path::new("/myfile.txt");
+        lis.touch(file_path, None, None, None).await.unwrap();
+
+        // std::io calls block, so keep them off the runtime's threads
+        let (lines, tail) = tokio::task::spawn_blocking(move || {
+            {
+                let mut writer = LisFileWriter::new(&mut lis, file_path);
+                writer.write_all(b"first line\n").unwrap();
+                writer.write_all(b"second line\n").unwrap();
+                writer.flush().unwrap();
+            }
+
+            let mut reader = LisFileReader::new(&lis, file_path);
+            reader.seek(SeekFrom::Start(0)).unwrap();
+            let lines = BufReader::new(reader)
+                .lines()
+                .collect::<io::Result<Vec<_>>>()
+                .unwrap();
+
+            // a small buffer still reads all of it, one range at a time
+            let mut reader = LisFileReader::new(&lis, file_path);
+            reader.seek(SeekFrom::End(-5)).unwrap();
+            let mut buf = [0; 2];
+            let mut read = Vec::new();
+            loop {
+                let n = reader.read(&mut buf).unwrap();
+                if n == 0 {
+                    break;
+                }
+                read.extend_from_slice(&buf[..n]);
+            }
+            (lines, read)
+        })
+        .await
+        .unwrap();
+
+        assert_eq!(lines, vec!["first line", "second line"]);
+        assert_eq!(tail, b"line\n");
+    }
+}
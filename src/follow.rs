@@ -0,0 +1,151 @@
+use bytes::Bytes;
+use futures_lite::{stream::Boxed, Stream, StreamExt};
+use iroh::{
+    client::{
+        blobs,
+        docs::{Doc, LiveEvent},
+    },
+    docs::store::Query,
+};
+
+use crate::{prelude::*, LisError};
+
+/// Where a follower is, and what it waits on for changes
+struct Follower<E> {
+    doc: Doc,
+    key: Vec<u8>,
+    blobs: blobs::Client,
+    events: E,
+    offset: u64,
+    done: bool,
+}
+
+impl<E: Stream<Item = Result<LiveEvent>> + Unpin> Follower<E> {
+    /// Bytes past `offset` in the file's current content, if it grew
+    /// A file that got shorter was truncated or replaced, so it's read again from the start
+    async fn appended(&mut self) -> Result<Option<Bytes>> {
+        let Some(entry) = self.doc.get_one(Query::key_exact(&self.key)).await? else {
+            // removed, or in the middle of being rewritten
+            return Ok(None);
+        };
+        // a remote entry's content may not be here yet, ContentReady comes when it is
+        if !self.blobs.has(entry.content_hash()).await? {
+            return Ok(None);
+        }
+        let len = entry.content_len();
+        if len < self.offset {
+            self.offset = 0;
+        }
+        if len == self.offset {
+            return Ok(None);
+        }
+        let bytes = self
+            .blobs
+            .read_at_to_bytes(
+                entry.content_hash(),
+                self.offset,
+                Some((len - self.offset) as usize),
+            )
+            .await?;
+        self.offset = len;
+        Ok(Some(bytes))
+    }
+
+    /// Waits until the file might have changed. `false` once there won't be more events
+    async fn changed(&mut self) -> Result<bool> {
+        while let Some(event) = self.events.next().await {
+            match event? {
+                LiveEvent::InsertLocal { entry } | LiveEvent::InsertRemote { entry, .. }
+                    if entry.key() == self.key =>
+                {
+                    return Ok(true)
+                }
+                LiveEvent::ContentReady { .. } => return Ok(true),
+                _ => {}
+            }
+        }
+        Ok(false)
+    }
+
+    async fn next(&mut self) -> Option<Result<Bytes>> {
+        if self.done {
+            return None;
+        }
+        loop {
+            match self.appended().await {
+                Ok(Some(bytes)) => return Some(Ok(bytes)),
+                Ok(None) => {}
+                Err(e) => {
+                    self.done = true;
+                    return Some(Err(e));
+                }
+            }
+            match self.changed().await {
+                Ok(true) => {}
+                Ok(false) => return None,
+                Err(e) => {
+                    self.done = true;
+                    return Some(Err(e));
+                }
+            }
+        }
+    }
+}
+
+impl Lis {
+    /// Streams what gets appended to `full_path` past `offset`, as it's written here or synced
+    /// from a peer, like `tail -f`. When the file shrinks it was truncated or rotated, and the
+    /// new content is streamed from its start
+    /// The stream doesn't borrow `self`, so the file can be written while it's followed
+    pub async fn follow(&self, full_path: &Path, offset: u64) -> Result<Boxed<Result<Bytes>>> {
+        if self.is_dir(full_path) {
+            return Err(LisError::IsADirectory(full_path.to_path_buf()).into());
+        }
+        let (doc, key) = self.doc_and_key(full_path).await?;
+        // subscribed before the first look, so nothing written in between is missed
+        let events = Box::pin(doc.subscribe().await?);
+        let follower = Follower {
+            doc,
+            key: key.to_vec(),
+            blobs: self.iroh_node.blobs().clone(),
+            events,
+            offset,
+            done: false,
+        };
+        Ok(
+            futures_lite::stream::unfold(follower, |mut follower| async move {
+                let next = follower.next().await?;
+                Some((next, follower))
+            })
+            .boxed(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[tokio::test]
+    async fn follow_appends_and_truncation() {
+        let tmp_dir = TempDir::new().unwrap();
+        let mut lis = Lis::new(&tmp_dir.path().to_path_buf(), true).await.unwrap();
+
+        let file_path = Path::new("/app.log");
+        lis.touch(file_path, None, None, None).await.unwrap();
+        lis.write(file_path, b"first\n", 0).await.unwrap();
+
+        let mut follower = lis.follow(file_path, 6).await.unwrap();
+        lis.write(file_path, b"second\n", 6).await.unwrap();
+        assert_eq!(follower.next().await.unwrap().unwrap(), "second\n");
+        lis.write(file_path, b"third\n", 13).await.unwrap();
+        assert_eq!(follower.next().await.unwrap().unwrap(), "third\n");
+
+        // rotated: replaced by something shorter
+        let (doc, key) = lis.doc_and_key(file_path).await.unwrap();
+        let author = lis.iroh_node.authors().default().await.unwrap();
+        doc.set_bytes(author, key, "new\n").await.unwrap();
+        assert_eq!(follower.next().await.unwrap().unwrap(), "new\n");
+    }
+}
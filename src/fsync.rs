@@ -0,0 +1,178 @@
+use std::fmt;
+
+use futures_lite::StreamExt;
+use iroh::{
+    blobs::{store::ConsistencyCheckProgress, util::SetTagOption, Hash},
+    docs::store::Query,
+};
+
+use crate::{prelude::*, LisError};
+
+/// Tag keeping the last checkpoint's tree, replaced by the next one
+const CHECKPOINT_TAG: &str = "lis-checkpoint";
+
+/// A point the whole tree was saved at by `Lis::checkpoint`. It's the hash of the collection
+/// holding the tree, so `import_collection` can bring it back
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CheckpointId(pub Hash);
+
+impl fmt::Display for CheckpointId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl Lis {
+    /// Makes sure what was written to `full_path` is on disk
+    /// Iroh batches writes to its doc and blob stores in transactions it commits on its own
+    /// shortly after. Reading the file's entry commits the doc store's, see `commit_blobs` for
+    /// the blob store's
+    pub async fn sync_file(&self, full_path: &Path) -> Result<()> {
+        if full_path == Path::new("/") {
+            self.iroh_node.docs().list().await?.count().await;
+        } else {
+            let (doc, key) = self.doc_and_key(full_path).await?;
+            doc.get_one(Query::key_exact(key))
+                .await?
+                .ok_or_else(|| LisError::NotFound(full_path.to_path_buf()))?;
+        }
+        self.commit_blobs().await
+    }
+
+    /// Makes sure everything written so far, content and metadata, is on disk
+    pub async fn sync_all(&self) -> Result<()> {
+        self.sync_file(Path::new("/")).await?;
+        self.manifest.save()
+    }
+
+    /// Makes everything written so far durable and records the tree as it is now, before a
+    /// shutdown or a backup. The returned id is also kept in the manifest as the last checkpoint
+    /// Only the last checkpoint keeps its content from being garbage collected, what earlier ones
+    /// hold that's no longer in the tree can go
+    pub async fn checkpoint(&mut self) -> Result<CheckpointId> {
+        let tag = SetTagOption::Named(CHECKPOINT_TAG.into());
+        let id = CheckpointId(self.export_collection_tagged(tag).await?);
+        self.manifest.checkpoint = Some(id);
+        self.sync_all().await?;
+        Ok(id)
+    }
+
+    /// Commits the blob store's pending writes
+    /// The client has no way to ask for that directly, but a consistency check (without repair)
+    /// runs outside of write transactions, so the open one is committed first. The check itself
+    /// goes over the store's metadata, which grows with the number of blobs
+    async fn commit_blobs(&self) -> Result<()> {
+        let mut progress = self.iroh_node.blobs().consistency_check(false).await?;
+        while let Some(update) = progress.next().await {
+            if let ConsistencyCheckProgress::Abort(e) = update? {
+                return Err(anyhow!("Could not commit the blob store: {e}"));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use iroh::blobs::Tag;
+    use tempfile::TempDir;
+
+    /// Copies the node's files while it's still running, like a crash would leave them
+    fn copy_dir(from: &Path, to: &Path) {
+        std::fs::create_dir_all(to).unwrap();
+        for entry in std::fs::read_dir(from).unwrap() {
+            let entry = entry.unwrap();
+            let dst = to.join(entry.file_name());
+            if entry.file_type().unwrap().is_dir() {
+                copy_dir(&entry.path(), &dst);
+            } else {
+                std::fs::copy(entry.path(), dst).unwrap();
+            }
+        }
+    }
+
+    /// Names and hashes of the node's tags
+    async fn tags(lis: &Lis) -> Vec<(Tag, Hash)> {
+        let mut tags = Vec::new();
+        let mut list = lis.iroh_node.tags().list().await.unwrap();
+        while let Some(tag) = list.next().await {
+            let tag = tag.unwrap();
+            tags.push((tag.name, tag.hash));
+        }
+        tags
+    }
+
+    #[tokio::test]
+    async fn sync_file_survives_crash() {
+        let tmp_dir = TempDir::new().unwrap();
+        let root = tmp_dir.path().join("root");
+        let mut lis = Lis::new(&root, true).await.unwrap();
+
+        let file_path = Path::new("/dir/file.txt");
+        lis.mkdir(Path::new("/dir"), None, None, None, false)
+            .await
+            .unwrap();
+        lis.touch(file_path, None, None, None).await.unwrap();
+        lis.write(file_path, b"made it to disk", 0).await.unwrap();
+        lis.sync_file(file_path).await.unwrap();
+        lis.sync_file(Path::new("/dir")).await.unwrap();
+
+        let crashed = tmp_dir.path().join("crashed");
+        copy_dir(&root, &crashed);
+        let mut reopened = Lis::new(&crashed, false).await.unwrap();
+        assert_eq!(reopened.read(file_path).await.unwrap(), "made it to disk");
+
+        let missing = lis.sync_file(Path::new("/missing")).await.unwrap_err();
+        assert!(matches!(
+            missing.downcast_ref::<LisError>(),
+            Some(LisError::NotFound(_))
+        ));
+        lis.sync_all().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn checkpoint_survives_crash() {
+        let tmp_dir = TempDir::new().unwrap();
+        let root = tmp_dir.path().join("root");
+        let mut lis = Lis::new(&root, true).await.unwrap();
+
+        let file_path = Path::new("/dir/file.txt");
+        lis.mkdir(Path::new("/dir"), None, None, None, false)
+            .await
+            .unwrap();
+        lis.touch(file_path, None, None, None).await.unwrap();
+        lis.write(file_path, b"checkpointed", 0).await.unwrap();
+        let id = lis.checkpoint().await.unwrap();
+
+        let crashed = tmp_dir.path().join("crashed");
+        copy_dir(&root, &crashed);
+        let mut reopened = Lis::new(&crashed, false).await.unwrap();
+        assert_eq!(reopened.manifest.checkpoint, Some(id));
+        assert!(reopened.is_dir(Path::new("/dir")));
+        assert_eq!(reopened.read(file_path).await.unwrap(), "checkpointed");
+
+        // the checkpoint brings the tree back after it changed
+        reopened.write(file_path, b"CHECK", 0).await.unwrap();
+        reopened.import_collection(id.0).await.unwrap();
+        assert_eq!(reopened.read(file_path).await.unwrap(), "checkpointed");
+    }
+
+    #[tokio::test]
+    async fn checkpoint_replaces_its_tag() {
+        let tmp_dir = TempDir::new().unwrap();
+        let mut lis = Lis::new(&tmp_dir.path().to_path_buf(), true).await.unwrap();
+        let file_path = Path::new("/file.txt");
+        lis.touch(file_path, None, None, None).await.unwrap();
+
+        lis.checkpoint().await.unwrap();
+        let after_first = tags(&lis).await;
+        lis.write(file_path, b"changed", 0).await.unwrap();
+        let id = lis.checkpoint().await.unwrap();
+
+        // the second checkpoint took over the first one's tag
+        let after_second = tags(&lis).await;
+        assert_eq!(after_second.len(), after_first.len());
+        assert!(after_second.contains(&(Tag::from(CHECKPOINT_TAG), id.0)));
+    }
+}
@@ -4,6 +4,7 @@ use std::{
     ffi::OsStr,
     os::{
         fd::AsRawFd,
+        raw::c_int,
         unix::{ffi::OsStrExt, fs::FileExt, io::IntoRawFd},
     },
     path::{Path, PathBuf},
@@ -12,6 +13,7 @@ use std::{
 
 use fuser::TimeOrNow;
 use fuser::TimeOrNow::Now;
+use fuser::{consts, KernelConfig, ReplyDirectoryPlus};
 use tokio::{
     fs::File,
     io::{AsyncBufReadExt, BufReader},
@@ -19,12 +21,32 @@ use tokio::{
 
 use crate::{
     prelude::*,
-    util::{key_from_file, key_to_string},
+    util::{errno_from_error, key_from_file},
+    FuseCounters,
 };
 
 impl fuser::Filesystem for Lis {
+    fn init(&mut self, _req: &Request<'_>, config: &mut KernelConfig) -> Result<(), c_int> {
+        // entries come with their attributes, so `ls -l` doesn't need a lookup per entry
+        if let Err(unsupported) = config.add_capabilities(consts::FUSE_DO_READDIRPLUS) {
+            debug!("Kernel doesn't support readdirplus ({unsupported:#x})");
+        }
+        Ok(())
+    }
+
+    fn destroy(&mut self) {
+        debug!("destroy()");
+        let handle = self.rt.clone();
+        match handle.block_on(self.checkpoint()) {
+            Ok(id) => info!("Checkpointed at {id}"),
+            Err(e) => error!("Could not checkpoint on unmount: {e}"),
+        }
+    }
+
     fn lookup(&mut self, req: &Request<'_>, parent: Inode, name: &OsStr, reply: fuser::ReplyEntry) {
         debug!("lookup(parent={parent}, name={:#?})", name);
+        self.catch_up_with_peers();
+        FuseCounters::bump(&self.fuse_counters.lookups);
         if name.len() > MAX_NAME_LENGTH as usize {
             reply.error(libc::ENAMETOOLONG);
             return;
@@ -61,6 +83,8 @@ impl fuser::Filesystem for Lis {
 
     fn getattr(&mut self, _req: &Request, ino: u64, reply: ReplyAttr) {
         debug!("getattr(ino={ino})");
+        self.catch_up_with_peers();
+        FuseCounters::bump(&self.fuse_counters.getattrs);
         match self.manifest.objects.get(&ino) {
             Some(obj) => reply.attr(&Duration::new(1, 0), &obj.attrs.clone().into()),
             None => reply.error(ENOSYS),
@@ -69,6 +93,7 @@ impl fuser::Filesystem for Lis {
 
     fn open(&mut self, req: &Request, ino: u64, flags: i32, reply: ReplyOpen) {
         debug!("open(ino={ino})");
+        self.catch_up_with_peers();
         let (access_mask, read, write) = match flags & libc::O_ACCMODE {
             libc::O_RDONLY => {
                 // Behavior is undefined, but most filesystems return EACCES
@@ -103,14 +128,24 @@ impl fuser::Filesystem for Lis {
                     req.gid(),
                     access_mask,
                 ) {
+                    if let Err(e) = self.check_open_files(&obj.full_path) {
+                        warn!("{e}");
+                        reply.error(e.errno());
+                        return;
+                    }
                     attrs.open_file_handles += 1;
                     if let Err(e) = self.write_inode(&attrs) {
                         error!("{e}");
                         reply.error(libc::ENOENT);
                         return;
                     }
-                    let open_flags = fuser::consts::FOPEN_DIRECT_IO;
-                    reply.opened(self.next_file_handle(read, write), open_flags);
+                    self.open_files += 1;
+                    let mut fh = self.next_file_handle(read, write);
+                    if flags & libc::O_NOATIME != 0 {
+                        fh |= FILE_HANDLE_NOATIME_BIT;
+                    }
+                    let keep_cache = !self.direct_io && self.content_unchanged(ino);
+                    reply.opened(fh, open_flags(self.direct_io, keep_cache));
                 } else {
                     reply.error(libc::EACCES);
                 }
@@ -129,7 +164,9 @@ impl fuser::Filesystem for Lis {
         _flush: bool,
         reply: ReplyEmpty,
     ) {
+        self.open_files = self.open_files.saturating_sub(1);
         if let Some(obj) = self.manifest.objects.get(&ino) {
+            let full_path = obj.full_path.clone();
             let mut attrs = obj.attrs.clone();
             attrs.open_file_handles -= 1;
             if let Err(e) = self.write_inode(&attrs) {
@@ -137,6 +174,13 @@ impl fuser::Filesystem for Lis {
                 reply.error(libc::ENOENT);
                 return;
             }
+            // hash what was written once, when the last handle goes away
+            if attrs.open_file_handles == 0 {
+                let handle = self.rt.clone();
+                if let Err(e) = handle.block_on(self.refresh_hash(&full_path)) {
+                    warn!("Could not hash {}: {e}", full_path.display());
+                }
+            }
         }
         reply.ok();
     }
@@ -172,7 +216,7 @@ impl fuser::Filesystem for Lis {
                     Ok(entry) => entry.key() == new_path_key,
                     Err(_) => false,
                 });
-                if already_present == true {
+                if already_present {
                     reply.error(libc::EEXIST);
                     return;
                 }
@@ -196,6 +240,11 @@ impl fuser::Filesystem for Lis {
         };
 
         let full_path = parent_path.join(name);
+        if let Err(e) = self.check_open_files(&full_path) {
+            warn!("{e}");
+            reply.error(e.errno());
+            return;
+        }
         if !check_access(
             parent_attrs.uid,
             parent_attrs.gid,
@@ -212,7 +261,7 @@ impl fuser::Filesystem for Lis {
         let gid = creation_gid(&parent_attrs, req.gid());
 
         if uid != 0 {
-            mode &= !(libc::S_ISUID | libc::S_ISGID) as u32;
+            mode &= !(libc::S_ISUID | libc::S_ISGID);
         }
 
         // Create new file on lis
@@ -220,7 +269,7 @@ impl fuser::Filesystem for Lis {
             handle.block_on(self.touch(&full_path, Some(mode as u16), Some(uid), Some(gid)))
         {
             error!("Could not put file on lis: {e}");
-            reply.error(libc::ENOENT);
+            reply.error(errno_from_error(&e, libc::ENOENT));
             return;
         }
 
@@ -248,6 +297,7 @@ impl fuser::Filesystem for Lis {
             reply.error(libc::ENOENT);
             return;
         }
+        self.open_files += 1;
 
         reply.created(
             &Duration::new(0, 0),
@@ -276,7 +326,7 @@ impl fuser::Filesystem for Lis {
             .get_full_path(parent, name)
             .expect("could not get full file name");
 
-        let mut attrs = match self.obj_from_path(&full_path) {
+        let attrs = match self.obj_from_path(&full_path) {
             Some(obj) => obj.attrs.clone(),
             None => {
                 error!("Could not find newly created dir {}", full_path.display());
@@ -308,9 +358,10 @@ impl fuser::Filesystem for Lis {
             return;
         }
 
-        if handle.block_on(self.remove(&full_path)).is_err() {
-            error!("Could not remove from lis");
-            reply.error(libc::ENOENT);
+        // also releases the inode once it's closed
+        if let Err(e) = handle.block_on(self.remove(&full_path)) {
+            error!("Could not remove {}: {e}", full_path.display());
+            reply.error(errno_from_error(&e, libc::ENOENT));
             return;
         }
 
@@ -322,20 +373,6 @@ impl fuser::Filesystem for Lis {
             return;
         }
 
-        attrs.hardlinks -= 1;
-        attrs.last_metadata_changed = SystemTime::now();
-        if let Err(e) = self.write_inode(&attrs) {
-            error!("{e}");
-            reply.error(libc::ENOENT);
-            return;
-        }
-
-        if let Err(e) = self.gc_inode(&attrs) {
-            error!("{e}");
-            reply.error(libc::ENOENT);
-            return;
-        }
-
         reply.ok();
     }
 
@@ -381,7 +418,7 @@ impl fuser::Filesystem for Lis {
             {
                 // If SGID is set and the file belongs to a group that the caller is not part of
                 // then the SGID bit is suppose to be cleared during chmod
-                attrs.mode = (mode & !libc::S_ISGID as u32) as u16;
+                attrs.mode = (mode & !libc::S_ISGID) as u16;
             } else {
                 attrs.mode = mode as u16;
             }
@@ -453,20 +490,22 @@ impl fuser::Filesystem for Lis {
                 // This is important as it preserves the semantic that a file handle opened
                 // with W_OK will never fail to truncate, even if the file has been subsequently
                 // chmod'ed
-                if check_file_handle_write(file_handle) {
-                    if let Err(error_code) = handle.block_on(self.truncate(ino, size, 0, 0)) {
-                        reply.error(error_code);
-                        return;
-                    }
-                } else {
+                if !check_file_handle_write(file_handle) {
                     reply.error(libc::EACCES);
                     return;
                 }
-            } else if let Err(error_code) =
-                handle.block_on(self.truncate(ino, size, req.uid(), req.gid()))
-            {
-                reply.error(error_code);
-                return;
+            }
+            let (uid, gid) = match fh {
+                Some(_) => (0, 0),
+                None => (req.uid(), req.gid()),
+            };
+            // the attributes saved below have to carry the new size
+            match handle.block_on(self.truncate(ino, size, uid, gid)) {
+                Ok(truncated) => attrs = truncated,
+                Err(error_code) => {
+                    reply.error(error_code);
+                    return;
+                }
             }
         }
 
@@ -550,6 +589,7 @@ impl fuser::Filesystem for Lis {
 
     fn opendir(&mut self, req: &Request, ino: u64, flags: i32, reply: ReplyOpen) {
         debug!("opendir() called on {:?}", ino);
+        self.catch_up_with_peers();
         let (access_mask, read, write) = match flags & libc::O_ACCMODE {
             libc::O_RDONLY => {
                 // Behavior is undefined, but most filesystems return EACCES
@@ -590,7 +630,6 @@ impl fuser::Filesystem for Lis {
                 } else {
                     reply.error(libc::EACCES);
                 }
-                return;
             }
             None => reply.error(libc::ENOENT),
         }
@@ -604,61 +643,102 @@ impl fuser::Filesystem for Lis {
         mut reply: ReplyDirectory,
     ) {
         debug!("readdir(ino={}, fh={}, offset={})", ino, fh, offset);
+        self.catch_up_with_peers();
         assert!(offset >= 0);
 
         let handle = self.rt.clone();
 
-        if offset != 0 {
-            reply.ok();
+        let Some(obj) = self.manifest.objects.get(&ino) else {
+            error!("Cannot find object at inode {ino}");
+            reply.error(libc::ENOENT);
             return;
-        }
+        };
+        let listed = match handle.block_on(self.list_with_metadata(&obj.full_path)) {
+            Ok(listed) => listed,
+            Err(e) => {
+                error!("Cannot list {}: {e}", obj.full_path.display());
+                reply.error(errno_from_error(&e, libc::EIO));
+                return;
+            }
+        };
 
-        if let Some(obj) = self.manifest.objects.get(&ino) {
-            let _ = reply.add(ino, 0, FileType::Directory, &Path::new("."));
-            let _ = reply.add(ino, 1, FileType::Directory, &Path::new(".."));
-            let entries = handle
-                .block_on(self.list(&obj.full_path))
-                .expect("could not list dir");
-
-            for (index, entry) in entries.into_iter().enumerate() {
-                if let Ok(entry) = entry {
-                    let skey = key_to_string(entry.key().to_vec().into())
-                        .expect("Could not go from key to string");
-                    let relpath = PathBuf::from(skey);
-                    let full_entry_path = obj.full_path.join(relpath.clone());
-
-                    match self.obj_from_path(&full_entry_path) {
-                        Some(obj) => {
-                            let _ = reply.add(
-                                obj.attrs.inode,
-                                offset + index as i64 + 1,
-                                obj.attrs.kind.into(),
-                                &relpath.clone(),
-                            );
-                        }
-                        None => {
-                            let entry_ino = self
-                                .manifest
-                                .inodes
-                                .get(&full_entry_path.to_path_buf())
-                                .unwrap_or(&0);
-                            error!(
-                                "Cannot find object from path {} (ino={})",
-                                full_entry_path.display(),
-                                entry_ino
-                            );
-                            debug!("{:#?}", self.manifest);
-                            reply.error(ENOSYS);
-                            return;
-                        }
-                    }
-                }
+        let mut entries = vec![
+            (PathBuf::from("."), ino, FileKind::Directory),
+            (PathBuf::from(".."), ino, FileKind::Directory),
+        ];
+        entries.extend(
+            listed
+                .into_iter()
+                .map(|(name, metadata)| (name, metadata.inode, metadata.kind)),
+        );
+
+        // offsets are where the next call picks up
+        for (index, (name, entry_ino, kind)) in
+            entries.into_iter().enumerate().skip(offset as usize)
+        {
+            if reply.add(entry_ino, index as i64 + 1, kind.into(), name) {
+                // buffer full
+                break;
             }
-            reply.ok();
-        } else {
+        }
+        reply.ok();
+    }
+
+    fn readdirplus(
+        &mut self,
+        _req: &Request,
+        ino: u64,
+        fh: u64,
+        offset: i64,
+        mut reply: ReplyDirectoryPlus,
+    ) {
+        debug!("readdirplus(ino={}, fh={}, offset={})", ino, fh, offset);
+        self.catch_up_with_peers();
+        assert!(offset >= 0);
+
+        let handle = self.rt.clone();
+
+        let Some(obj) = self.manifest.objects.get(&ino) else {
             error!("Cannot find object at inode {ino}");
-            reply.error(ENOSYS);
+            reply.error(libc::ENOENT);
+            return;
+        };
+        let listed = match handle.block_on(self.list_with_metadata(&obj.full_path)) {
+            Ok(listed) => listed,
+            Err(e) => {
+                error!("Cannot list {}: {e}", obj.full_path.display());
+                reply.error(errno_from_error(&e, libc::EIO));
+                return;
+            }
+        };
+
+        // the kernel ignores the attributes of `.` and `..`
+        let mut entries = vec![
+            (PathBuf::from("."), obj.attrs.clone()),
+            (PathBuf::from(".."), obj.attrs.clone()),
+        ];
+        for (name, metadata) in listed {
+            if let Some(entry) = self.manifest.objects.get(&metadata.inode) {
+                entries.push((name, entry.attrs.clone()));
+            }
+        }
+
+        for (index, (name, attrs)) in entries.into_iter().enumerate().skip(offset as usize) {
+            let entry_ino = attrs.inode;
+            if reply.add(
+                entry_ino,
+                index as i64 + 1,
+                name,
+                // same as getattr, so stat right after the listing is answered by the kernel
+                &Duration::new(1, 0),
+                &attrs.into(),
+                0,
+            ) {
+                // buffer full
+                break;
+            }
         }
+        reply.ok();
     }
 
     fn rmdir(&mut self, req: &Request, parent: u64, name: &OsStr, reply: ReplyEmpty) {
@@ -670,10 +750,10 @@ impl fuser::Filesystem for Lis {
             .get_full_path(parent, name)
             .expect("could not get full file name");
 
-        let mut attrs = match self.obj_from_path(&full_path) {
+        let attrs = match self.obj_from_path(&full_path) {
             Some(obj) => obj.attrs.clone(),
             None => {
-                error!("Could not find newly created dir {}", full_path.display());
+                error!("Could not find dir {}", full_path.display());
                 reply.error(libc::ENOENT);
                 return;
             }
@@ -688,14 +768,6 @@ impl fuser::Filesystem for Lis {
             }
         };
 
-        // lis rmdir
-        // TODO: other error type for when trying to rm root dir
-        if let Err(e) = handle.block_on(self.rmdir(&full_path)) {
-            error!("Unable to rmdir {}: {e}", full_path.display());
-            reply.error(libc::ENOTEMPTY);
-            return;
-        }
-
         if !check_access(
             parent_attrs.uid,
             parent_attrs.gid,
@@ -718,22 +790,135 @@ impl fuser::Filesystem for Lis {
             return;
         }
 
+        // lis rmdir, also releases the inode
+        if let Err(e) = handle.block_on(self.rmdir(&full_path)) {
+            error!("Unable to rmdir {}: {e}", full_path.display());
+            reply.error(errno_from_error(&e, libc::EBUSY));
+            return;
+        }
+
         parent_attrs.last_metadata_changed = SystemTime::now();
         parent_attrs.last_modified = SystemTime::now();
         if let Err(e) = self.write_inode(&parent_attrs) {
-            error!("Could not create dir {}: {e}", full_path.display());
+            error!("Could not update dir {}: {e}", parent_attrs.inode);
             reply.error(libc::ENOENT);
             return;
         }
 
-        attrs.hardlinks = 0;
-        attrs.last_metadata_changed = SystemTime::now();
-        if let Err(e) = self.write_inode(&attrs) {
-            error!("Could not create dir {}: {e}", full_path.display());
+        reply.ok();
+    }
+
+    fn rename(
+        &mut self,
+        req: &Request,
+        parent: u64,
+        name: &OsStr,
+        newparent: u64,
+        newname: &OsStr,
+        flags: u32,
+        reply: ReplyEmpty,
+    ) {
+        debug!(
+            "rename(parent={parent}, name={:#?}, newparent={newparent}, newname={:#?}, flags={flags:#x})",
+            name, newname
+        );
+
+        // RENAME_WHITEOUT isn't supported, and RENAME_EXCHANGE can't be combined with
+        // RENAME_NOREPLACE
+        if flags & !(libc::RENAME_NOREPLACE | libc::RENAME_EXCHANGE) != 0
+            || flags & libc::RENAME_NOREPLACE != 0 && flags & libc::RENAME_EXCHANGE != 0
+        {
+            reply.error(libc::EINVAL);
+            return;
+        }
+        let exchange = flags & libc::RENAME_EXCHANGE != 0;
+        let overwrite = flags & libc::RENAME_NOREPLACE == 0;
+
+        let handle = self.rt.clone();
+
+        let (Ok(from), Ok(to)) = (
+            self.get_full_path(parent, name),
+            self.get_full_path(newparent, newname),
+        ) else {
             reply.error(libc::ENOENT);
             return;
+        };
+
+        let attrs = match self.obj_from_path(&from) {
+            Some(obj) => obj.attrs.clone(),
+            None => {
+                reply.error(libc::ENOENT);
+                return;
+            }
+        };
+
+        for dir in [parent, newparent] {
+            let Some(dir_attrs) = self.manifest.objects.get(&dir).map(|obj| &obj.attrs) else {
+                reply.error(libc::ENOENT);
+                return;
+            };
+            if !check_access(
+                dir_attrs.uid,
+                dir_attrs.gid,
+                dir_attrs.mode,
+                req.uid(),
+                req.gid(),
+                libc::W_OK,
+            ) {
+                reply.error(libc::EACCES);
+                return;
+            }
+        }
+
+        // "Sticky bit" handling, on both the source and a replaced destination
+        let replaced_uid = self.obj_from_path(&to).map(|obj| obj.attrs.uid);
+        for (dir, owner) in [(parent, Some(attrs.uid)), (newparent, replaced_uid)] {
+            let (Some(dir_attrs), Some(owner)) =
+                (self.manifest.objects.get(&dir).map(|obj| &obj.attrs), owner)
+            else {
+                continue;
+            };
+            if dir_attrs.mode & libc::S_ISVTX as u16 != 0
+                && req.uid() != 0
+                && req.uid() != dir_attrs.uid
+                && req.uid() != owner
+            {
+                reply.error(libc::EACCES);
+                return;
+            }
+        }
+
+        let renamed = if exchange {
+            handle.block_on(self.exchange(&from, &to))
+        } else {
+            handle.block_on(self.rename(&from, &to, overwrite))
+        };
+        if let Err(e) = renamed {
+            error!(
+                "Could not rename {} to {}: {e}",
+                from.display(),
+                to.display()
+            );
+            reply.error(errno_from_error(&e, libc::EIO));
+            return;
+        }
+
+        for dir in [parent, newparent] {
+            if let Some(obj) = self.manifest.objects.get(&dir) {
+                let mut dir_attrs = obj.attrs.clone();
+                dir_attrs.last_metadata_changed = SystemTime::now();
+                dir_attrs.last_modified = SystemTime::now();
+                if let Err(e) = self.write_inode(&dir_attrs) {
+                    error!("{e}");
+                    reply.error(libc::ENOENT);
+                    return;
+                }
+            }
         }
-        if let Err(e) = self.gc_inode(&attrs) {
+
+        let mut attrs = attrs;
+        attrs.last_metadata_changed = SystemTime::now();
+        if let Err(e) = self.write_inode(&attrs) {
             error!("{e}");
             reply.error(libc::ENOENT);
             return;
@@ -786,7 +971,7 @@ impl fuser::Filesystem for Lis {
                     Ok(entry) => entry.key() == new_path_key,
                     Err(_) => false,
                 });
-                if already_present == true {
+                if already_present {
                     reply.error(libc::EEXIST);
                     return;
                 }
@@ -800,18 +985,18 @@ impl fuser::Filesystem for Lis {
 
         // create dir
         if req.uid() != 0 {
-            mode &= !(libc::S_ISUID | libc::S_ISGID) as u32;
+            mode &= !(libc::S_ISUID | libc::S_ISGID);
         }
         if parent_attrs.mode & libc::S_ISGID as u16 != 0 {
-            mode |= libc::S_ISGID as u32;
+            mode |= libc::S_ISGID;
         }
         let uid = req.uid();
         let gid = creation_gid(&parent_attrs, req.gid());
         if let Err(e) =
-            handle.block_on(self.mkdir(&full_path, Some(mode as u16), Some(uid), Some(gid)))
+            handle.block_on(self.mkdir(&full_path, Some(mode as u16), Some(uid), Some(gid), false))
         {
             error!("Could not create dir {}: {e}", full_path.display());
-            reply.error(libc::ENOENT);
+            reply.error(errno_from_error(&e, libc::ENOENT));
             return;
         }
 
@@ -837,6 +1022,513 @@ impl fuser::Filesystem for Lis {
         reply.entry(&Duration::new(0, 0), &attrs.into(), 0);
     }
 
+    fn symlink(
+        &mut self,
+        req: &Request<'_>,
+        parent: u64,
+        link_name: &OsStr,
+        target: &Path,
+        reply: ReplyEntry,
+    ) {
+        debug!(
+            "symlink(parent={parent}, link_name={:#?}, target={:#?})",
+            link_name, target
+        );
+        let handle = self.rt.clone();
+
+        let (mut parent_attrs, parent_path) = match self.manifest.objects.get(&parent) {
+            Some(obj) => (obj.attrs.clone(), obj.full_path.clone()),
+            None => {
+                error!("Could not find parent at inode {parent}");
+                reply.error(libc::ENOENT);
+                return;
+            }
+        };
+        let full_path = parent_path.join(link_name);
+
+        if !check_access(
+            parent_attrs.uid,
+            parent_attrs.gid,
+            parent_attrs.mode,
+            req.uid(),
+            req.gid(),
+            libc::W_OK,
+        ) {
+            error!("Access denied for {}", parent_path.display());
+            reply.error(libc::EACCES);
+            return;
+        }
+
+        let gid = creation_gid(&parent_attrs, req.gid());
+        if let Err(e) =
+            handle.block_on(self.create_symlink(&full_path, target, Some(req.uid()), Some(gid)))
+        {
+            error!("Could not create symlink {}: {e}", full_path.display());
+            reply.error(errno_from_error(&e, libc::ENOENT));
+            return;
+        }
+
+        // update parent attributes
+        parent_attrs.last_modified = SystemTime::now();
+        parent_attrs.last_metadata_changed = SystemTime::now();
+        if let Err(e) = self.write_inode(&parent_attrs) {
+            error!("Could not create symlink {}: {e}", full_path.display());
+            reply.error(libc::ENOENT);
+            return;
+        }
+
+        match self.obj_from_path(&full_path) {
+            Some(obj) => reply.entry(&Duration::new(0, 0), &obj.attrs.clone().into(), 0),
+            None => {
+                error!(
+                    "Could not find newly created symlink {}",
+                    full_path.display()
+                );
+                reply.error(libc::ENOENT);
+            }
+        }
+    }
+
+    fn link(
+        &mut self,
+        req: &Request<'_>,
+        ino: u64,
+        newparent: u64,
+        newname: &OsStr,
+        reply: ReplyEntry,
+    ) {
+        debug!(
+            "link(ino={ino}, newparent={newparent}, newname={:#?})",
+            newname
+        );
+        let handle = self.rt.clone();
+
+        let existing = match self.manifest.objects.get(&ino) {
+            // hardlinks to directories are not allowed
+            Some(obj) if matches!(obj.attrs.kind, FileKind::Directory) => {
+                reply.error(libc::EPERM);
+                return;
+            }
+            Some(obj) => obj.full_path.clone(),
+            None => {
+                reply.error(libc::ENOENT);
+                return;
+            }
+        };
+        let (mut parent_attrs, parent_path) = match self.manifest.objects.get(&newparent) {
+            Some(obj) => (obj.attrs.clone(), obj.full_path.clone()),
+            None => {
+                error!("Could not find parent at inode {newparent}");
+                reply.error(libc::ENOENT);
+                return;
+            }
+        };
+        let full_path = parent_path.join(newname);
+
+        if !check_access(
+            parent_attrs.uid,
+            parent_attrs.gid,
+            parent_attrs.mode,
+            req.uid(),
+            req.gid(),
+            libc::W_OK,
+        ) {
+            error!("Access denied for {}", parent_path.display());
+            reply.error(libc::EACCES);
+            return;
+        }
+
+        if let Err(e) = handle.block_on(self.link(&existing, &full_path)) {
+            error!(
+                "Could not link {} to {}: {e}",
+                full_path.display(),
+                existing.display()
+            );
+            reply.error(errno_from_error(&e, libc::ENOENT));
+            return;
+        }
+
+        // update parent attributes
+        parent_attrs.last_modified = SystemTime::now();
+        parent_attrs.last_metadata_changed = SystemTime::now();
+        if let Err(e) = self.write_inode(&parent_attrs) {
+            error!("Could not link {}: {e}", full_path.display());
+            reply.error(libc::ENOENT);
+            return;
+        }
+
+        match self.manifest.objects.get(&ino) {
+            Some(obj) => reply.entry(&Duration::new(0, 0), &obj.attrs.clone().into(), 0),
+            None => reply.error(libc::ENOENT),
+        }
+    }
+
+    fn flush(
+        &mut self,
+        _req: &Request<'_>,
+        ino: u64,
+        _fh: u64,
+        _lock_owner: u64,
+        reply: ReplyEmpty,
+    ) {
+        debug!("flush(ino={ino})");
+        // writes reach the store as they happen, nothing is buffered here. Getting them on disk
+        // is for fsync, close(2) doesn't promise that
+        if self.manifest.objects.contains_key(&ino) {
+            reply.ok();
+        } else {
+            reply.error(libc::ENOENT);
+        }
+    }
+
+    fn fsync(&mut self, _req: &Request<'_>, ino: u64, _fh: u64, datasync: bool, reply: ReplyEmpty) {
+        debug!("fsync(ino={ino}, datasync={datasync})");
+        self.fsync_inode(ino, datasync, reply);
+    }
+
+    fn fsyncdir(
+        &mut self,
+        _req: &Request<'_>,
+        ino: u64,
+        _fh: u64,
+        datasync: bool,
+        reply: ReplyEmpty,
+    ) {
+        debug!("fsyncdir(ino={ino}, datasync={datasync})");
+        self.fsync_inode(ino, datasync, reply);
+    }
+
+    fn fallocate(
+        &mut self,
+        _req: &Request<'_>,
+        ino: u64,
+        fh: u64,
+        offset: i64,
+        length: i64,
+        mode: i32,
+        reply: ReplyEmpty,
+    ) {
+        debug!("fallocate(ino={ino}, offset={offset}, length={length}, mode={mode:#x})");
+        if offset < 0 || length <= 0 {
+            reply.error(libc::EINVAL);
+            return;
+        }
+        if !check_file_handle_write(fh) {
+            reply.error(libc::EBADF);
+            return;
+        }
+        let full_path = match self.manifest.objects.get(&ino) {
+            Some(obj) => obj.full_path.clone(),
+            None => {
+                reply.error(libc::ENOENT);
+                return;
+            }
+        };
+        let (offset, length) = (offset as u64, length as u64);
+
+        let handle = self.rt.clone();
+        let result = match mode {
+            0 => handle.block_on(self.preallocate(&full_path, offset.saturating_add(length))),
+            // nothing to reserve, content is only stored when written
+            libc::FALLOC_FL_KEEP_SIZE => Ok(()),
+            // the kernel only accepts punching holes together with KEEP_SIZE
+            m if m == libc::FALLOC_FL_PUNCH_HOLE | libc::FALLOC_FL_KEEP_SIZE => {
+                handle.block_on(self.punch_hole(&full_path, offset, length))
+            }
+            _ => {
+                reply.error(libc::EOPNOTSUPP);
+                return;
+            }
+        };
+        match result {
+            Ok(()) => reply.ok(),
+            Err(e) => {
+                error!("Could not fallocate {}: {e}", full_path.display());
+                reply.error(errno_from_error(&e, libc::EIO));
+            }
+        }
+    }
+
+    fn copy_file_range(
+        &mut self,
+        _req: &Request<'_>,
+        ino_in: u64,
+        fh_in: u64,
+        offset_in: i64,
+        ino_out: u64,
+        fh_out: u64,
+        offset_out: i64,
+        len: u64,
+        _flags: u32,
+        reply: fuser::ReplyWrite,
+    ) {
+        debug!(
+            "copy_file_range(ino_in={ino_in}, offset_in={offset_in}, ino_out={ino_out}, \
+            offset_out={offset_out}, len={len})"
+        );
+        if offset_in < 0 || offset_out < 0 {
+            reply.error(libc::EINVAL);
+            return;
+        }
+        if !check_file_handle_read(fh_in) || !check_file_handle_write(fh_out) {
+            reply.error(libc::EBADF);
+            return;
+        }
+        let (src, dst) = match (
+            self.manifest.objects.get(&ino_in),
+            self.manifest.objects.get(&ino_out),
+        ) {
+            (Some(src), Some(dst)) => (src.full_path.clone(), dst.full_path.clone()),
+            _ => {
+                reply.error(libc::ENOENT);
+                return;
+            }
+        };
+
+        // a single reply can't report more than u32::MAX bytes
+        let len = len.min(u32::MAX as u64);
+        match self.rt.clone().block_on(self.copy_range(
+            &src,
+            offset_in as u64,
+            &dst,
+            offset_out as u64,
+            len,
+        )) {
+            Ok(copied) => {
+                if let Some(obj) = self.manifest.objects.get(&ino_out) {
+                    let mut attrs = obj.attrs.clone();
+                    clear_suid_sgid(&mut attrs);
+                    if let Err(e) = self.write_inode(&attrs) {
+                        error!("Could not update {}: {e}", dst.display());
+                    }
+                }
+                reply.written(copied as u32);
+            }
+            Err(e) => {
+                error!("Could not copy {} to {}: {e}", src.display(), dst.display());
+                reply.error(errno_from_error(&e, libc::EIO));
+            }
+        }
+    }
+
+    fn statfs(&mut self, _req: &Request<'_>, _ino: u64, reply: fuser::ReplyStatfs) {
+        debug!("statfs()");
+        match self.rt.clone().block_on(Lis::statfs(self)) {
+            Ok(stats) => reply.statfs(
+                stats.blocks,
+                stats.blocks_free,
+                stats.blocks_available,
+                stats.files,
+                stats.files_free,
+                stats.block_size,
+                stats.max_name_length,
+                stats.block_size,
+            ),
+            Err(e) => {
+                error!("Could not get filesystem stats: {e}");
+                reply.error(libc::EIO);
+            }
+        }
+    }
+
+    fn setxattr(
+        &mut self,
+        req: &Request<'_>,
+        ino: u64,
+        name: &OsStr,
+        value: &[u8],
+        flags: i32,
+        _position: u32,
+        reply: ReplyEmpty,
+    ) {
+        debug!(
+            "setxattr(ino={ino}, name={:#?}, size={})",
+            name,
+            value.len()
+        );
+        let (mut attrs, full_path) = match self.manifest.objects.get(&ino) {
+            Some(obj) => (obj.attrs.clone(), obj.full_path.clone()),
+            None => {
+                reply.error(libc::ENOENT);
+                return;
+            }
+        };
+        if let Err(error_code) = xattr_access_check(name.as_bytes(), libc::W_OK, &attrs, req) {
+            reply.error(error_code);
+            return;
+        }
+
+        let Some(name) = name.to_str() else {
+            reply.error(libc::EINVAL);
+            return;
+        };
+        let handle = self.rt.clone();
+        let exists = match self.get_xattr(&attrs, &full_path, name) {
+            Ok(value) => value.is_some(),
+            Err(e) => {
+                error!("Could not get xattr of {}: {e}", full_path.display());
+                reply.error(errno_from_error(&e, libc::EIO));
+                return;
+            }
+        };
+        if flags & libc::XATTR_CREATE != 0 && exists {
+            reply.error(libc::EEXIST);
+            return;
+        }
+        if flags & libc::XATTR_REPLACE != 0 && !exists {
+            reply.error(libc::ENODATA);
+            return;
+        }
+
+        let result = if is_annotation(name) {
+            handle.block_on(self.set_annotation(&full_path, name, value))
+        } else {
+            attrs.xattrs.insert(name.to_string(), value.to_vec());
+            attrs.last_metadata_changed = SystemTime::now();
+            self.write_inode(&attrs)
+        };
+        match result {
+            Ok(()) => reply.ok(),
+            Err(e) => {
+                error!("Could not set xattr on inode {ino}: {e}");
+                reply.error(errno_from_error(&e, libc::EIO));
+            }
+        }
+    }
+
+    fn getxattr(
+        &mut self,
+        req: &Request<'_>,
+        ino: u64,
+        name: &OsStr,
+        size: u32,
+        reply: fuser::ReplyXattr,
+    ) {
+        debug!("getxattr(ino={ino}, name={:#?}, size={size})", name);
+        let (attrs, full_path) = match self.manifest.objects.get(&ino) {
+            Some(obj) => (obj.attrs.clone(), obj.full_path.clone()),
+            None => {
+                reply.error(libc::ENOENT);
+                return;
+            }
+        };
+        if let Err(error_code) = xattr_access_check(name.as_bytes(), libc::R_OK, &attrs, req) {
+            reply.error(error_code);
+            return;
+        }
+        let Some(name) = name.to_str() else {
+            reply.error(libc::ENODATA);
+            return;
+        };
+
+        match self.get_xattr(&attrs, &full_path, name) {
+            // a size of 0 asks how big a buffer the value needs
+            Ok(Some(value)) if size == 0 => reply.size(value.len() as u32),
+            Ok(Some(value)) if value.len() <= size as usize => reply.data(&value),
+            Ok(Some(_)) => reply.error(libc::ERANGE),
+            Ok(None) => reply.error(libc::ENODATA),
+            Err(e) => {
+                error!("Could not get xattr of {}: {e}", full_path.display());
+                reply.error(errno_from_error(&e, libc::EIO));
+            }
+        }
+    }
+
+    fn listxattr(&mut self, _req: &Request<'_>, ino: u64, size: u32, reply: fuser::ReplyXattr) {
+        debug!("listxattr(ino={ino}, size={size})");
+        let (attrs, full_path) = match self.manifest.objects.get(&ino) {
+            Some(obj) => (obj.attrs.clone(), obj.full_path.clone()),
+            None => {
+                reply.error(libc::ENOENT);
+                return;
+            }
+        };
+        let handle = self.rt.clone();
+        let annotations = match handle.block_on(self.annotations(&full_path)) {
+            Ok(annotations) => annotations,
+            Err(e) => {
+                error!("Could not list xattrs of {}: {e}", full_path.display());
+                reply.error(errno_from_error(&e, libc::EIO));
+                return;
+            }
+        };
+
+        // NUL-terminated names, one after the other
+        let mut names = Vec::new();
+        let annotation_names = annotations.keys().filter(|name| is_annotation(name));
+        for name in attrs.xattrs.keys().chain(annotation_names) {
+            names.extend_from_slice(name.as_bytes());
+            names.push(0);
+        }
+        if size == 0 {
+            reply.size(names.len() as u32);
+        } else if names.len() <= size as usize {
+            reply.data(&names);
+        } else {
+            reply.error(libc::ERANGE);
+        }
+    }
+
+    fn removexattr(&mut self, req: &Request<'_>, ino: u64, name: &OsStr, reply: ReplyEmpty) {
+        debug!("removexattr(ino={ino}, name={:#?})", name);
+        let (mut attrs, full_path) = match self.manifest.objects.get(&ino) {
+            Some(obj) => (obj.attrs.clone(), obj.full_path.clone()),
+            None => {
+                reply.error(libc::ENOENT);
+                return;
+            }
+        };
+        if let Err(error_code) = xattr_access_check(name.as_bytes(), libc::W_OK, &attrs, req) {
+            reply.error(error_code);
+            return;
+        }
+        let Some(name) = name.to_str() else {
+            reply.error(libc::ENODATA);
+            return;
+        };
+
+        let result = if is_annotation(name) {
+            let handle = self.rt.clone();
+            handle.block_on(self.remove_annotation(&full_path, name))
+        } else if attrs.xattrs.remove(name).is_some() {
+            attrs.last_metadata_changed = SystemTime::now();
+            self.write_inode(&attrs).map(|()| true)
+        } else {
+            Ok(false)
+        };
+        match result {
+            Ok(true) => reply.ok(),
+            Ok(false) => reply.error(libc::ENODATA),
+            Err(e) => {
+                error!("Could not remove xattr from inode {ino}: {e}");
+                reply.error(errno_from_error(&e, libc::EIO));
+            }
+        }
+    }
+
+    fn readlink(&mut self, _req: &Request<'_>, ino: u64, reply: ReplyData) {
+        debug!("readlink(ino={ino})");
+        let full_path = match self.manifest.objects.get(&ino) {
+            Some(obj) if matches!(obj.attrs.kind, FileKind::Symlink) => obj.full_path.clone(),
+            Some(_) => {
+                reply.error(libc::EINVAL);
+                return;
+            }
+            None => {
+                reply.error(libc::ENOENT);
+                return;
+            }
+        };
+
+        match self.rt.clone().block_on(self.read_link(&full_path)) {
+            Ok(target) => reply.data(target.as_os_str().as_bytes()),
+            Err(e) => {
+                error!("Could not read symlink {}: {e}", full_path.display());
+                reply.error(errno_from_error(&e, libc::EIO));
+            }
+        }
+    }
+
     fn read(
         &mut self,
         _req: &Request,
@@ -849,6 +1541,7 @@ impl fuser::Filesystem for Lis {
         reply: ReplyData,
     ) {
         debug!("read(ino={ino} offset={offset} size={size})");
+        FuseCounters::bump(&self.fuse_counters.reads);
         assert!(offset >= 0);
         let handle = self.rt.clone();
 
@@ -857,21 +1550,24 @@ impl fuser::Filesystem for Lis {
             return;
         }
 
-        let path = match self.manifest.objects.get(&ino) {
-            Some(obj) => obj.full_path.clone(),
+        let (path, mut attrs) = match self.manifest.objects.get(&ino) {
+            Some(obj) => (obj.full_path.clone(), obj.attrs.clone()),
             None => {
                 reply.error(libc::ENOENT);
                 return;
             }
         };
 
-        let offset = offset as usize;
-        match handle.block_on(self.read(&path)) {
-            Ok(bytes_content) => {
-                let content_size = bytes_content.len();
-                let read_size: usize =
-                    min(size, content_size.saturating_sub(offset) as u32) as usize;
-                let buffer = bytes_content.slice(offset..(offset + read_size));
+        match handle.block_on(self.read_range(&path, offset as u64, size as usize)) {
+            Ok(buffer) => {
+                let now = SystemTime::now();
+                if !self.noatime && !check_file_handle_noatime(fh) && atime_is_stale(&attrs, now) {
+                    attrs.last_accessed = now;
+                    if let Err(e) = self.write_inode(&attrs) {
+                        error!("Could not update atime of {}: {e}", path.display());
+                    }
+                }
+
                 reply.data(&buffer);
             }
             Err(e) => {
@@ -911,10 +1607,10 @@ impl fuser::Filesystem for Lis {
         };
 
         // save data to lis
-        if handle
-            .block_on(self.write(&full_path, data, offset as usize))
-            .is_ok()
-        {
+        if let Err(e) = handle.block_on(self.write(&full_path, data, offset as usize)) {
+            error!("Could not write to {}: {e}", full_path.display());
+            reply.error(errno_from_error(&e, libc::EBADF));
+        } else {
             // update attributes
             attrs.last_metadata_changed = SystemTime::now();
             attrs.last_modified = SystemTime::now();
@@ -931,13 +1627,80 @@ impl fuser::Filesystem for Lis {
             }
 
             reply.written(data.len() as u32);
+        }
+    }
+}
+
+impl Lis {
+    /// Picks up what peers wrote before answering a callback that looks paths up
+    fn catch_up_with_peers(&mut self) {
+        let handle = self.rt.clone();
+        if let Err(e) = handle.block_on(self.apply_remote_changes()) {
+            error!("Could not apply changes from peers: {e}");
+        }
+    }
+
+    /// Whether the content of `ino` is the same as when it was last opened, so the kernel can
+    /// keep its cached pages. Remembers the content for the next open
+    fn content_unchanged(&mut self, ino: u64) -> bool {
+        let Some(obj) = self.manifest.objects.get(&ino) else {
+            return false;
+        };
+        let (full_path, size) = (obj.full_path.clone(), obj.attrs.size);
+        let handle = self.rt.clone();
+        let hash = match handle.block_on(self.entry_hash(&full_path)) {
+            Ok(hash) => hash,
+            Err(e) => {
+                warn!(
+                    "Could not get the content hash of {}: {e}",
+                    full_path.display()
+                );
+                self.opened_content.remove(&ino);
+                return false;
+            }
+        };
+        self.opened_content.insert(ino, (hash, size)) == Some((hash, size))
+    }
+
+    /// Value of the xattr `name` of the object at `full_path` with `attrs`, from its annotations
+    /// or its manifest entry depending on the namespace
+    fn get_xattr(
+        &self,
+        attrs: &InodeAttributes,
+        full_path: &Path,
+        name: &str,
+    ) -> Result<Option<Vec<u8>>> {
+        if is_annotation(name) {
+            self.rt.block_on(self.get_annotation(full_path, name))
         } else {
-            reply.error(libc::EBADF);
+            Ok(attrs.xattrs.get(name).cloned())
+        }
+    }
+
+    /// Shared by `fsync` and `fsyncdir`. `datasync` skips saving the metadata
+    fn fsync_inode(&mut self, ino: u64, datasync: bool, reply: ReplyEmpty) {
+        let full_path = match self.manifest.objects.get(&ino) {
+            Some(obj) => obj.full_path.clone(),
+            None => {
+                reply.error(libc::ENOENT);
+                return;
+            }
+        };
+        let mut result = self.rt.clone().block_on(self.sync_file(&full_path));
+        if result.is_ok() && !datasync {
+            result = self.manifest.save();
+        }
+        match result {
+            Ok(()) => reply.ok(),
+            Err(e) => {
+                error!("Could not sync {}: {e}", full_path.display());
+                reply.error(errno_from_error(&e, libc::EIO));
+            }
         }
     }
 }
 
-#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum FileKind {
     File,
     Directory,
@@ -966,6 +1729,19 @@ pub fn path_kind(path: &Path) -> Result<FileKind> {
     }
 }
 
+/// Flags for `reply.opened` on regular files, depending on whether the mount bypasses the page
+/// cache and whether the pages cached for the file are still good
+fn open_flags(direct_io: bool, keep_cache: bool) -> u32 {
+    if direct_io {
+        fuser::consts::FOPEN_DIRECT_IO
+    } else if keep_cache {
+        fuser::consts::FOPEN_KEEP_CACHE
+    } else {
+        // the kernel drops the file's cached pages
+        0
+    }
+}
+
 fn creation_gid(parent: &InodeAttributes, gid: u32) -> u32 {
     if parent.mode & libc::S_ISGID as u16 != 0 {
         return parent.gid;
@@ -1014,7 +1790,49 @@ pub fn check_access(
         access_mask -= access_mask & file_mode;
     }
 
-    return access_mask == 0;
+    access_mask == 0
+}
+
+/// Whether the xattr `name` is stored as an annotation (see `Lis::set_annotation`), which syncs
+/// to peers. Other namespaces are about this node, and stay in its manifest
+fn is_annotation(name: &str) -> bool {
+    name.starts_with("user.")
+}
+
+/// Whether the caller may read (`R_OK`) or change (`W_OK`) the xattr `name`. `user.` ones follow
+/// the file's permissions, `trusted.` ones and changes to `security.` ones are for root only
+fn xattr_access_check(
+    name: &[u8],
+    access_mask: i32,
+    attrs: &InodeAttributes,
+    req: &Request<'_>,
+) -> Result<(), c_int> {
+    if name.starts_with(b"user.") {
+        if check_access(
+            attrs.uid,
+            attrs.gid,
+            attrs.mode,
+            req.uid(),
+            req.gid(),
+            access_mask,
+        ) {
+            Ok(())
+        } else {
+            Err(libc::EACCES)
+        }
+    } else if name.starts_with(b"security.") && access_mask == libc::R_OK {
+        // anyone can read them, e.g. `ls` asks for security.selinux
+        Ok(())
+    } else if name.starts_with(b"trusted.") || name.starts_with(b"security.") {
+        if req.uid() == 0 {
+            Ok(())
+        } else {
+            Err(libc::EPERM)
+        }
+    } else {
+        // system.* (ACLs) and unknown namespaces
+        Err(libc::ENOTSUP)
+    }
 }
 
 fn check_file_handle_read(file_handle: u64) -> bool {
@@ -1023,6 +1841,19 @@ fn check_file_handle_read(file_handle: u64) -> bool {
 fn check_file_handle_write(file_handle: u64) -> bool {
     (file_handle & FILE_HANDLE_WRITE_BIT) != 0
 }
+fn check_file_handle_noatime(file_handle: u64) -> bool {
+    (file_handle & FILE_HANDLE_NOATIME_BIT) != 0
+}
+
+/// relatime rules, the Linux default: atime is only worth a metadata write if it's older than
+/// the last modification or change, or more than a day old
+fn atime_is_stale(attrs: &InodeAttributes, now: SystemTime) -> bool {
+    attrs.last_accessed <= attrs.last_modified
+        || attrs.last_accessed <= attrs.last_metadata_changed
+        || now
+            .duration_since(attrs.last_accessed)
+            .is_ok_and(|age| age >= Duration::from_secs(24 * 60 * 60))
+}
 async fn get_groups(pid: u32) -> Vec<u32> {
     {
         let path = format!("/proc/{pid}/task/{pid}/status");
@@ -1043,6 +1874,10 @@ async fn get_groups(pid: u32) -> Vec<u32> {
     vec![]
 }
 
+fn unix_epoch() -> SystemTime {
+    SystemTime::UNIX_EPOCH
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct InodeAttributes {
     // TODO: pub parent: Inode,
@@ -1052,13 +1887,17 @@ pub struct InodeAttributes {
     pub last_accessed: SystemTime,
     pub last_modified: SystemTime,
     pub last_metadata_changed: SystemTime,
+    // Birth time, set once when the inode is created. Older manifests don't have it
+    #[serde(default = "unix_epoch")]
+    pub created: SystemTime,
     pub kind: FileKind,
     // Permissions and special mode bits
     pub mode: u16,
     pub hardlinks: u32,
     pub uid: u32,
     pub gid: u32,
-    pub xattrs: BTreeMap<Vec<u8>, Vec<u8>>,
+    // Extended attributes by name. Names are kept as strings so the manifest can store them
+    pub xattrs: BTreeMap<String, Vec<u8>>,
 }
 
 impl From<InodeAttributes> for fuser::FileAttr {
@@ -1066,11 +1905,11 @@ impl From<InodeAttributes> for fuser::FileAttr {
         fuser::FileAttr {
             ino: attrs.inode,
             size: attrs.size,
-            blocks: (attrs.size + BLOCK_SIZE - 1) / BLOCK_SIZE,
+            blocks: attrs.size.div_ceil(BLOCK_SIZE),
             atime: attrs.last_accessed,
             mtime: attrs.last_modified,
             ctime: attrs.last_metadata_changed,
-            crtime: SystemTime::UNIX_EPOCH,
+            crtime: attrs.created,
             kind: attrs.kind.into(),
             perm: attrs.mode,
             nlink: attrs.hardlinks,
@@ -0,0 +1,169 @@
+use std::fmt;
+
+use sha2::{Digest, Sha256};
+
+use crate::{fuse::FileKind, prelude::*};
+
+/// Algorithm for the secondary content hash kept next to Iroh's native BLAKE3 hash
+/// Dedup and content addressing inside Lis always use the native hash; the secondary one is only
+/// recorded so files can be looked up or verified by systems that address content differently
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HashAlgorithm {
+    /// No secondary hash, the native hash is already BLAKE3
+    #[default]
+    Blake3,
+    Sha256,
+}
+
+impl HashAlgorithm {
+    /// Hex digest of `content`, or `None` when no secondary hash is kept
+    pub fn digest(&self, content: &[u8]) -> Option<String> {
+        match self {
+            HashAlgorithm::Blake3 => None,
+            HashAlgorithm::Sha256 => Some(
+                Sha256::digest(content)
+                    .iter()
+                    .map(|b| format!("{b:02x}"))
+                    .collect(),
+            ),
+        }
+    }
+}
+
+impl fmt::Display for HashAlgorithm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HashAlgorithm::Blake3 => write!(f, "blake3"),
+            HashAlgorithm::Sha256 => write!(f, "sha256"),
+        }
+    }
+}
+
+impl Lis {
+    /// Switches the store's secondary hash algorithm. Files are rehashed with it the next time
+    /// their hash is asked for
+    pub async fn set_hash_algorithm(&mut self, algorithm: HashAlgorithm) -> Result<()> {
+        if self.manifest.hash_algorithm == algorithm {
+            return Ok(());
+        }
+        self.manifest.hash_algorithm = algorithm;
+
+        for obj in self.manifest.objects.values_mut() {
+            if matches!(obj.attrs.kind, FileKind::File) {
+                obj.content_hash = None;
+                obj.content_hash_stale = true;
+            }
+        }
+        self.manifest.save()
+    }
+
+    /// Marks the secondary hash of a file as out of date after its content changed. It's only
+    /// recomputed when asked for, so a file written in many small pieces is hashed once
+    pub(crate) fn mark_hash_stale(&mut self, full_path: &Path) -> Result<()> {
+        if self.manifest.hash_algorithm == HashAlgorithm::Blake3 {
+            return Ok(());
+        }
+        let Some(ino) = self.manifest.inodes.get(full_path).copied() else {
+            return Ok(());
+        };
+        match self.manifest.objects.get_mut(&ino) {
+            Some(obj) if !obj.content_hash_stale => {
+                obj.content_hash = None;
+                obj.content_hash_stale = true;
+                self.manifest.save()
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Recomputes the secondary hash of a file if its content changed since it was last hashed
+    pub(crate) async fn refresh_hash(&mut self, full_path: &Path) -> Result<()> {
+        if !self
+            .obj_from_path(full_path)
+            .is_some_and(|obj| obj.content_hash_stale)
+        {
+            return Ok(());
+        }
+        let content = self.read(full_path).await?;
+        let digest = self.manifest.hash_algorithm.digest(&content);
+        if let Some(ino) = self.manifest.inodes.get(full_path).copied() {
+            if let Some(obj) = self.manifest.objects.get_mut(&ino) {
+                obj.content_hash = digest;
+                obj.content_hash_stale = false;
+            }
+        }
+        self.manifest.save()
+    }
+
+    /// Secondary hash of the file at `full_path`, in the store's `HashAlgorithm`
+    pub async fn content_hash(&mut self, full_path: &Path) -> Result<Option<String>> {
+        self.refresh_hash(full_path).await?;
+        Ok(self
+            .obj_from_path(full_path)
+            .and_then(|obj| obj.content_hash.clone()))
+    }
+
+    /// Paths of the files whose secondary hash is `hex_digest`
+    pub async fn find_by_hash(&mut self, hex_digest: &str) -> Result<Vec<PathBuf>> {
+        let stale: Vec<_> = self
+            .manifest
+            .objects
+            .values()
+            .filter(|obj| obj.content_hash_stale)
+            .map(|obj| obj.full_path.clone())
+            .collect();
+        for path in stale {
+            self.refresh_hash(&path).await?;
+        }
+
+        let hex_digest = hex_digest.to_lowercase();
+        Ok(self
+            .manifest
+            .objects
+            .values()
+            .filter(|obj| obj.content_hash.as_deref() == Some(hex_digest.as_str()))
+            .map(|obj| obj.full_path.clone())
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[tokio::test]
+    async fn sha256_content_hash() {
+        let tmp_dir = TempDir::new().unwrap();
+        let mut lis = Lis::new(&tmp_dir.path().to_path_buf(), true).await.unwrap();
+        let file_path = Path::new("/hello.txt");
+        lis.touch(file_path, None, None, None).await.unwrap();
+        assert_eq!(lis.content_hash(file_path).await.unwrap(), None);
+
+        lis.set_hash_algorithm(HashAlgorithm::Sha256).await.unwrap();
+        lis.write(file_path, b"he", 0).await.unwrap();
+        lis.write(file_path, b"llo", 2).await.unwrap();
+
+        // writes only mark the hash stale, it's computed when asked for
+        assert!(lis.obj_from_path(file_path).unwrap().content_hash_stale);
+        // `printf hello | sha256sum`
+        let expected = "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824";
+        assert_eq!(
+            lis.content_hash(file_path).await.unwrap().as_deref(),
+            Some(expected)
+        );
+        assert!(!lis.obj_from_path(file_path).unwrap().content_hash_stale);
+
+        lis.write(file_path, b"J", 0).await.unwrap();
+        assert_eq!(
+            lis.find_by_hash(expected).await.unwrap(),
+            Vec::<PathBuf>::new()
+        );
+        lis.write(file_path, b"h", 0).await.unwrap();
+        assert_eq!(
+            lis.find_by_hash(expected).await.unwrap(),
+            vec![file_path.to_path_buf()]
+        );
+    }
+}
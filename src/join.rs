@@ -0,0 +1,439 @@
+use futures_lite::{Stream, StreamExt};
+use iroh::{
+    base::key::PublicKey,
+    blobs::Hash,
+    client::docs::{Doc, Entry, LiveEvent},
+    docs::{store::Query, Capability, CapabilityKind, ContentStatus, DocTicket},
+    net::NodeAddr,
+};
+use tokio::{fs, sync::mpsc};
+
+use crate::{
+    fuse::FileKind,
+    manifest::Manifest,
+    prelude::*,
+    util::{bytes_to_namespaceid, is_annotation_key, key_to_string},
+    LisError,
+};
+
+/// How long a peer gets to sync one directory
+const SYNC_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// An entry a peer wrote in the directory `dir`
+pub(crate) struct RemoteChange {
+    dir: PathBuf,
+    entry: Entry,
+}
+
+/// Entries peers wrote, queued by the doc subscriptions until `Lis::apply_remote_changes`
+pub(crate) struct RemoteChanges {
+    tx: mpsc::UnboundedSender<RemoteChange>,
+    rx: mpsc::UnboundedReceiver<RemoteChange>,
+}
+
+impl Default for RemoteChanges {
+    fn default() -> Self {
+        let (tx, rx) = mpsc::unbounded_channel();
+        RemoteChanges { tx, rx }
+    }
+}
+
+impl Lis {
+    /// Creates a node in `root` attached to the filesystem shared by an invite `ticket` (see
+    /// `create_invite`), and syncs it from the ticket's peers. `progress` gets each path as
+    /// it's added. The docs keep syncing with the peers afterwards, see `apply_remote_changes`
+    /// Directories don't say they're directories in their parent's doc, only their manifest
+    /// does. An entry that could be one (a doc id) is asked for as a doc, and is a file if the
+    /// peers don't have it. Only the root dir is writable with a read-write ticket, the
+    /// directories below it are joined read-only
+    pub async fn join(
+        root: &PathBuf,
+        ticket: &DocTicket,
+        mut progress: impl FnMut(&Path),
+    ) -> Result<Self> {
+        let manifest_path = root.join("manifest.json");
+        if Manifest::load(&manifest_path)?.is_some() {
+            return Err(LisError::AlreadyExists(root.clone()).into());
+        }
+        fs::create_dir_all(root).await?;
+
+        let iroh_node = iroh::node::Node::persistent(root).await?.spawn().await?;
+        let author = iroh_node.authors().create().await?;
+        iroh_node.authors().set_default(author).await?;
+
+        let (root_doc, events) = iroh_node
+            .docs()
+            .import_and_subscribe(ticket.clone())
+            .await?;
+        if !wait_for_sync(Box::pin(events)).await? {
+            return Err(anyhow!(
+                "Could not sync the root dir from the ticket's peers"
+            ));
+        }
+        let manifest = Manifest::new(manifest_path, root_doc.id().to_string())?;
+        manifest.save()?;
+
+        // the tree is empty so far, `from_parts` only watches the root dir
+        let mut lis = Lis::from_parts(root, iroh_node, manifest, root_doc.clone()).await?;
+        lis.import_tree(Path::new("/"), root_doc, &ticket.nodes, &mut progress)
+            .await?;
+        Ok(lis)
+    }
+
+    /// Adds what's in the directory `dir_path`, whose doc is `doc`, and everything below it to
+    /// the manifest, joining the docs of its subdirectories and watching them for changes
+    async fn import_tree(
+        &mut self,
+        dir_path: &Path,
+        doc: Doc,
+        peers: &[NodeAddr],
+        progress: &mut impl FnMut(&Path),
+    ) -> Result<()> {
+        let mut dirs = vec![(dir_path.to_path_buf(), doc)];
+        while let Some((dir_path, doc)) = dirs.pop() {
+            let mut entries = doc.get_many(Query::all().build()).await?;
+            let mut found = Vec::new();
+            while let Some(entry) = entries.next().await {
+                let entry = entry?;
+                if !is_annotation_key(entry.key()) {
+                    found.push(entry);
+                }
+            }
+
+            for entry in found {
+                let full_path = dir_path.join(key_to_string(entry.key().to_vec().into())?);
+                if self.obj_from_path(&full_path).is_some() {
+                    continue;
+                }
+                match self.join_dir(&doc, &entry, peers).await? {
+                    Some(dir_doc) => {
+                        self.create_fs_objects(
+                            &full_path,
+                            FileKind::Directory,
+                            None,
+                            None,
+                            None,
+                            None,
+                        )?;
+                        self.read_only_dirs.insert(full_path.clone());
+                        // before reading it, so nothing written in between is missed
+                        self.watch_dir(&full_path, &dir_doc).await?;
+                        dirs.push((full_path.clone(), dir_doc));
+                    }
+                    None => {
+                        let size = Some(entry.content_len());
+                        self.create_fs_objects(&full_path, FileKind::File, size, None, None, None)?;
+                    }
+                }
+                progress(&full_path);
+            }
+        }
+        Ok(())
+    }
+
+    /// Syncs the directory `entry` of `parent` points to, or `None` if it's a file
+    async fn join_dir(
+        &self,
+        parent: &Doc,
+        entry: &Entry,
+        peers: &[NodeAddr],
+    ) -> Result<Option<Doc>> {
+        if entry.content_len() != 32 {
+            return Ok(None);
+        }
+        let Ok(id) = bytes_to_namespaceid(entry.content_bytes(parent).await?) else {
+            return Ok(None);
+        };
+        let docs = self.iroh_node.docs();
+        // not every 32 bytes are a doc id
+        let Ok(doc) = docs.import_namespace(Capability::Read(id)).await else {
+            return Ok(None);
+        };
+        let events = doc.subscribe().await?;
+        doc.start_sync(peers.to_vec()).await?;
+        if wait_for_sync(Box::pin(events)).await? {
+            return Ok(Some(doc));
+        }
+        docs.drop_doc(id).await?;
+        Ok(None)
+    }
+
+    /// Watches every directory's doc for entries written by peers, finds the directories this
+    /// node can only read, and resumes syncing with the peers each doc synced with before
+    pub(crate) async fn watch_tree(&mut self) -> Result<()> {
+        let mut capabilities = BTreeMap::new();
+        let mut docs = self.iroh_node.docs().list().await?;
+        while let Some(doc) = docs.next().await {
+            let (id, capability) = doc?;
+            capabilities.insert(id, capability);
+        }
+
+        let mut dirs: Vec<PathBuf> = self
+            .manifest
+            .objects
+            .values()
+            .filter(|obj| matches!(obj.attrs.kind, FileKind::Directory))
+            .map(|obj| obj.full_path.clone())
+            .collect();
+        dirs.push(PathBuf::from("/"));
+        dirs.sort();
+        dirs.dedup();
+        for dir in dirs {
+            let doc = match self.find_dir_doc(&dir).await {
+                Ok(doc) => doc,
+                Err(e) => {
+                    warn!("Can't watch {}: {e}", dir.display());
+                    continue;
+                }
+            };
+            if matches!(capabilities.get(&doc.id()), Some(CapabilityKind::Read)) {
+                self.read_only_dirs.insert(dir.clone());
+            }
+            self.watch_dir(&dir, &doc).await?;
+            let peers = sync_peers(&doc).await?;
+            if !peers.is_empty() {
+                doc.start_sync(peers).await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Queues the entries peers write in `doc`, the doc of the directory `dir_path`, for
+    /// `apply_remote_changes`. An entry that could be a directory waits until its content (the
+    /// directory's doc id) is here
+    async fn watch_dir(&self, dir_path: &Path, doc: &Doc) -> Result<()> {
+        let mut events = Box::pin(doc.subscribe().await?);
+        let tx = self.remote_changes.tx.clone();
+        let dir = dir_path.to_path_buf();
+        tokio::spawn(async move {
+            let mut waiting: BTreeMap<Hash, Vec<Entry>> = BTreeMap::new();
+            while let Some(Ok(event)) = events.next().await {
+                let ready = match event {
+                    LiveEvent::InsertRemote { entry, .. } if is_annotation_key(entry.key()) => {
+                        continue
+                    }
+                    LiveEvent::InsertRemote {
+                        entry,
+                        content_status,
+                        ..
+                    } => {
+                        if entry.content_len() == 32
+                            && !matches!(content_status, ContentStatus::Complete)
+                        {
+                            waiting.entry(entry.content_hash()).or_default().push(entry);
+                            continue;
+                        }
+                        vec![entry]
+                    }
+                    LiveEvent::ContentReady { hash } => waiting.remove(&hash).unwrap_or_default(),
+                    _ => continue,
+                };
+                for entry in ready {
+                    let change = RemoteChange {
+                        dir: dir.clone(),
+                        entry,
+                    };
+                    if tx.send(change).is_err() {
+                        // the node is gone
+                        return;
+                    }
+                }
+            }
+        });
+        Ok(())
+    }
+
+    /// Brings the manifest up to date with what peers wrote since the last call: new files and
+    /// directories get objects, changed files their new size and mtime, and removed ones are
+    /// dropped. The FUSE callbacks that look paths up call it; library users call it before
+    /// `stat` or `list` to see peers' changes
+    pub async fn apply_remote_changes(&mut self) -> Result<()> {
+        // only the last write to each path matters, e.g. a removal and the new content
+        let mut changes = BTreeMap::new();
+        while let Ok(change) = self.remote_changes.rx.try_recv() {
+            let name = key_to_string(change.entry.key().to_vec().into())?;
+            changes.insert(change.dir.join(name), change);
+        }
+
+        for (full_path, change) in changes {
+            let entry = &change.entry;
+            if entry.content_len() == 0 {
+                // an empty entry is how a removal syncs
+                self.forget_tree(&full_path);
+                continue;
+            }
+            match self.obj_from_path(&full_path).map(|obj| obj.attrs.clone()) {
+                Some(attrs) if matches!(attrs.kind, FileKind::Directory) => {}
+                Some(mut attrs) => {
+                    attrs.size = entry.content_len();
+                    attrs.last_modified = UNIX_EPOCH + Duration::from_micros(entry.timestamp());
+                    attrs.last_metadata_changed = SystemTime::now();
+                    self.write_inode(&attrs)?;
+                    self.mark_hash_stale(&full_path)?;
+                }
+                None => {
+                    let doc = self.find_dir_doc(&change.dir).await?;
+                    let peers = sync_peers(&doc).await?;
+                    self.import_tree(&change.dir, doc, &peers, &mut |path| {
+                        debug!("{} added by a peer", path.display())
+                    })
+                    .await?;
+                }
+            }
+        }
+        self.manifest.save()
+    }
+
+    /// Drops `full_path` and everything below it from the manifest
+    fn forget_tree(&mut self, full_path: &Path) {
+        let paths: Vec<PathBuf> = self
+            .manifest
+            .inodes
+            .keys()
+            .filter(|path| path.starts_with(full_path))
+            .cloned()
+            .collect();
+        for path in paths {
+            if let Some(ino) = self.manifest.inodes.remove(&path) {
+                self.manifest.objects.remove(&ino);
+            }
+            self.read_only_dirs.remove(&path);
+            debug!("{} removed by a peer", path.display());
+        }
+    }
+}
+
+/// Peers `doc` synced with before, to sync with again
+pub(crate) async fn sync_peers(doc: &Doc) -> Result<Vec<NodeAddr>> {
+    let peers = doc.get_sync_peers().await?.unwrap_or_default();
+    Ok(peers
+        .iter()
+        .filter_map(|peer| PublicKey::from_bytes(peer).ok())
+        .map(NodeAddr::from)
+        .collect())
+}
+
+/// Waits for a doc's first sync and the content it brought. `false` if the peer doesn't have
+/// the doc
+async fn wait_for_sync(mut events: impl Stream<Item = Result<LiveEvent>> + Unpin) -> Result<bool> {
+    tokio::time::timeout(SYNC_TIMEOUT, async {
+        let mut synced = false;
+        while let Some(event) = events.next().await {
+            match event? {
+                LiveEvent::SyncFinished(sync) if sync.result.is_err() => return Ok(false),
+                LiveEvent::SyncFinished(_) => synced = true,
+                LiveEvent::PendingContentReady if synced => return Ok(true),
+                _ => {}
+            }
+        }
+        Err(anyhow!("Stopped getting sync events"))
+    })
+    .await
+    .map_err(|_| anyhow!("Timed out syncing from peers"))?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[tokio::test]
+    async fn join_syncs_remote_tree() {
+        let tmp_dir = TempDir::new().unwrap();
+        let mut shared = Lis::new(&tmp_dir.path().join("shared"), true)
+            .await
+            .unwrap();
+        shared
+            .mkdir(Path::new("/dir"), None, None, None, false)
+            .await
+            .unwrap();
+        // 32 bytes, like a directory's entry
+        for (path, content) in [
+            ("/a.txt", "hello"),
+            ("/dir/b.txt", "0123456789abcdef0123456789abcdef"),
+        ] {
+            shared
+                .touch(Path::new(path), None, None, None)
+                .await
+                .unwrap();
+            shared
+                .write(Path::new(path), content.as_bytes(), 0)
+                .await
+                .unwrap();
+        }
+
+        let ticket = shared.create_invite(false).await.unwrap();
+        let mut synced = Vec::new();
+        let mut joined = Lis::join(&tmp_dir.path().join("joined"), &ticket, |path| {
+            synced.push(path.to_path_buf())
+        })
+        .await
+        .unwrap();
+        synced.sort();
+        assert_eq!(synced, ["/a.txt", "/dir", "/dir/b.txt"].map(PathBuf::from));
+        assert!(joined.is_dir(Path::new("/dir")));
+        assert_eq!(joined.read(Path::new("/a.txt")).await.unwrap(), "hello");
+        assert_eq!(
+            joined.read(Path::new("/dir/b.txt")).await.unwrap(),
+            "0123456789abcdef0123456789abcdef"
+        );
+        joined
+            .touch(Path::new("/c.txt"), None, None, None)
+            .await
+            .unwrap();
+
+        // directories below the root are only readable, whatever the ticket
+        let e = joined
+            .touch(Path::new("/dir/c.txt"), None, None, None)
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            e.downcast_ref::<LisError>(),
+            Some(LisError::ReadOnly(_))
+        ));
+
+        // what the peer writes afterwards shows up too
+        shared
+            .write(Path::new("/a.txt"), b"hello again", 0)
+            .await
+            .unwrap();
+        shared
+            .mkdir(Path::new("/later"), None, None, None, false)
+            .await
+            .unwrap();
+        shared
+            .touch(Path::new("/later/d.txt"), None, None, None)
+            .await
+            .unwrap();
+        let a_size = |lis: &Lis| {
+            lis.obj_from_path(Path::new("/a.txt"))
+                .map(|obj| obj.attrs.size)
+        };
+        tokio::time::timeout(SYNC_TIMEOUT, async {
+            while !joined.exists(Path::new("/later/d.txt")) || a_size(&joined) != Some(11) {
+                tokio::time::sleep(Duration::from_millis(100)).await;
+                joined.apply_remote_changes().await.unwrap();
+            }
+        })
+        .await
+        .expect("peer's changes never showed up");
+        assert!(joined.is_dir(Path::new("/later")));
+        assert_eq!(
+            joined.read(Path::new("/a.txt")).await.unwrap(),
+            "hello again"
+        );
+
+        let ticket = shared.create_invite(true).await.unwrap();
+        let mut read_only = Lis::join(&tmp_dir.path().join("read-only"), &ticket, |_| {})
+            .await
+            .unwrap();
+        let e = read_only
+            .touch(Path::new("/c.txt"), None, None, None)
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            e.downcast_ref::<LisError>(),
+            Some(LisError::ReadOnly(_))
+        ));
+    }
+}
@@ -1,10 +1,23 @@
-use std::{ffi::OsStr, os::raw::c_int, str::FromStr, sync::atomic::Ordering};
+use std::{
+    collections::{BTreeSet, HashMap},
+    ffi::OsStr,
+    future::Future,
+    io,
+    os::{
+        raw::c_int,
+        unix::{ffi::OsStrExt, fs::PermissionsExt},
+    },
+    str::FromStr,
+    sync::{atomic::Ordering, Arc},
+};
 
-use bytes::Bytes;
+use bytes::{Bytes, BytesMut};
 use futures_lite::StreamExt;
 use iroh::{
-    client::docs::{Doc, Entry},
-    docs::{store::Query, NamespaceId},
+    base::node_addr::AddrInfoOptions,
+    blobs::{util::SetTagOption, Hash},
+    client::docs::{Doc, Entry, ShareMode},
+    docs::{store::Query, CapabilityKind, DocTicket, NamespaceId},
     net::ticket::NodeTicket,
     node::Node,
 };
@@ -19,14 +32,73 @@ pub use manifest::Manifest;
 mod util;
 use util::*;
 
+mod error;
+pub use error::LisError;
+
 mod cli;
-pub use cli::{Cli, Commands};
+pub use cli::{write_completions, Cli, Commands, Format};
+
+mod config;
+pub use config::Config;
 
 mod fuse;
-use fuse::{check_access, clear_suid_sgid, FileKind, InodeAttributes};
+pub use fuse::FileKind;
+use fuse::{check_access, clear_suid_sgid, InodeAttributes};
 
 mod object;
 use object::Object;
+pub use object::{Metadata, MetadataPatch, StatInfo};
+
+mod doctor;
+pub use doctor::{Check, CheckStatus};
+
+mod file;
+pub use file::{LisFileReader, LisFileWriter};
+
+mod collection;
+
+mod hash;
+pub use hash::HashAlgorithm;
+
+mod delta;
+pub use delta::{changed_windows, delta, DeltaOp, RollingChecksum, WindowHash};
+
+mod audit;
+pub use audit::{AuditEntry, AuditOp};
+
+mod trash;
+
+mod link;
+
+mod fsync;
+pub use fsync::CheckpointId;
+
+mod statfs;
+pub use statfs::FsStats;
+
+mod orphans;
+pub use orphans::OrphanReport;
+
+mod dedup;
+pub use dedup::DedupStats;
+
+mod cat;
+pub use cat::Span;
+
+mod follow;
+
+mod join;
+use join::RemoteChanges;
+
+mod annotation;
+
+mod mtime;
+pub use mtime::ImportMtime;
+
+mod walk;
+
+mod counters;
+pub use counters::FuseCounters;
 
 // mod directory;
 // use directory::Directory;
@@ -37,6 +109,34 @@ pub struct Lis {
     pub rt: tokio::runtime::Handle,
     root_doc: Doc,
     pub root: PathBuf,
+    /// Bypass the kernel page cache on open (`FOPEN_DIRECT_IO`). When `false`, opened files keep
+    /// their cached pages (`FOPEN_KEEP_CACHE`) unless their content changed since the last open
+    pub direct_io: bool,
+    /// Creating an entry in a directory that already has this many fails with
+    /// `LisError::DirectoryFull`
+    pub max_dir_entries: u64,
+    /// Opening a file while this many are open fails with `LisError::TooManyOpenFiles`
+    pub max_open_files: u64,
+    /// Files currently open through the mount
+    open_files: u64,
+    /// Never update access times on read. Otherwise they're updated relatime-style
+    pub noatime: bool,
+    /// Move removed files and directories to `/.trash` instead of deleting them
+    pub trash: bool,
+    /// Modification time imported files get
+    pub import_mtime: ImportMtime,
+    /// Joined with a read-only ticket, nothing can be changed
+    read_only: bool,
+    /// Directories whose doc this node can only read, e.g. the ones below the root of a tree
+    /// joined with a read-write ticket
+    read_only_dirs: BTreeSet<PathBuf>,
+    /// Entries peers wrote, waiting for `apply_remote_changes`
+    remote_changes: RemoteChanges,
+    /// Calls to the FUSE callbacks, clone it before mounting to read it afterwards
+    pub fuse_counters: Arc<FuseCounters>,
+    /// Content hash and size of each file when it was last opened through the mount. Its cached
+    /// pages are only kept while these don't change
+    opened_content: HashMap<u64, (Option<Hash>, u64)>,
 }
 
 impl Lis {
@@ -48,9 +148,19 @@ impl Lis {
         if overwrite {
             // TODO: add prompt for overwrite: are you sure? [Y/n]
 
-            // remove old root dir if one existed before
-            let _ = fs::remove_dir_all(root); // don't care about result. if dir not exists it's
-                                              // fine
+            // remove what an old node left in root, but keep its config
+            if let Ok(mut entries) = fs::read_dir(root).await {
+                while let Some(entry) = entries.next_entry().await? {
+                    if entry.file_name() == config::CONFIG_FILE_NAME {
+                        continue;
+                    }
+                    if entry.file_type().await?.is_dir() {
+                        fs::remove_dir_all(entry.path()).await?;
+                    } else {
+                        fs::remove_file(entry.path()).await?;
+                    }
+                }
+            }
         }
         // create root if not exists
         fs::create_dir_all(root.clone()).await?;
@@ -87,30 +197,85 @@ impl Lis {
             }
         };
 
-        let lis = Lis {
+        Lis::from_parts(root, iroh_node, manifest, root_doc).await
+    }
+
+    /// Puts together a node whose root dir is `root_doc`, with the default options
+    async fn from_parts(
+        root: &Path,
+        iroh_node: Node<iroh::blobs::store::fs::Store>,
+        manifest: Manifest,
+        root_doc: Doc,
+    ) -> Result<Self> {
+        let mut read_only = false;
+        let mut docs = iroh_node.docs().list().await?;
+        while let Some(doc) = docs.next().await {
+            let (id, capability) = doc?;
+            if id == root_doc.id() {
+                read_only = matches!(capability, CapabilityKind::Read);
+            }
+        }
+
+        let mut lis = Lis {
             iroh_node,
             manifest,
             rt: tokio::runtime::Handle::current(),
             root_doc,
-            root: root.clone(),
+            root: root.to_path_buf(),
+            direct_io: true,
+            max_dir_entries: DEFAULT_MAX_DIR_ENTRIES,
+            max_open_files: DEFAULT_MAX_OPEN_FILES,
+            open_files: 0,
+            noatime: false,
+            trash: false,
+            import_mtime: ImportMtime::default(),
+            read_only,
+            read_only_dirs: BTreeSet::new(),
+            remote_changes: RemoteChanges::default(),
+            fuse_counters: Arc::default(),
+            opened_content: HashMap::new(),
         };
+        lis.watch_tree().await?;
         Ok(lis)
     }
 
+    /// Errors with `LisError::ReadOnly` if the node was joined with a read-only ticket, or the
+    /// doc of the directory `full_path` is in is one this node can only read
+    pub fn check_writable(&self, full_path: &Path) -> Result<(), LisError> {
+        let dir = full_path.parent().unwrap_or(full_path);
+        if self.read_only || self.read_only_dirs.contains(dir) {
+            return Err(LisError::ReadOnly(full_path.to_path_buf()));
+        }
+        Ok(())
+    }
+
     /// Creates a new inode for use
     pub fn next_ino(&mut self) -> Inode {
-        let ino = self.manifest.cur_ino.fetch_add(1, Ordering::SeqCst).into();
+        let ino = self.manifest.cur_ino.fetch_add(1, Ordering::SeqCst);
         self.manifest
             .save()
             .expect("could not write to manifest file");
         ino
     }
 
+    /// Errors with `LisError::TooManyOpenFiles` if opening `full_path` would go over
+    /// `max_open_files`
+    pub fn check_open_files(&self, full_path: &Path) -> Result<(), LisError> {
+        if self.open_files >= self.max_open_files {
+            return Err(LisError::TooManyOpenFiles(full_path.to_path_buf()));
+        }
+        Ok(())
+    }
+
     /// Creates a new file handle for use
     pub fn next_file_handle(&mut self, read: bool, write: bool) -> FileHandle {
-        let mut fh = self.manifest.cur_fh.fetch_add(1, Ordering::SeqCst).into();
+        let mut fh = self.manifest.cur_fh.fetch_add(1, Ordering::SeqCst);
         // Assert that we haven't run out of file handles
-        assert!(fh < FILE_HANDLE_READ_BIT.min(FILE_HANDLE_WRITE_BIT));
+        assert!(
+            fh < FILE_HANDLE_READ_BIT
+                .min(FILE_HANDLE_WRITE_BIT)
+                .min(FILE_HANDLE_NOATIME_BIT)
+        );
         if read {
             fh |= FILE_HANDLE_READ_BIT;
         }
@@ -127,13 +292,16 @@ impl Lis {
     /// Create new empty file on lis
     pub async fn touch(
         &mut self,
-        full_path: &PathBuf,
+        full_path: &Path,
         mode: Option<u16>,
         uid: Option<u32>,
         gid: Option<u32>,
     ) -> Result<()> {
+        self.check_writable(full_path)?;
+        validate_path(full_path)?;
+
         // find doc where file will live
-        let (doc, key) = self.doc_and_key(&full_path).await?;
+        let (doc, key) = self.doc_and_key(full_path).await?;
 
         // if key already in filesystem, do nothing and return Ok
         let query = Query::key_exact(key.clone());
@@ -141,6 +309,8 @@ impl Lis {
             return Ok(());
         }
 
+        self.check_dir_capacity(&doc, full_path).await?;
+
         let default_author = self.iroh_node.authors().default().await?;
         let content = b"null"; //cannot be b"" because iroh will think it's a deleted file
         doc.set_bytes(default_author, key.to_vec(), content.to_vec())
@@ -148,11 +318,63 @@ impl Lis {
 
         // add file obj to filesystem
         let size: u64 = 4;
-        self.create_fs_objects(&full_path, FileKind::File, Some(size), mode, uid, gid)?;
+        self.create_fs_objects(full_path, FileKind::File, Some(size), mode, uid, gid)?;
+        self.mark_hash_stale(full_path)?;
+        self.audit(AuditOp::Create, full_path).await?;
+
+        Ok(())
+    }
+
+    /// Creates a symlink at `link` pointing to `target`, which is stored as the entry's content
+    /// `target` is kept as given and doesn't have to exist
+    pub async fn create_symlink(
+        &mut self,
+        link: &Path,
+        target: &Path,
+        uid: Option<u32>,
+        gid: Option<u32>,
+    ) -> Result<()> {
+        self.check_writable(link)?;
+        validate_path(link)?;
+        let target = target.as_os_str().as_bytes();
+        if target.is_empty() {
+            return Err(LisError::InvalidName(link.to_path_buf()).into());
+        }
+
+        let (doc, key) = self.doc_and_key(link).await?;
+        if doc.get_one(Query::key_exact(key.clone())).await?.is_some() {
+            return Err(LisError::AlreadyExists(link.to_path_buf()).into());
+        }
+        self.check_dir_capacity(&doc, link).await?;
+
+        let default_author = self.iroh_node.authors().default().await?;
+        doc.set_bytes(default_author, key.to_vec(), target.to_vec())
+            .await?;
+
+        self.create_fs_objects(
+            link,
+            FileKind::Symlink,
+            Some(target.len() as u64),
+            None,
+            uid,
+            gid,
+        )?;
+        self.audit(AuditOp::Create, link).await?;
 
         Ok(())
     }
 
+    /// Target of the symlink at `link`
+    pub async fn read_link(&mut self, link: &Path) -> Result<PathBuf> {
+        match self.obj_from_path(link) {
+            Some(obj) if matches!(obj.attrs.kind, FileKind::Symlink) => {}
+            Some(_) => return Err(LisError::NotASymlink(link.to_path_buf()).into()),
+            None => return Err(LisError::NotFound(link.to_path_buf()).into()),
+        }
+        let target = self.read(link).await?;
+        Ok(PathBuf::from(OsStr::from_bytes(&target)))
+    }
+
     /// List all files in node
     pub async fn list(&self, full_path: &Path) -> Result<Vec<Result<Entry>>> {
         let mut path = full_path;
@@ -163,27 +385,71 @@ impl Lis {
 
         // iterate until last dir
         let mut doc = self.root_doc.clone();
+        let mut walked = PathBuf::from("/");
         for dir in path.iter() {
+            walked.push(dir);
             doc = match self.next_doc(&doc, Path::new(dir)).await? {
                 Some(next_doc) => next_doc,
-                None => {
-                    return Err(anyhow!(
-                        "could not find {} in tree",
-                        Path::new(dir).display()
-                    ))
-                }
+                None => return Err(LisError::NotFound(walked).into()),
             };
         }
 
         let query = Query::all().build();
-        let entries = doc.get_many(query).await?.collect::<Vec<_>>().await;
+        let entries = doc
+            .get_many(query)
+            .await?
+            .filter(|entry| !matches!(entry, Ok(entry) if is_annotation_key(entry.key())))
+            .collect::<Vec<_>>()
+            .await;
 
         Ok(entries)
     }
 
+    /// Lists a directory along with each entry's metadata, which comes from the manifest in the
+    /// same pass instead of a `stat` per entry. Names are relative to `full_path`. Entries the
+    /// manifest doesn't know yet, e.g. synced from a peer a moment ago, are left out
+    pub async fn list_with_metadata(&self, full_path: &Path) -> Result<Vec<(PathBuf, Metadata)>> {
+        let entries = self.list(full_path).await?;
+        let mut listed = Vec::with_capacity(entries.len());
+        for entry in entries {
+            let name = PathBuf::from(key_to_string(entry?.key().to_vec().into())?);
+            let entry_path = full_path.join(&name);
+            match self.obj_from_path(&entry_path) {
+                Some(obj) => listed.push((name, Metadata::from(&obj.attrs))),
+                None => warn!(
+                    "{} is not in the manifest, skipping it",
+                    entry_path.display()
+                ),
+            }
+        }
+        Ok(listed)
+    }
+
     pub fn obj_from_path(&self, full_path: &Path) -> Option<&Object> {
         let ino = self.manifest.inodes.get(full_path)?;
-        self.manifest.objects.get(&ino)
+        self.manifest.objects.get(ino)
+    }
+
+    /// Metadata of `full_path`, or `None` if it doesn't exist. Only the manifest is looked at,
+    /// unless the file's secondary hash is stale and has to be recomputed
+    pub async fn stat(&mut self, full_path: &Path) -> Result<Option<StatInfo>> {
+        let full_path = add_leading_slash(full_path);
+        if full_path != Path::new("/") {
+            validate_path(&full_path)?;
+        }
+        self.refresh_hash(&full_path).await?;
+        Ok(self.obj_from_path(&full_path).map(StatInfo::from))
+    }
+
+    /// Whether `full_path` exists
+    pub fn exists(&self, full_path: &Path) -> bool {
+        self.obj_from_path(&add_leading_slash(full_path)).is_some()
+    }
+
+    /// Whether `full_path` is a directory known to the manifest
+    pub fn is_dir(&self, full_path: &Path) -> bool {
+        self.obj_from_path(full_path)
+            .is_some_and(|obj| matches!(obj.attrs.kind, FileKind::Directory))
     }
 
     pub fn write_inode(&mut self, attrs: &InodeAttributes) -> Result<()> {
@@ -197,44 +463,289 @@ impl Lis {
         }
     }
 
+    /// Applies metadata patches to many objects and saves the manifest once
+    /// Metadata only lives in the manifest, so nothing is applied unless every path exists
+    pub fn set_metadata_many(&mut self, updates: &[(PathBuf, MetadataPatch)]) -> Result<()> {
+        for (path, _) in updates {
+            self.check_writable(path)?;
+        }
+        let inodes = updates
+            .iter()
+            .map(|(path, _)| {
+                self.manifest
+                    .inodes
+                    .get(path)
+                    .copied()
+                    .ok_or_else(|| LisError::NotFound(path.clone()))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        for (ino, (_, patch)) in inodes.into_iter().zip(updates) {
+            if let Some(obj) = self.manifest.objects.get_mut(&ino) {
+                patch.apply(&mut obj.attrs);
+            }
+        }
+        self.manifest.save()
+    }
+
+    /// Sets or clears the free-form description of the object at `full_path`
+    pub fn set_description(&mut self, full_path: &Path, description: Option<String>) -> Result<()> {
+        self.check_writable(full_path)?;
+        let ino = *self
+            .manifest
+            .inodes
+            .get(full_path)
+            .ok_or_else(|| LisError::NotFound(full_path.to_path_buf()))?;
+        if let Some(obj) = self.manifest.objects.get_mut(&ino) {
+            obj.description = description;
+            obj.attrs.last_metadata_changed = SystemTime::now();
+        }
+        self.manifest.save()
+    }
+
     /// Adds files and directories to Lis
-    /// Returns `(path, key)` pairs of the added file upon success
+    /// A directory is imported with everything below it. Modes are kept. Existing files and
+    /// directories at the destination are an error, unless `overwrite` is set
+    /// Returns `(path, key)` pairs of the added files upon success
     pub async fn import_file(
         &mut self,
         src_path: &Path,
         dst_path: &Path,
+        overwrite: bool,
     ) -> Result<Vec<(PathBuf, String)>> {
         if !src_path.exists() {
-            return Err(anyhow!("Path {} does not exist", src_path.display()));
+            return Err(LisError::NotFound(src_path.to_path_buf()).into());
         }
 
         let full_src_path = fs::canonicalize(&src_path).await?;
-        if !full_src_path.is_file() {
-            return Err(anyhow!("{} is not a file", full_src_path.display()));
-        }
         let full_dst_path = add_leading_slash(dst_path);
+        validate_path(&full_dst_path)?;
 
-        // TODO: call write
-        let (doc, key) = self.doc_and_key(&full_dst_path).await?;
+        if full_src_path.is_file() {
+            let imported = self
+                .import_one_file(&full_src_path, &full_dst_path, overwrite)
+                .await?;
+            return Ok(vec![(src_path.to_path_buf(), imported)]);
+        }
+        if !full_src_path.is_dir() {
+            return Err(anyhow!(
+                "{} is not a file or directory",
+                full_src_path.display()
+            ));
+        }
+
+        let mut imported = Vec::new();
+        let mut dirs = vec![(full_src_path, full_dst_path)];
+        while let Some((src_dir, dst_dir)) = dirs.pop() {
+            let mode = fs::metadata(&src_dir).await?.permissions().mode() & 0o7777;
+            self.mkdir(&dst_dir, Some(mode as u16), None, None, overwrite)
+                .await?;
+
+            let mut entries = fs::read_dir(&src_dir).await?;
+            while let Some(entry) = entries.next_entry().await? {
+                let src = entry.path();
+                let dst = dst_dir.join(entry.file_name());
+                let file_type = entry.file_type().await?;
+                if file_type.is_dir() {
+                    dirs.push((src, dst));
+                } else if file_type.is_file() {
+                    let key = self.import_one_file(&src, &dst, overwrite).await?;
+                    imported.push((src, key));
+                } else {
+                    warn!("Skipping {}, not a file or directory", src.display());
+                }
+            }
+        }
+        Ok(imported)
+    }
+
+    /// Imports the host file `src_path` to `full_dst_path`, returning its key
+    async fn import_one_file(
+        &mut self,
+        src_path: &Path,
+        full_dst_path: &Path,
+        overwrite: bool,
+    ) -> Result<String> {
+        self.check_writable(full_dst_path)?;
+        let (doc, key) = self.doc_and_key(full_dst_path).await?;
 
         let default_author = self.iroh_node.authors().default().await?;
         let query = Query::key_exact(key.clone());
         if doc.get_one(query).await?.is_some() {
+            if !overwrite {
+                return Err(LisError::AlreadyExists(full_dst_path.to_path_buf()).into());
+            }
+            if self.is_dir(full_dst_path) {
+                return Err(LisError::IsADirectory(full_dst_path.to_path_buf()).into());
+            }
             doc.del(default_author, key.clone()).await?; // delete old entry
+        } else {
+            self.check_dir_capacity(&doc, full_dst_path).await?;
         }
 
-        doc.import_file(default_author, key.clone(), full_src_path, false)
+        doc.import_file(default_author, key.clone(), src_path.to_path_buf(), false)
             .await?
             .collect::<Vec<_>>()
             .await;
 
-        let size = fs::metadata(src_path).await?.len();
-        self.create_fs_objects(&full_dst_path, FileKind::File, Some(size), None, None, None)?;
+        let hash = doc
+            .get_one(Query::key_exact(key))
+            .await?
+            .ok_or_else(|| anyhow!("Could not import {}", src_path.display()))?
+            .content_hash();
+        let metadata = fs::metadata(src_path).await?;
+        let size = metadata.len();
+        let mode = (metadata.permissions().mode() & 0o7777) as u16;
+        let ino = match self.obj_from_path(full_dst_path) {
+            Some(obj) => obj.attrs.inode,
+            None => self.create_fs_objects(
+                full_dst_path,
+                FileKind::File,
+                Some(size),
+                Some(mode),
+                None,
+                None,
+            )?,
+        };
+        let mut attrs = self.manifest.objects[&ino].attrs.clone();
+        attrs.size = size;
+        attrs.mode = mode;
+        attrs.last_modified = self.import_mtime.mtime(&hash);
+        attrs.last_metadata_changed = SystemTime::now();
+        self.write_inode(&attrs)?;
+        self.sync_links(full_dst_path).await?;
+        self.mark_hash_stale(full_dst_path)?;
+        self.audit(AuditOp::Import, full_dst_path).await?;
+
+        let original_filename = src_path
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string());
+        let imported_by = self.iroh_node.node_id().to_string();
+        if let Some(ino) = self.manifest.inodes.get(full_dst_path).copied() {
+            if let Some(obj) = self.manifest.objects.get_mut(&ino) {
+                obj.original_filename = original_filename;
+                obj.imported_by = Some(imported_by);
+            }
+        }
+        self.manifest.save()?;
+
+        Ok(full_dst_path.to_string_lossy().to_string())
+    }
+
+    /// Downloads `url` (following redirects) and streams it into Lis at `dst_path`
+    /// An existing file is only replaced with `overwrite`. Returns the hash of the stored content
+    /// so it can be verified
+    pub async fn import_from_url(
+        &mut self,
+        url: &str,
+        dst_path: &Path,
+        overwrite: bool,
+    ) -> Result<Hash> {
+        validate_path(dst_path)?;
+        let full_dst_path = add_leading_slash(dst_path);
+        self.check_writable(&full_dst_path)?;
+
+        // before downloading anything
+        let (doc, key) = self.doc_and_key(&full_dst_path).await?;
+        let query = Query::key_exact(key);
+        if doc.get_one(query).await?.is_some() {
+            if !overwrite {
+                return Err(LisError::AlreadyExists(full_dst_path).into());
+            }
+            if self.is_dir(&full_dst_path) {
+                return Err(LisError::IsADirectory(full_dst_path).into());
+            }
+        } else {
+            self.check_dir_capacity(&doc, &full_dst_path).await?;
+        }
+
+        let response = reqwest::get(url).await?.error_for_status()?;
+        let source_url = response.url().to_string();
+        let original_filename = response
+            .headers()
+            .get(reqwest::header::CONTENT_DISPOSITION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(content_disposition_filename)
+            .or_else(|| {
+                response
+                    .url()
+                    .path_segments()
+                    .and_then(|mut segments| segments.next_back())
+                    .filter(|name| !name.is_empty())
+                    .map(str::to_string)
+            });
+        let content_type = response
+            .headers()
+            .get(reqwest::header::CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_string);
+
+        let stream = response
+            .bytes_stream()
+            .map(|chunk| chunk.map_err(io::Error::other));
+        let outcome = self
+            .iroh_node
+            .blobs()
+            .add_stream(stream, SetTagOption::Auto)
+            .await?
+            .finish()
+            .await?;
+        if outcome.size == 0 {
+            self.iroh_node.tags().delete(outcome.tag).await?;
+            // iroh would take a zero-length entry for a deleted file
+            return Err(anyhow!("{url} returned an empty body, nothing to import"));
+        }
+
+        let put = self
+            .put_hash(&full_dst_path, outcome.hash, outcome.size)
+            .await;
+        // the doc entry keeps the blob from here on, the tag would keep it once the file is gone
+        self.iroh_node.tags().delete(outcome.tag).await?;
+        let ino = put?;
+        if let Some(obj) = self.manifest.objects.get_mut(&ino) {
+            obj.source_url = Some(source_url);
+            obj.content_type = content_type;
+            obj.original_filename = original_filename;
+            obj.imported_by = Some(self.iroh_node.node_id().to_string());
+        }
+        self.manifest.save()?;
+
+        Ok(outcome.hash)
+    }
+
+    /// Points the file at `full_path` to content already in the blob store, creating the file if
+    /// needed. Returns its inode
+    async fn put_hash(&mut self, full_path: &Path, hash: Hash, size: u64) -> Result<Inode> {
+        self.check_writable(full_path)?;
+        validate_path(full_path)?;
+        let (doc, key) = self.doc_and_key(full_path).await?;
+        let default_author = self.iroh_node.authors().default().await?;
+        let query = Query::key_exact(key.clone());
+        if doc.get_one(query).await?.is_some() {
+            if self.is_dir(full_path) {
+                return Err(LisError::IsADirectory(full_path.to_path_buf()).into());
+            }
+            doc.del(default_author, key.clone()).await?; // delete old entry
+        } else {
+            self.check_dir_capacity(&doc, full_path).await?;
+        }
+        doc.set_hash(default_author, key, hash, size).await?;
 
-        Ok(vec![(
-            src_path.to_path_buf(),
-            full_dst_path.to_string_lossy().to_string(),
-        )])
+        let ino = match self.obj_from_path(full_path) {
+            Some(obj) => obj.attrs.inode,
+            None => {
+                self.create_fs_objects(full_path, FileKind::File, Some(size), None, None, None)?
+            }
+        };
+        let mut attrs = self.manifest.objects[&ino].attrs.clone();
+        attrs.size = size;
+        attrs.last_modified = self.import_mtime.mtime(&hash);
+        attrs.last_metadata_changed = SystemTime::now();
+        self.write_inode(&attrs)?;
+        self.sync_links(full_path).await?;
+        self.mark_hash_stale(full_path)?;
+        self.audit(AuditOp::Import, full_path).await?;
+        Ok(ino)
     }
 
     /// Given a full_path, returns the doc where the file is located and its key in that doc
@@ -248,22 +759,41 @@ impl Lis {
 
         let doc = self
             .find_dir_doc(
-                &full_path
+                full_path
                     .parent()
-                    .ok_or(anyhow!("Could not find Doc for parent dir"))?
-                    .to_path_buf(),
+                    .ok_or(anyhow!("Could not find Doc for parent dir"))?,
             )
             .await?;
 
         Ok((doc, key))
     }
 
+    /// Errors with `LisError::DirectoryFull` if the directory `doc`, where `full_path` is about to
+    /// be created, is at `max_dir_entries`
+    async fn check_dir_capacity(&self, doc: &Doc, full_path: &Path) -> Result<()> {
+        let query = Query::all().build();
+        let entries = doc
+            .get_many(query)
+            .await?
+            .filter(|entry| !matches!(entry, Ok(entry) if is_annotation_key(entry.key())))
+            .take(self.max_dir_entries as usize)
+            .count()
+            .await as u64;
+        if entries >= self.max_dir_entries {
+            let dir = full_path.parent().unwrap_or(full_path);
+            return Err(LisError::DirectoryFull(dir.to_path_buf()).into());
+        }
+        Ok(())
+    }
+
     /// Writes data to a path
     async fn write(&mut self, full_path: &Path, data: &[u8], offset: usize) -> Result<()> {
-        let mut content = match self.read(&full_path).await?.try_into_mut() {
-            Ok(mut_content) => mut_content,
-            Err(_) => return Err(anyhow!("Could not get mutable byte array")),
-        };
+        self.check_writable(full_path)?;
+        let mut content = self
+            .read(full_path)
+            .await?
+            .try_into_mut()
+            .unwrap_or_else(|shared| BytesMut::from(&shared[..]));
 
         // make sure has enough size
         let required_length = offset + data.len();
@@ -275,7 +805,7 @@ impl Lis {
         content[offset..offset + data.len()].copy_from_slice(data);
 
         // remove old content
-        let (doc, key) = self.doc_and_key(&full_path).await?;
+        let (doc, key) = self.doc_and_key(full_path).await?;
         let default_author = self.iroh_node.authors().default().await?;
         let query = Query::key_exact(key.clone());
 
@@ -286,6 +816,16 @@ impl Lis {
         // save new buffer to doc
         doc.set_bytes(default_author, key.to_vec(), content.freeze())
             .await?;
+        if let Some(obj) = self.obj_from_path(full_path) {
+            let mut attrs = obj.attrs.clone();
+            attrs.size = attrs.size.max(required_length as u64);
+            attrs.last_modified = SystemTime::now();
+            attrs.last_metadata_changed = SystemTime::now();
+            self.write_inode(&attrs)?;
+        }
+        self.sync_links(full_path).await?;
+        self.mark_hash_stale(full_path)?;
+        self.audit(AuditOp::Write, full_path).await?;
 
         Ok(())
     }
@@ -298,7 +838,7 @@ impl Lis {
         mode: Option<u16>,
         uid: Option<u32>,
         gid: Option<u32>,
-    ) -> Result<()> {
+    ) -> Result<Inode> {
         let inode = self.next_ino();
         let obj = Object::new(full_path, inode, kind, size, mode, uid, gid)?;
 
@@ -309,19 +849,284 @@ impl Lis {
 
         self.manifest.save()?;
 
-        Ok(())
+        Ok(inode)
     }
 
-    /// Remove a file
+    /// Remove a file, or move it to the trash if that's enabled
+    /// Directories are refused, they go through `rmdir`. The file's inode is released once
+    /// nothing has it open
     pub async fn remove(&mut self, full_path: &Path) -> Result<()> {
+        self.check_writable(full_path)?;
         let (doc, key) = self.doc_and_key(full_path).await?;
+        if doc.get_one(Query::key_exact(key.clone())).await?.is_none() {
+            return Err(LisError::NotFound(full_path.to_path_buf()).into());
+        }
+        if self.is_dir(full_path) {
+            return Err(
+                anyhow::Error::new(LisError::IsADirectory(full_path.to_path_buf())).context(
+                    format!(
+                        "Cannot remove {}, use rmdir for directories",
+                        full_path.display()
+                    ),
+                ),
+            );
+        }
+
+        if self.trashes(full_path) {
+            return self.move_to_trash(full_path).await;
+        }
 
         doc.del(self.iroh_node.authors().default().await?, key.clone())
             .await?;
+        self.drop_annotations(full_path).await?;
+        self.audit(AuditOp::Remove, full_path).await?;
+
+        if let Some(obj) = self.obj_from_path(full_path) {
+            let mut attrs = obj.attrs.clone();
+            attrs.hardlinks = attrs.hardlinks.saturating_sub(1);
+            attrs.last_metadata_changed = SystemTime::now();
+            if attrs.hardlinks > 0 {
+                self.forget_link(full_path, attrs.inode);
+            }
+            self.write_inode(&attrs)?;
+            self.gc_inode(&attrs)?;
+        }
+
+        Ok(())
+    }
+
+    /// Moves `from` to `to` without copying content, keeping its inode
+    /// With `overwrite`, an existing `to` is replaced like POSIX `rename(2)` does: a file by a
+    /// file, an empty directory by a directory. Otherwise an existing `to` is an error
+    /// (`RENAME_NOREPLACE`). The replaced content is left to Iroh's GC once nothing references it
+    pub async fn rename(&mut self, from: &Path, to: &Path, overwrite: bool) -> Result<()> {
+        self.check_writable(to)?;
+        if from == to {
+            return Ok(());
+        }
+        if from == Path::new("/") || to == Path::new("/") {
+            return Err(anyhow!("Cannot rename root dir"));
+        }
+        validate_path(to)?;
+        if to.starts_with(from) {
+            return Err(anyhow!("Cannot move {} into itself", from.display()));
+        }
+
+        let (from_doc, from_key) = self.doc_and_key(from).await?;
+        let entry = from_doc
+            .get_one(Query::key_exact(from_key.clone()))
+            .await?
+            .ok_or_else(|| LisError::NotFound(from.to_path_buf()))?;
+        if self.obj_from_path(from).is_none() {
+            return Err(LisError::NotFound(from.to_path_buf()).into());
+        }
+        let from_is_dir = self.is_dir(from);
+        // hardlinks of the same file, POSIX leaves both in place
+        if self.manifest.inodes.get(from) == self.manifest.inodes.get(to) {
+            return Ok(());
+        }
+
+        let (to_doc, to_key) = self.doc_and_key(to).await?;
+        let author = self.iroh_node.authors().default().await?;
+
+        // deal with whatever is at the destination
+        if to_doc
+            .get_one(Query::key_exact(to_key.clone()))
+            .await?
+            .is_some()
+        {
+            if !overwrite {
+                return Err(LisError::AlreadyExists(to.to_path_buf()).into());
+            }
+            match (from_is_dir, self.is_dir(to)) {
+                (false, true) => return Err(LisError::IsADirectory(to.to_path_buf()).into()),
+                (true, false) => return Err(LisError::NotADirectory(to.to_path_buf()).into()),
+                // fails if the directory is not empty
+                (true, true) => self.drop_dir(to).await?,
+                // the `set_hash` below replaces the entry in one go, it never goes missing
+                (false, false) => {}
+            }
+            if let Some(to_obj) = self.obj_from_path(to) {
+                let mut attrs = to_obj.attrs.clone();
+                if !from_is_dir && attrs.hardlinks > 1 {
+                    // other names of the replaced file keep it
+                    attrs.hardlinks -= 1;
+                    attrs.last_metadata_changed = SystemTime::now();
+                    self.forget_link(to, attrs.inode);
+                    self.write_inode(&attrs)?;
+                } else if let Some(to_ino) = self.manifest.inodes.remove(to) {
+                    self.manifest.objects.remove(&to_ino);
+                }
+            }
+        } else if to.parent() != from.parent() {
+            self.check_dir_capacity(&to_doc, to).await?;
+        }
+
+        // point the destination key to the same content. For directories, that's the doc id
+        to_doc
+            .set_hash(author, to_key, entry.content_hash(), entry.content_len())
+            .await?;
+        from_doc.del(author, from_key).await?;
+        self.move_annotations(from, to).await?;
+
+        // move the object and everything below it, keeping their inodes
+        let moved: Vec<(PathBuf, Inode)> = self
+            .manifest
+            .inodes
+            .range(from.to_path_buf()..)
+            .take_while(|(path, _)| path.starts_with(from))
+            .map(|(path, ino)| (path.clone(), *ino))
+            .collect();
+        for (old_path, ino) in moved {
+            let new_path = to.join(old_path.strip_prefix(from)?);
+            self.manifest.inodes.remove(&old_path);
+            self.manifest.inodes.insert(new_path.clone(), ino);
+            if let Some(obj) = self.manifest.objects.get_mut(&ino) {
+                obj.full_path = new_path;
+            }
+        }
+        self.manifest.save()?;
+        debug!("Renamed {} to {}", from.display(), to.display());
+        self.audit(
+            AuditOp::Rename {
+                to: to.to_path_buf(),
+            },
+            from,
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    /// Swaps what's at `a` and `b`, which must both exist. Either can be a file or a directory
+    /// Each key is pointed to the other's content in place instead of being deleted and added
+    /// again, so readers of either path always find something there
+    pub async fn exchange(&mut self, a: &Path, b: &Path) -> Result<()> {
+        self.check_writable(a)?;
+        if a == Path::new("/") || b == Path::new("/") {
+            return Err(anyhow!("Cannot exchange root dir"));
+        }
+        if a.starts_with(b) || b.starts_with(a) {
+            return Err(anyhow!(
+                "Cannot exchange {} with {}, one is inside the other",
+                a.display(),
+                b.display()
+            ));
+        }
+        for path in [a, b] {
+            if self.obj_from_path(path).is_none() {
+                return Err(LisError::NotFound(path.to_path_buf()).into());
+            }
+        }
+        // hardlinks of the same file, there's nothing to swap
+        if self.manifest.inodes.get(a) == self.manifest.inodes.get(b) {
+            return Ok(());
+        }
+
+        let (a_doc, a_key) = self.doc_and_key(a).await?;
+        let (b_doc, b_key) = self.doc_and_key(b).await?;
+        let a_entry = a_doc
+            .get_one(Query::key_exact(a_key.clone()))
+            .await?
+            .ok_or_else(|| LisError::NotFound(a.to_path_buf()))?;
+        let b_entry = b_doc
+            .get_one(Query::key_exact(b_key.clone()))
+            .await?
+            .ok_or_else(|| LisError::NotFound(b.to_path_buf()))?;
+
+        let author = self.iroh_node.authors().default().await?;
+        a_doc
+            .set_hash(author, a_key, b_entry.content_hash(), b_entry.content_len())
+            .await?;
+        b_doc
+            .set_hash(author, b_key, a_entry.content_hash(), a_entry.content_len())
+            .await?;
+        self.swap_annotations(a, b).await?;
+
+        // swap the objects and everything below them, keeping their inodes
+        let mut moved = Vec::new();
+        for (from, to) in [(a, b), (b, a)] {
+            for (old_path, ino) in self
+                .manifest
+                .inodes
+                .range(from.to_path_buf()..)
+                .take_while(|(path, _)| path.starts_with(from))
+            {
+                moved.push((
+                    old_path.clone(),
+                    to.join(old_path.strip_prefix(from)?),
+                    *ino,
+                ));
+            }
+        }
+        for (old_path, _, _) in &moved {
+            self.manifest.inodes.remove(old_path);
+        }
+        let now = SystemTime::now();
+        for (old_path, new_path, ino) in moved {
+            self.manifest.inodes.insert(new_path.clone(), ino);
+            if let Some(obj) = self.manifest.objects.get_mut(&ino) {
+                if obj.full_path == old_path {
+                    obj.full_path = new_path.clone();
+                }
+                if new_path == a || new_path == b {
+                    obj.attrs.last_metadata_changed = now;
+                }
+            }
+        }
+        self.manifest.save()?;
+        debug!("Exchanged {} and {}", a.display(), b.display());
+        self.audit(
+            AuditOp::Exchange {
+                with: b.to_path_buf(),
+            },
+            a,
+        )
+        .await?;
 
         Ok(())
     }
 
+    /// Makes the fully-written directory `staging_path` visible at `public_path` in one step
+    /// Missing parents of `public_path` are created first. The move itself re-keys the staging
+    /// directory's doc under its new parent, so readers see either nothing or the whole subtree
+    pub async fn publish(&mut self, staging_path: &Path, public_path: &Path) -> Result<()> {
+        if !self.is_dir(staging_path) {
+            return Err(LisError::NotADirectory(staging_path.to_path_buf()).into());
+        }
+        self.mkdir_parents(public_path).await?;
+        self.rename(staging_path, public_path, false).await
+    }
+
+    /// Moves each of `sources` into the existing directory `dest_dir`, keeping their names
+    /// Fails as a whole if `dest_dir` isn't a directory. Otherwise returns one result per source,
+    /// with its new path, so a name collision only fails that source
+    pub async fn move_into(
+        &mut self,
+        sources: &[&Path],
+        dest_dir: &Path,
+    ) -> Result<Vec<Result<PathBuf>>> {
+        if self.obj_from_path(dest_dir).is_none() {
+            return Err(LisError::NotFound(dest_dir.to_path_buf()).into());
+        }
+        if !self.is_dir(dest_dir) {
+            return Err(LisError::NotADirectory(dest_dir.to_path_buf()).into());
+        }
+
+        let mut results = Vec::with_capacity(sources.len());
+        for src in sources {
+            let result = match src.file_name() {
+                Some(name) => {
+                    let dst = dest_dir.join(name);
+                    self.rename(src, &dst, false).await.map(|_| dst)
+                }
+                None => Err(anyhow!("{} has no name to move", src.display())),
+            };
+            results.push(result);
+        }
+        Ok(results)
+    }
+
     // Check whether a file should be removed from storage. Should be called after decrementing
     // the link count, or closing a file handle
     fn gc_inode(&mut self, attrs: &InodeAttributes) -> Result<()> {
@@ -343,55 +1148,176 @@ impl Lis {
 
     /// Get contents of a file
     pub async fn read(&mut self, full_path: &Path) -> Result<Bytes> {
-        let (doc, key) = self.doc_and_key(&full_path).await?;
+        self.apply_remote_changes().await?;
+        let (doc, key) = self.doc_and_key(full_path).await?;
 
         // get content of the key from doc
         let query = Query::key_exact(key);
         let entry = doc
             .get_one(query)
             .await?
-            .ok_or_else(|| anyhow!("entry not found"))?;
+            .ok_or_else(|| LisError::NotFound(full_path.to_path_buf()))?;
 
-        entry.content_bytes(self.iroh_node.client()).await
+        // what's stored, without a preallocated tail or what's left past a truncation
+        let size = self.logical_size(full_path, entry.content_len());
+        if size >= entry.content_len() {
+            return entry.content_bytes(self.iroh_node.client()).await;
+        }
+        self.iroh_node
+            .blobs()
+            .read_at_to_bytes(entry.content_hash(), 0, Some(size as usize))
+            .await
     }
 
-    /// Generate a NodeTicket invite
-    pub async fn invite(&self) -> Result<NodeTicket> {
-        let node_addr = self.iroh_node.net().node_addr().await?;
-        NodeTicket::new(node_addr)
+    /// Size of the file at `full_path` whose entry stores `stored` bytes: what the manifest says,
+    /// which is more when it was preallocated and less when it was truncated to nothing
+    pub(crate) fn logical_size(&self, full_path: &Path, stored: u64) -> u64 {
+        self.obj_from_path(full_path)
+            .map_or(stored, |obj| obj.attrs.size)
     }
-    /// Joins a network from a NodeTicket invite
-    pub fn join(&mut self, ticket: &NodeTicket) -> Result<()> {
-        let endpoint = self.iroh_node.endpoint();
-        endpoint.add_node_addr(ticket.node_addr().clone())
+
+    /// Hash of the blob the doc entry of `full_path` points to, `None` if it has no entry
+    pub(crate) async fn entry_hash(&self, full_path: &Path) -> Result<Option<Hash>> {
+        let (doc, key) = self.doc_and_key(full_path).await?;
+        Ok(doc
+            .get_one(Query::key_exact(key))
+            .await?
+            .map(|entry| entry.content_hash()))
     }
 
-    fn get_full_path(&self, parent: Inode, name: &OsStr) -> Result<PathBuf> {
-        let name = PathBuf::from(name);
-        let parent_obj = self
-            .manifest
-            .objects
-            .get(&parent)
-            .ok_or(anyhow!("could not find parent's inode"))?;
-        Ok(parent_obj.full_path.join(name))
+    /// Reads at most `len` bytes of a file starting at `offset`, fetching only that range of
+    /// its blob. Reading past the end returns fewer bytes, or none. A preallocated range past the
+    /// end of the content reads as zeros
+    pub async fn read_range(&self, full_path: &Path, offset: u64, len: usize) -> Result<Bytes> {
+        let (doc, key) = self.doc_and_key(full_path).await?;
+        let entry = doc
+            .get_one(Query::key_exact(key))
+            .await?
+            .ok_or_else(|| LisError::NotFound(full_path.to_path_buf()))?;
+
+        let size = self.logical_size(full_path, entry.content_len());
+        // a file truncated to nothing still stores a byte
+        let stored = entry.content_len().min(size);
+        if offset >= size || len == 0 {
+            return Ok(Bytes::new());
+        }
+        let len = len.min((size - offset) as usize);
+        if offset + len as u64 <= stored {
+            return self
+                .iroh_node
+                .blobs()
+                .read_at_to_bytes(entry.content_hash(), offset, Some(len))
+                .await;
+        }
+
+        let mut buffer = BytesMut::with_capacity(len);
+        if offset < stored {
+            let in_blob = (stored - offset) as usize;
+            buffer.extend_from_slice(
+                &self
+                    .iroh_node
+                    .blobs()
+                    .read_at_to_bytes(entry.content_hash(), offset, Some(in_blob))
+                    .await?,
+            );
+        }
+        buffer.resize(len, 0);
+        Ok(buffer.freeze())
     }
 
-    /// Create directory if doesn't already exist
-    pub async fn mkdir(
-        &mut self,
-        full_path: &PathBuf,
+    /// Reads a file as a stream of chunks, without loading all of it in memory
+    pub async fn read_stream(&self, full_path: &Path) -> Result<iroh::client::blobs::Reader> {
+        if self.is_dir(full_path) {
+            return Err(LisError::IsADirectory(full_path.to_path_buf()).into());
+        }
+        let (doc, key) = self.doc_and_key(full_path).await?;
+        let entry = doc
+            .get_one(Query::key_exact(key))
+            .await?
+            .ok_or_else(|| LisError::NotFound(full_path.to_path_buf()))?;
+
+        let size = self.logical_size(full_path, entry.content_len());
+        self.iroh_node
+            .blobs()
+            .read_at(
+                entry.content_hash(),
+                0,
+                Some(entry.content_len().min(size) as usize),
+            )
+            .await
+    }
+
+    /// Keeps the node online, serving its blobs and docs to peers, until `shutdown` resolves
+    /// Then saves the manifest and shuts the Iroh node down
+    pub async fn serve(self, shutdown: impl Future<Output = ()>) -> Result<()> {
+        let ticket = self.invite().await?;
+        info!("Serving {} as {ticket}", self.root.display());
+
+        shutdown.await;
+
+        info!("Shutting down");
+        self.manifest.save()?;
+        self.iroh_node.shutdown().await
+    }
+
+    /// Generate a NodeTicket invite
+    pub async fn invite(&self) -> Result<NodeTicket> {
+        let node_addr = self.iroh_node.net().node_addr().await?;
+        NodeTicket::new(node_addr)
+    }
+
+    /// Ticket sharing the root directory's doc, with this node's relay and direct addresses so a
+    /// peer can connect and sync it. A read-only ticket doesn't give the peer the key to write
+    /// Peers are only let in to sync docs that are live, so every directory's doc is made live
+    pub async fn create_invite(&self, read_only: bool) -> Result<DocTicket> {
+        for obj in self.manifest.objects.values() {
+            if matches!(obj.attrs.kind, FileKind::Directory) && obj.full_path != Path::new("/") {
+                let doc = self.find_dir_doc(&obj.full_path).await?;
+                doc.start_sync(vec![]).await?;
+            }
+        }
+
+        let mode = if read_only {
+            ShareMode::Read
+        } else {
+            ShareMode::Write
+        };
+        self.root_doc
+            .share(mode, AddrInfoOptions::RelayAndAddresses)
+            .await
+    }
+
+    fn get_full_path(&self, parent: Inode, name: &OsStr) -> Result<PathBuf> {
+        let name = PathBuf::from(name);
+        let parent_obj = self
+            .manifest
+            .objects
+            .get(&parent)
+            .ok_or(anyhow!("could not find parent's inode"))?;
+        Ok(parent_obj.full_path.join(name))
+    }
+
+    /// Create directory if doesn't already exist
+    /// With `exist_ok`, an existing directory at `full_path` is not an error and its id is
+    /// returned. An existing file still is
+    pub async fn mkdir(
+        &mut self,
+        full_path: &Path,
         mode: Option<u16>,
         uid: Option<u32>,
         gid: Option<u32>,
+        exist_ok: bool,
     ) -> Result<NamespaceId> {
+        self.check_writable(full_path)?;
+        validate_path(full_path)?;
+
         // find parent dir
         // if we're creating /1/2/3, this will find the doc of /1/2
         let parent_doc = self
             .find_dir_doc(
-                &full_path
+                full_path
                     .parent()
-                    .ok_or(anyhow!("Could not find Doc for parent dir"))?
-                    .to_path_buf(),
+                    .ok_or(anyhow!("Could not find Doc for parent dir"))?,
             )
             .await?;
 
@@ -401,62 +1327,117 @@ impl Lis {
                 .ok_or(anyhow!("Could not get last dir name"))?,
         );
 
+        if exist_ok && self.is_dir(full_path) {
+            if let Some(doc) = self.next_doc(&parent_doc, relpath).await? {
+                return Ok(doc.id());
+            }
+        }
+
+        self.check_dir_capacity(&parent_doc, full_path).await?;
+
         // create doc representing dir
         let doc = self.create_doc(&parent_doc, relpath).await?;
 
         // add needed objects to fs structure (fuse)
         self.create_fs_objects(full_path, FileKind::Directory, None, mode, uid, gid)?;
         debug!("Created directory {}", full_path.display());
+        self.audit(AuditOp::Mkdir, full_path).await?;
 
         Ok(doc.id())
     }
 
-    pub async fn rmdir(&mut self, full_path: &PathBuf) -> Result<()> {
-        if *full_path == PathBuf::from("/") {
+    /// Creates the missing parent directories of `full_path`, like `mkdir -p $(dirname path)`
+    pub async fn mkdir_parents(&mut self, full_path: &Path) -> Result<()> {
+        // ancestors() walks up from the path itself, create the parents top-down
+        let parents: Vec<_> = full_path
+            .ancestors()
+            .skip(1)
+            .filter(|dir| dir.file_name().is_some())
+            .collect();
+        for dir in parents.into_iter().rev() {
+            self.mkdir(dir, None, None, None, true).await?;
+        }
+        Ok(())
+    }
+
+    /// Removes an empty directory, or moves it to the trash if that's enabled, and releases its
+    /// inode. The root dir can't be removed
+    pub async fn rmdir(&mut self, full_path: &Path) -> Result<()> {
+        self.check_writable(full_path)?;
+        if full_path == Path::new("/") {
             return Err(anyhow!("Cannot delete root dir"));
         }
+        if self.obj_from_path(full_path).is_some() && !self.is_dir(full_path) {
+            return Err(LisError::NotADirectory(full_path.to_path_buf()).into());
+        }
 
-        let doc = self.find_dir_doc(&full_path).await?;
+        if self.trashes(full_path) {
+            self.check_dir_empty(full_path).await?;
+            return self.move_to_trash(full_path).await;
+        }
+        self.drop_dir(full_path).await?;
+
+        if let Some(obj) = self.obj_from_path(full_path) {
+            let mut attrs = obj.attrs.clone();
+            attrs.hardlinks = 0;
+            attrs.last_metadata_changed = SystemTime::now();
+            self.write_inode(&attrs)?;
+            self.gc_inode(&attrs)?;
+        }
+        Ok(())
+    }
 
-        // only delete empty directories
+    /// Errors with `LisError::NotEmpty` unless the directory at `full_path` is empty
+    async fn check_dir_empty(&self, full_path: &Path) -> Result<()> {
+        let doc = self.find_dir_doc(full_path).await?;
         let query = Query::all().build();
-        if doc.get_many(query).await?.collect::<Vec<_>>().await.len() != 0 {
-            return Err(anyhow!("Directory not empty"));
+        let mut entries = doc
+            .get_many(query)
+            .await?
+            .filter(|entry| !matches!(entry, Ok(entry) if is_annotation_key(entry.key())));
+        if entries.next().await.is_some() {
+            return Err(LisError::NotEmpty(full_path.to_path_buf()).into());
         }
+        Ok(())
+    }
+
+    /// Deletes the empty directory at `full_path` and its entry in the parent directory
+    async fn drop_dir(&mut self, full_path: &Path) -> Result<()> {
+        self.check_dir_empty(full_path).await?;
 
+        let doc = self.find_dir_doc(full_path).await?;
         self.iroh_node.docs().drop_doc(doc.id()).await?;
         debug!("Removed directory {}", full_path.display());
 
         // also remove entry in parent dir (if any)
-        let (parent_doc, key) = self.doc_and_key(&full_path).await?;
+        let (parent_doc, key) = self.doc_and_key(full_path).await?;
         let query = Query::key_exact(key.clone());
         if parent_doc.get_one(query).await?.is_some() {
             parent_doc
                 .del(self.iroh_node.authors().default().await?, key.clone())
                 .await?; // delete old entry
         }
+        self.drop_annotations(full_path).await?;
+        self.audit(AuditOp::Rmdir, full_path).await?;
 
         Ok(())
     }
 
-    async fn find_dir_doc(&self, full_path: &PathBuf) -> Result<Doc> {
+    async fn find_dir_doc(&self, full_path: &Path) -> Result<Doc> {
         // strip leading / from path
-        let mut path = full_path.clone();
+        let mut path = full_path.to_path_buf();
         if path.starts_with("/") {
             path = path.strip_prefix("/")?.to_path_buf();
         }
 
         // iterate until last dir
         let mut doc = self.root_doc.clone();
+        let mut walked = PathBuf::from("/");
         for dir in &path {
+            walked.push(dir);
             doc = match self.next_doc(&doc, Path::new(dir)).await? {
                 Some(next_doc) => next_doc,
-                None => {
-                    return Err(anyhow!(
-                        "could not find {} in tree",
-                        Path::new(dir).display()
-                    ))
-                }
+                None => return Err(LisError::NotFound(walked).into()),
             };
         }
         Ok(doc)
@@ -471,11 +1452,10 @@ impl Lis {
             None => return Ok(None),
         };
 
-        Ok(self
-            .iroh_node
+        self.iroh_node
             .docs()
             .open(bytes_to_namespaceid(next_doc_id)?)
-            .await?)
+            .await
     }
     /// Creates new Doc with name `next_key` and `base_doc` as its parent
     async fn create_doc(&mut self, base_doc: &Doc, dir_name: &Path) -> Result<Doc> {
@@ -483,7 +1463,7 @@ impl Lis {
         let key = key_from_file(Path::new(""), dir_name)?;
         let query = Query::key_exact(key.clone());
         if let Some(_doc_id) = base_doc.get_one(query).await? {
-            return Err(anyhow!("cannot create directory, already exists"));
+            return Err(LisError::AlreadyExists(dir_name.to_path_buf()).into());
         }
 
         // Doc doesn't already exist, create new Doc
@@ -493,6 +1473,12 @@ impl Lis {
             .set_bytes(author, key, namespaceid_to_bytes(new_doc.id()))
             .await?;
 
+        // peers of a shared tree can only join the new directory once it's live
+        let peers = join::sync_peers(base_doc).await?;
+        if !peers.is_empty() {
+            new_doc.start_sync(peers).await?;
+        }
+
         Ok(new_doc)
     }
 
@@ -518,9 +1504,12 @@ impl Lis {
             return Err(libc::EACCES);
         }
 
-        attrs.size = new_length;
-        attrs.last_metadata_changed = SystemTime::now();
-        attrs.last_modified = SystemTime::now();
+        let full_path = self.manifest.objects[&ino].full_path.clone();
+        if let Err(e) = self.set_len(&full_path, new_length).await {
+            error!("Could not truncate {}: {e}", full_path.display());
+            return Err(errno_from_error(&e, libc::EIO));
+        }
+        attrs = self.manifest.objects[&ino].attrs.clone();
 
         // Clear SETUID & SETGID on truncate
         clear_suid_sgid(&mut attrs);
@@ -532,6 +1521,225 @@ impl Lis {
 
         Ok(attrs)
     }
+
+    /// Sets the size of the file at `full_path`, like `truncate(2)`: a shrunk file keeps only its
+    /// first `size` bytes, and a grown one reads as zeros past its old end, which takes no space
+    pub async fn set_len(&mut self, full_path: &Path, size: u64) -> Result<()> {
+        self.check_writable(full_path)?;
+        if size > MAX_FILE_SIZE {
+            return Err(anyhow!(
+                "Cannot grow {} to {size} bytes, files are at most {MAX_FILE_SIZE}",
+                full_path.display()
+            ));
+        }
+        let mut attrs = match self.obj_from_path(full_path) {
+            Some(obj) if matches!(obj.attrs.kind, FileKind::Directory) => {
+                return Err(LisError::IsADirectory(full_path.to_path_buf()).into())
+            }
+            Some(obj) => obj.attrs.clone(),
+            None => return Err(LisError::NotFound(full_path.to_path_buf()).into()),
+        };
+
+        if size < attrs.size {
+            // an empty entry would be a deleted one, a zero byte reads the same as a grown file
+            let content = match size {
+                0 => Bytes::from_static(b"\x00"),
+                _ => self.read_range(full_path, 0, size as usize).await?,
+            };
+            // replaces the entry in one go, it never goes missing
+            let (doc, key) = self.doc_and_key(full_path).await?;
+            let author = self.iroh_node.authors().default().await?;
+            doc.set_bytes(author, key, content).await?;
+        }
+
+        attrs.size = size;
+        attrs.last_modified = SystemTime::now();
+        attrs.last_metadata_changed = SystemTime::now();
+        self.write_inode(&attrs)?;
+        self.sync_links(full_path).await?;
+        self.mark_hash_stale(full_path)?;
+        self.audit(AuditOp::Write, full_path).await?;
+        Ok(())
+    }
+
+    /// Grows the file to at least `size` bytes without storing anything: the new range reads
+    /// as zeros until it's written. Never shrinks the file
+    pub async fn preallocate(&mut self, full_path: &Path, size: u64) -> Result<()> {
+        self.check_writable(full_path)?;
+        if size > MAX_FILE_SIZE {
+            return Err(anyhow!(
+                "Cannot preallocate {size} bytes for {}, files are at most {MAX_FILE_SIZE}",
+                full_path.display()
+            ));
+        }
+        let mut attrs = match self.obj_from_path(full_path) {
+            Some(obj) if matches!(obj.attrs.kind, FileKind::Directory) => {
+                return Err(LisError::IsADirectory(full_path.to_path_buf()).into())
+            }
+            Some(obj) => obj.attrs.clone(),
+            None => return Err(LisError::NotFound(full_path.to_path_buf()).into()),
+        };
+        if size <= attrs.size {
+            return Ok(());
+        }
+
+        attrs.size = size;
+        attrs.last_metadata_changed = SystemTime::now();
+        attrs.last_modified = SystemTime::now();
+        self.write_inode(&attrs)?;
+        self.sync_links(full_path).await?;
+        self.audit(AuditOp::Write, full_path).await?;
+        Ok(())
+    }
+
+    /// Zeroes `len` bytes of the file from `offset`, keeping its size. Files are single blobs,
+    /// so the zeros are stored rather than left as a hole
+    pub async fn punch_hole(&mut self, full_path: &Path, offset: u64, len: u64) -> Result<()> {
+        if self.is_dir(full_path) {
+            return Err(LisError::IsADirectory(full_path.to_path_buf()).into());
+        }
+        let stored = self.read(full_path).await?.len() as u64;
+        let end = offset.saturating_add(len).min(stored);
+        if offset >= end {
+            // past the stored content, the range already reads as zeros
+            return Ok(());
+        }
+        let zeros = vec![0; (end - offset) as usize];
+        self.write(full_path, &zeros, offset as usize).await
+    }
+
+    /// Copies `len` bytes of `src` from `src_offset` into `dst` at `dst_offset`, and returns how
+    /// many were copied, fewer if `src` ends first. A whole file copied over one that's no longer
+    /// only gets `dst` pointed to the same blob. Anything else is read and written back
+    pub async fn copy_range(
+        &mut self,
+        src: &Path,
+        src_offset: u64,
+        dst: &Path,
+        dst_offset: u64,
+        len: u64,
+    ) -> Result<u64> {
+        self.check_writable(dst)?;
+        for path in [src, dst] {
+            match self.obj_from_path(path) {
+                Some(obj) if matches!(obj.attrs.kind, FileKind::Directory) => {
+                    return Err(LisError::IsADirectory(path.to_path_buf()).into())
+                }
+                Some(_) => {}
+                None => return Err(LisError::NotFound(path.to_path_buf()).into()),
+            }
+        }
+
+        let (src_doc, src_key) = self.doc_and_key(src).await?;
+        let src_entry = src_doc
+            .get_one(Query::key_exact(src_key))
+            .await?
+            .ok_or_else(|| LisError::NotFound(src.to_path_buf()))?;
+        let src_stored = src_entry.content_len();
+        let src_size = self.logical_size(src, src_stored);
+        if src_offset >= src_size || len == 0 {
+            return Ok(0);
+        }
+
+        let (dst_doc, dst_key) = self.doc_and_key(dst).await?;
+        let dst_stored = dst_doc
+            .get_one(Query::key_exact(dst_key.clone()))
+            .await?
+            .ok_or_else(|| LisError::NotFound(dst.to_path_buf()))?
+            .content_len();
+
+        let whole_file = src_offset == 0
+            && dst_offset == 0
+            && len >= src_size
+            && src_stored == src_size
+            && dst_stored <= src_size;
+        if !whole_file {
+            let data = self.read_range(src, src_offset, len as usize).await?;
+            self.write(dst, &data, dst_offset as usize).await?;
+            return Ok(data.len() as u64);
+        }
+
+        // replaces the entry in one go, it never goes missing
+        let author = self.iroh_node.authors().default().await?;
+        dst_doc
+            .set_hash(author, dst_key, src_entry.content_hash(), src_stored)
+            .await?;
+        if let Some(obj) = self.obj_from_path(dst) {
+            let mut attrs = obj.attrs.clone();
+            attrs.size = attrs.size.max(src_size);
+            attrs.last_modified = SystemTime::now();
+            attrs.last_metadata_changed = SystemTime::now();
+            self.write_inode(&attrs)?;
+        }
+        self.sync_links(dst).await?;
+        self.mark_hash_stale(dst)?;
+        self.audit(AuditOp::Write, dst).await?;
+        Ok(src_size)
+    }
+
+    /// Replaces the content of `full_path` with `content`, but only if its current content hash
+    /// is `expected`, or if it doesn't exist when `expected` is `None`. Otherwise nothing is
+    /// written and it's a `LisError::Conflict`. Returns the new content hash
+    /// Hashes are Iroh's of the stored content, like `list` shows. The check and the write are
+    /// one call on this node, so its callers can't interleave, but a peer's write synced in
+    /// right after is still merged as usual
+    pub async fn compare_and_write(
+        &mut self,
+        full_path: &Path,
+        expected: Option<Hash>,
+        content: &[u8],
+    ) -> Result<Hash> {
+        self.check_writable(full_path)?;
+        validate_path(full_path)?;
+        if self.is_dir(full_path) {
+            return Err(LisError::IsADirectory(full_path.to_path_buf()).into());
+        }
+
+        let (doc, key) = self.doc_and_key(full_path).await?;
+        let current = doc
+            .get_one(Query::key_exact(key.clone()))
+            .await?
+            .map(|entry| entry.content_hash());
+        if current != expected {
+            return Err(LisError::Conflict(full_path.to_path_buf()).into());
+        }
+
+        let author = self.iroh_node.authors().default().await?;
+        // an existing entry is replaced by the `set_bytes` below, so it never goes missing
+        if current.is_none() {
+            self.check_dir_capacity(&doc, full_path).await?;
+        }
+        // empty content would be a deletion, the size in the manifest says it's empty
+        let stored = if content.is_empty() {
+            &b"\x00"[..]
+        } else {
+            content
+        };
+        let hash = doc.set_bytes(author, key, stored.to_vec()).await?;
+
+        let size = content.len() as u64;
+        match self.obj_from_path(full_path) {
+            Some(obj) => {
+                let mut attrs = obj.attrs.clone();
+                attrs.size = size;
+                attrs.last_modified = SystemTime::now();
+                attrs.last_metadata_changed = SystemTime::now();
+                self.write_inode(&attrs)?;
+            }
+            None => {
+                self.create_fs_objects(full_path, FileKind::File, Some(size), None, None, None)?;
+            }
+        }
+        self.sync_links(full_path).await?;
+        self.mark_hash_stale(full_path)?;
+        let op = if current.is_some() {
+            AuditOp::Write
+        } else {
+            AuditOp::Create
+        };
+        self.audit(op, full_path).await?;
+        Ok(hash)
+    }
 }
 
 #[cfg(test)]
@@ -548,6 +1756,66 @@ mod tests {
             .expect("Could not create new Lis node")
     }
 
+    #[tokio::test]
+    async fn overwrite_keeps_config() {
+        let tmp_dir = TempDir::new().unwrap();
+        let root = PathBuf::from(tmp_dir.path());
+        let config_path = root.join(config::CONFIG_FILE_NAME);
+        std::fs::write(&config_path, "noatime = true\n").unwrap();
+
+        let mut lis = setup_lis(&tmp_dir).await;
+        lis.touch(Path::new("/a.txt"), None, None, None)
+            .await
+            .unwrap();
+        lis.iroh_node.shutdown().await.unwrap();
+
+        // the node starts over, the config stays
+        let lis = setup_lis(&tmp_dir).await;
+        assert!(lis.obj_from_path(Path::new("/a.txt")).is_none());
+        assert_eq!(
+            std::fs::read_to_string(&config_path).unwrap(),
+            "noatime = true\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn stat() {
+        let tmp_dir = TempDir::new().unwrap();
+        let mut lis = setup_lis(&tmp_dir).await;
+        lis.set_hash_algorithm(HashAlgorithm::Sha256).await.unwrap();
+
+        let mut file = NamedTempFile::new_in(tmp_dir.path()).unwrap();
+        write!(file, "hello").unwrap();
+        lis.import_file(file.path(), Path::new("hello.txt"), false)
+            .await
+            .unwrap();
+        lis.set_description(Path::new("/hello.txt"), Some("greeting".to_string()))
+            .unwrap();
+
+        let stat = lis.stat(Path::new("hello.txt")).await.unwrap().unwrap();
+        assert_eq!(stat.kind, FileKind::File);
+        assert_eq!(stat.size, 5);
+        assert_eq!(
+            stat.content_hash.as_deref(),
+            Some("2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824")
+        );
+        assert_eq!(
+            stat.original_filename,
+            file.path()
+                .file_name()
+                .map(|name| name.to_string_lossy().to_string())
+        );
+        assert_eq!(stat.description.as_deref(), Some("greeting"));
+        assert!(stat.imported_by.is_some());
+        assert!(lis.exists(Path::new("/hello.txt")));
+
+        let root = lis.stat(Path::new("/")).await.unwrap().unwrap();
+        assert_eq!(root.kind, FileKind::Directory);
+        assert_eq!(lis.stat(Path::new("/missing")).await.unwrap(), None);
+        assert!(!lis.exists(Path::new("/missing")));
+        assert!(lis.stat(Path::new("/a/../b")).await.is_err());
+    }
+
     #[tokio::test]
     async fn import_dir() {
         let tmp_dir = TempDir::new().expect("Could not create temp dir");
@@ -560,7 +1828,7 @@ mod tests {
         let src_path = file.path();
         let dst_path = Path::new(file.path().file_name().unwrap());
 
-        lis.import_file(src_path, dst_path)
+        lis.import_file(src_path, dst_path, false)
             .await
             .expect("Could not import file"); // should succeed
         let get_content = lis.read(dst_path).await.expect("Could not get file"); // should succeed
@@ -580,7 +1848,7 @@ mod tests {
         let dst_path = Path::new(binding.file_name().unwrap());
 
         // import file twice
-        lis.import_file(file.path(), dst_path)
+        lis.import_file(file.path(), dst_path, false)
             .await
             .expect("Could not import file"); // should succeed
 
@@ -588,7 +1856,16 @@ mod tests {
         let more_content = " more";
         write!(file, "{}", more_content).expect("Could not write to named temp file");
 
-        lis.import_file(file.path(), dst_path)
+        // which needs overwrite
+        let e = lis
+            .import_file(file.path(), dst_path, false)
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            e.downcast_ref::<LisError>(),
+            Some(LisError::AlreadyExists(_))
+        ));
+        lis.import_file(file.path(), dst_path, true)
             .await
             .expect("Could not import file");
 
@@ -600,6 +1877,94 @@ mod tests {
         assert_eq!(files.len(), 1); // there should only be one file
     }
 
+    #[tokio::test]
+    async fn import_mtime_from_content() {
+        let tmp_dir = TempDir::new().unwrap();
+        let mut lis = setup_lis(&tmp_dir).await;
+        lis.set_hash_algorithm(HashAlgorithm::Sha256).await.unwrap();
+        lis.import_mtime = ImportMtime::Content;
+
+        let mut file = NamedTempFile::new_in(tmp_dir.path()).unwrap();
+        write!(file, "same every time").unwrap();
+        let dst_path = Path::new("/file.txt");
+
+        let mut imported = Vec::new();
+        for overwrite in [false, true] {
+            lis.import_file(file.path(), dst_path, overwrite)
+                .await
+                .unwrap();
+            let mtime = lis.obj_from_path(dst_path).unwrap().attrs.last_modified;
+            imported.push((mtime, lis.content_hash(dst_path).await.unwrap()));
+        }
+        assert_eq!(imported[0], imported[1]);
+        assert!(imported[0].1.is_some());
+
+        lis.import_mtime = ImportMtime::Epoch;
+        lis.import_file(file.path(), dst_path, true).await.unwrap();
+        let obj = lis.obj_from_path(dst_path).unwrap();
+        assert_eq!(obj.attrs.last_modified, UNIX_EPOCH);
+    }
+
+    #[tokio::test]
+    async fn import_dir_tree() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let tmp_dir = TempDir::new().unwrap();
+        let mut lis = setup_lis(&tmp_dir).await;
+
+        let src = TempDir::new().unwrap();
+        std::fs::create_dir_all(src.path().join("sub").join("empty")).unwrap();
+        std::fs::write(src.path().join("a.txt"), "a").unwrap();
+        std::fs::write(src.path().join("sub").join("b.txt"), "b").unwrap();
+        std::fs::set_permissions(
+            src.path().join("a.txt"),
+            std::fs::Permissions::from_mode(0o640),
+        )
+        .unwrap();
+
+        let imported = lis
+            .import_file(src.path(), Path::new("/tree"), false)
+            .await
+            .unwrap();
+        assert_eq!(imported.len(), 2);
+
+        let names = |entries: Vec<Result<Entry>>| {
+            let mut names: Vec<String> = entries
+                .into_iter()
+                .map(|entry| key_to_string(Bytes::copy_from_slice(entry.unwrap().key())).unwrap())
+                .collect();
+            names.sort();
+            names
+        };
+        assert_eq!(
+            names(lis.list(Path::new("/tree")).await.unwrap()),
+            vec!["a.txt", "sub"]
+        );
+        assert_eq!(
+            names(lis.list(Path::new("/tree/sub")).await.unwrap()),
+            vec!["b.txt", "empty"]
+        );
+        assert!(lis.is_dir(Path::new("/tree/sub/empty")));
+        assert_eq!(lis.read(Path::new("/tree/sub/b.txt")).await.unwrap(), "b");
+        let a = lis.obj_from_path(Path::new("/tree/a.txt")).unwrap();
+        assert_eq!(a.attrs.mode, 0o640);
+
+        // importing again needs overwrite
+        let e = lis
+            .import_file(src.path(), Path::new("/tree"), false)
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            e.downcast_ref::<LisError>(),
+            Some(LisError::AlreadyExists(_))
+        ));
+        std::fs::write(src.path().join("a.txt"), "new a").unwrap();
+        lis.import_file(src.path(), Path::new("/tree"), true)
+            .await
+            .unwrap();
+        assert_eq!(lis.read(Path::new("/tree/a.txt")).await.unwrap(), "new a");
+    }
+
     #[tokio::test]
     async fn import_file() {
         let tmp_dir = TempDir::new().expect("Could not create temp dir");
@@ -613,14 +1978,14 @@ mod tests {
         let binding = file.path().to_path_buf();
         let dst_path = Path::new(binding.file_name().unwrap());
 
-        lis.import_file(file.path(), dst_path)
+        lis.import_file(file.path(), dst_path, false)
             .await
             .expect("Could not import file");
 
         // but second time has more content
         let more_content = " more";
         write!(file, "{}", more_content).expect("Could not write to named temp file");
-        lis.import_file(file.path(), dst_path)
+        lis.import_file(file.path(), dst_path, true)
             .await
             .expect("Could not import file");
 
@@ -632,6 +1997,104 @@ mod tests {
         assert_eq!(files.len(), 1); // there should only be one file
     }
 
+    /// Serves `/new`, a small payload, `/old` redirecting to it, and `/empty`, an empty body
+    async fn serve_http() -> std::net::SocketAddr {
+        use tokio::{
+            io::{AsyncReadExt, AsyncWriteExt},
+            net::TcpListener,
+        };
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            loop {
+                let (mut socket, _) = listener.accept().await.unwrap();
+                let mut buf = [0; 1024];
+                let n = socket.read(&mut buf).await.unwrap();
+                let request = String::from_utf8_lossy(&buf[..n]);
+                let response = if request.starts_with("GET /old") {
+                    "HTTP/1.1 302 Found\r\nLocation: /new\r\nContent-Length: 0\r\nConnection: close\r\n\r\n".to_string()
+                } else if request.starts_with("GET /empty") {
+                    "HTTP/1.1 200 OK\r\nContent-Length: 0\r\nConnection: close\r\n\r\n".to_string()
+                } else {
+                    let body = "Brian was here. Briefly.";
+                    format!(
+                        "HTTP/1.1 200 OK\r\nContent-Type: text/plain\r\nContent-Disposition: attachment; filename=\"brian.txt\"\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                        body.len()
+                    )
+                };
+                socket.write_all(response.as_bytes()).await.unwrap();
+            }
+        });
+        addr
+    }
+
+    #[tokio::test]
+    async fn import_from_url() {
+        let tmp_dir = TempDir::new().unwrap();
+        let mut lis = setup_lis(&tmp_dir).await;
+        let addr = serve_http().await;
+
+        let dst_path = Path::new("/downloaded.txt");
+        let hash = lis
+            .import_from_url(&format!("http://{addr}/old"), dst_path, false)
+            .await
+            .unwrap();
+
+        assert_eq!(hash, Hash::new("Brian was here. Briefly."));
+        assert_eq!(
+            lis.read(dst_path).await.unwrap(),
+            "Brian was here. Briefly."
+        );
+
+        let obj = lis.obj_from_path(dst_path).unwrap();
+        assert_eq!(obj.source_url, Some(format!("http://{addr}/new")));
+        assert_eq!(obj.content_type.as_deref(), Some("text/plain"));
+        assert_eq!(obj.original_filename.as_deref(), Some("brian.txt"));
+        assert_eq!(obj.imported_by, Some(lis.iroh_node.node_id().to_string()));
+
+        // only the doc entry keeps the download
+        let tags: Vec<_> = lis.iroh_node.tags().list().await.unwrap().collect().await;
+        assert!(tags.iter().all(|tag| tag.as_ref().unwrap().hash != hash));
+    }
+
+    #[tokio::test]
+    async fn import_from_url_existing() {
+        let tmp_dir = TempDir::new().unwrap();
+        let mut lis = setup_lis(&tmp_dir).await;
+        let addr = serve_http().await;
+        let url = format!("http://{addr}/new");
+
+        // existing file, only replaced with overwrite
+        let file = Path::new("/file.txt");
+        lis.touch(file, None, None, None).await.unwrap();
+        lis.write(file, b"old content", 0).await.unwrap();
+        let err = lis.import_from_url(&url, file, false).await.unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<LisError>(),
+            Some(LisError::AlreadyExists(_))
+        ));
+        assert_eq!(lis.read(file).await.unwrap(), "old content");
+        lis.import_from_url(&url, file, true).await.unwrap();
+        assert_eq!(lis.read(file).await.unwrap(), "Brian was here. Briefly.");
+
+        // directories are never replaced
+        let dir = Path::new("/dir");
+        lis.mkdir(dir, None, None, None, false).await.unwrap();
+        let err = lis.import_from_url(&url, dir, true).await.unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<LisError>(),
+            Some(LisError::IsADirectory(_))
+        ));
+        assert!(lis.is_dir(dir));
+
+        // nor is anything stored for an empty body
+        let empty = Path::new("/empty.txt");
+        let empty_url = format!("http://{addr}/empty");
+        assert!(lis.import_from_url(&empty_url, empty, false).await.is_err());
+        assert!(lis.obj_from_path(empty).is_none());
+    }
+
     #[tokio::test]
     async fn mkdir() {
         let tmp_dir = TempDir::new().unwrap();
@@ -643,19 +2106,19 @@ mod tests {
         write!(file, "{}", content).unwrap();
 
         // create /1
-        lis.mkdir(&Path::new("/1").to_path_buf(), None, None, None)
+        lis.mkdir(Path::new("/1"), None, None, None, false)
             .await
             .unwrap();
         assert_eq!(lis.list(Path::new("/")).await.unwrap().len(), 1);
 
         // create /1/2
-        lis.mkdir(&Path::new("/1/2").to_path_buf(), None, None, None)
+        lis.mkdir(Path::new("/1/2"), None, None, None, false)
             .await
             .unwrap();
         assert_eq!(lis.list(Path::new("/1")).await.unwrap().len(), 1);
 
         // create /1/2/3
-        lis.mkdir(&Path::new("/1/2/3").to_path_buf(), None, None, None)
+        lis.mkdir(Path::new("/1/2/3"), None, None, None, false)
             .await
             .unwrap();
         assert_eq!(lis.list(Path::new("/1/2")).await.unwrap().len(), 1);
@@ -665,7 +2128,7 @@ mod tests {
         let dst_path = Path::new("/1/2/3").join(file.path().file_name().unwrap());
 
         // import file twice
-        lis.import_file(src_path, &dst_path)
+        lis.import_file(src_path, &dst_path, false)
             .await
             .expect("Could not import file");
 
@@ -678,6 +2141,26 @@ mod tests {
         assert_eq!(get_content, "Brian was here. Briefly."); // new content should be there
     }
 
+    #[tokio::test]
+    async fn mkdir_exist_ok() {
+        let tmp_dir = TempDir::new().unwrap();
+        let mut lis = setup_lis(&tmp_dir).await;
+
+        let dir_path = Path::new("/1");
+        let id = lis.mkdir(dir_path, None, None, None, true).await.unwrap();
+        assert_eq!(
+            lis.mkdir(dir_path, None, None, None, true).await.unwrap(),
+            id
+        );
+        assert!(lis.mkdir(dir_path, None, None, None, false).await.is_err());
+        assert_eq!(lis.list(Path::new("/")).await.unwrap().len(), 1);
+
+        // a file in the way is still an error
+        let file_path = Path::new("/myfile.txt");
+        lis.touch(file_path, None, None, None).await.unwrap();
+        assert!(lis.mkdir(file_path, None, None, None, true).await.is_err());
+    }
+
     #[tokio::test]
     async fn rmdir() {
         let tmp_dir = TempDir::new().unwrap();
@@ -689,31 +2172,52 @@ mod tests {
         write!(file, "{}", content).unwrap();
 
         // create /1
-        lis.mkdir(&Path::new("/1").to_path_buf(), None, None, None)
+        lis.mkdir(Path::new("/1"), None, None, None, false)
             .await
             .unwrap();
         assert_eq!(lis.list(Path::new("/")).await.unwrap().len(), 1);
 
         // create /1/2
-        lis.mkdir(&Path::new("/1/2").to_path_buf(), None, None, None)
+        lis.mkdir(Path::new("/1/2"), None, None, None, false)
             .await
             .unwrap();
         assert_eq!(lis.list(Path::new("/1")).await.unwrap().len(), 1);
 
         // rmdir /1/2
-        lis.rmdir(&Path::new("/1/2").to_path_buf()).await.unwrap();
+        lis.rmdir(Path::new("/1/2")).await.unwrap();
         assert_eq!(lis.list(Path::new("/1")).await.unwrap().len(), 0);
 
         // rmdir /1
-        lis.rmdir(&Path::new("/1").to_path_buf()).await.unwrap();
+        lis.rmdir(Path::new("/1")).await.unwrap();
         assert_eq!(lis.list(Path::new("/")).await.unwrap().len(), 0);
 
         // rmdir / (should fail)
-        let should_be_err = lis.rmdir(&Path::new("/").to_path_buf()).await;
+        let should_be_err = lis.rmdir(Path::new("/")).await;
         assert!(should_be_err.is_err());
         if let Err(e) = should_be_err {
             assert_eq!(e.to_string(), "Cannot delete root dir");
         }
+        assert!(lis.obj_from_path(Path::new("/1")).is_none());
+
+        // only empty directories can be removed
+        lis.mkdir(Path::new("/a"), None, None, None, false)
+            .await
+            .unwrap();
+        lis.touch(Path::new("/a/file.txt"), None, None, None)
+            .await
+            .unwrap();
+        let e = lis.rmdir(Path::new("/a")).await.unwrap_err();
+        assert!(matches!(
+            e.downcast_ref::<LisError>(),
+            Some(LisError::NotEmpty(_))
+        ));
+        assert!(lis.is_dir(Path::new("/a")));
+
+        let e = lis.rmdir(Path::new("/a/file.txt")).await.unwrap_err();
+        assert!(matches!(
+            e.downcast_ref::<LisError>(),
+            Some(LisError::NotADirectory(_))
+        ));
     }
 
     #[tokio::test]
@@ -723,9 +2227,7 @@ mod tests {
 
         // Create empty file (touch) in lis
         let file_path = Path::new("/myfile.txt");
-        lis.touch(&file_path.to_path_buf(), None, None, None)
-            .await
-            .unwrap();
+        lis.touch(file_path, None, None, None).await.unwrap();
 
         // retrieve content from the file (should be b"null")
         let get_content = lis.read(file_path).await.unwrap();
@@ -739,9 +2241,7 @@ mod tests {
 
         // Create empty file (touch) in lis
         let file_path = Path::new("/myfile.txt");
-        lis.touch(&file_path.to_path_buf(), None, None, None)
-            .await
-            .unwrap();
+        lis.touch(file_path, None, None, None).await.unwrap();
 
         // retrieve content from the file (should be b"null")
         assert_eq!(lis.read(file_path).await.unwrap(), "null");
@@ -754,23 +2254,743 @@ mod tests {
     }
 
     #[tokio::test]
-    async fn remove() {
+    async fn read_stream() {
+        let tmp_dir = TempDir::new().unwrap();
+        let mut lis = setup_lis(&tmp_dir).await;
+
+        let file_path = Path::new("/big.bin");
+        let content: Vec<u8> = (0..1024 * 1024).map(|i| (i % 251) as u8).collect();
+        lis.touch(file_path, None, None, None).await.unwrap();
+        lis.write(file_path, &content, 0).await.unwrap();
+
+        let mut chunks = lis.read_stream(file_path).await.unwrap();
+        let mut streamed = Vec::new();
+        let mut chunk_count = 0;
+        while let Some(chunk) = chunks.next().await {
+            streamed.extend_from_slice(&chunk.unwrap());
+            chunk_count += 1;
+        }
+        assert_eq!(streamed, content);
+        assert!(chunk_count > 1);
+
+        lis.mkdir(Path::new("/dir"), None, None, None, false)
+            .await
+            .unwrap();
+        let e = lis.read_stream(Path::new("/dir")).await.unwrap_err();
+        assert!(matches!(
+            e.downcast_ref::<LisError>(),
+            Some(LisError::IsADirectory(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn read_range() {
+        let tmp_dir = TempDir::new().unwrap();
+        let mut lis = setup_lis(&tmp_dir).await;
+
+        let file_path = Path::new("/big.bin");
+        let content: Vec<u8> = (0..4 * 1024 * 1024).map(|i| (i % 251) as u8).collect();
+        lis.touch(file_path, None, None, None).await.unwrap();
+        lis.write(file_path, &content, 0).await.unwrap();
+
+        // a window deep into the file that starts and ends mid-chunk
+        let offset = 3 * 1024 * 1024 + 1000;
+        let window = lis.read_range(file_path, offset, 4096).await.unwrap();
+        assert_eq!(window.len(), 4096);
+        assert_eq!(window, content[offset as usize..offset as usize + 4096]);
+
+        // the end of the file cuts the read short
+        let size = content.len() as u64;
+        let tail = lis.read_range(file_path, size - 10, 4096).await.unwrap();
+        assert_eq!(tail, content[content.len() - 10..]);
+        assert!(lis
+            .read_range(file_path, size + 1, 4096)
+            .await
+            .unwrap()
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn set_len() {
+        let tmp_dir = TempDir::new().unwrap();
+        let mut lis = setup_lis(&tmp_dir).await;
+
+        let file_path = Path::new("/file.txt");
+        lis.touch(file_path, None, None, None).await.unwrap();
+        lis.write(file_path, b"hello world", 0).await.unwrap();
+
+        lis.set_len(file_path, 5).await.unwrap();
+        assert_eq!(lis.read(file_path).await.unwrap(), "hello");
+        assert_eq!(lis.obj_from_path(file_path).unwrap().attrs.size, 5);
+
+        // growing past the end reads as zeros
+        lis.set_len(file_path, 8).await.unwrap();
+        let all = lis.read_range(file_path, 0, usize::MAX).await.unwrap();
+        assert_eq!(all, "hello\0\0\0");
+
+        lis.set_len(file_path, 0).await.unwrap();
+        assert_eq!(lis.read(file_path).await.unwrap(), "");
+        assert_eq!(lis.obj_from_path(file_path).unwrap().attrs.size, 0);
+
+        // nothing is left of the old content once written or grown again
+        lis.write(file_path, b"hi", 0).await.unwrap();
+        assert_eq!(lis.read(file_path).await.unwrap(), "hi");
+        lis.set_len(file_path, 0).await.unwrap();
+        lis.set_len(file_path, 3).await.unwrap();
+        let all = lis.read_range(file_path, 0, usize::MAX).await.unwrap();
+        assert_eq!(all, "\0\0\0");
+
+        let e = lis.set_len(Path::new("/"), 0).await.unwrap_err();
+        assert!(matches!(
+            e.downcast_ref::<LisError>(),
+            Some(LisError::IsADirectory(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn preallocate() {
+        let tmp_dir = TempDir::new().unwrap();
+        let mut lis = setup_lis(&tmp_dir).await;
+
+        let file_path = Path::new("/file");
+        lis.touch(file_path, None, None, None).await.unwrap();
+        lis.write(file_path, b"head", 0).await.unwrap();
+        lis.preallocate(file_path, 1024 * 1024).await.unwrap();
+
+        // nothing is stored for the preallocated range
+        assert_eq!(
+            lis.obj_from_path(file_path).unwrap().attrs.size,
+            1024 * 1024
+        );
+        assert_eq!(lis.read(file_path).await.unwrap(), "head");
+
+        let range = lis.read_range(file_path, 2, 6).await.unwrap();
+        assert_eq!(range, b"ad\0\0\0\0"[..]);
+        let tail = lis
+            .read_range(file_path, 1024 * 1024 - 3, 16)
+            .await
+            .unwrap();
+        assert_eq!(tail, vec![0; 3]);
+
+        // a write into the range keeps the preallocated size
+        lis.write(file_path, b"middle", 512).await.unwrap();
+        let range = lis.read_range(file_path, 510, 10).await.unwrap();
+        assert_eq!(range, b"\0\0middle\0\0"[..]);
+        assert_eq!(
+            lis.obj_from_path(file_path).unwrap().attrs.size,
+            1024 * 1024
+        );
+
+        // preallocating less than the current size does nothing
+        lis.preallocate(file_path, 10).await.unwrap();
+        assert_eq!(
+            lis.obj_from_path(file_path).unwrap().attrs.size,
+            1024 * 1024
+        );
+
+        // other hardlinks see the new size
+        let link_path = Path::new("/link");
+        lis.link(file_path, link_path).await.unwrap();
+        lis.preallocate(link_path, 2 * 1024 * 1024).await.unwrap();
+        assert_eq!(
+            lis.obj_from_path(file_path).unwrap().attrs.size,
+            2 * 1024 * 1024
+        );
+        assert_eq!(
+            lis.read_range(file_path, 2 * 1024 * 1024 - 1, 16)
+                .await
+                .unwrap(),
+            vec![0]
+        );
+    }
+
+    #[tokio::test]
+    async fn list_with_metadata() {
+        let tmp_dir = TempDir::new().unwrap();
+        let mut lis = setup_lis(&tmp_dir).await;
+
+        lis.mkdir(Path::new("/dir"), Some(0o700), None, None, false)
+            .await
+            .unwrap();
+        lis.touch(Path::new("/dir/a.txt"), Some(0o644), None, None)
+            .await
+            .unwrap();
+        lis.write(Path::new("/dir/a.txt"), b"hello world", 0)
+            .await
+            .unwrap();
+        lis.mkdir(Path::new("/dir/sub"), None, None, None, false)
+            .await
+            .unwrap();
+        lis.create_symlink(Path::new("/dir/link"), Path::new("a.txt"), None, None)
+            .await
+            .unwrap();
+
+        let mut listed = lis.list_with_metadata(Path::new("/dir")).await.unwrap();
+        listed.sort_by(|a, b| a.0.cmp(&b.0));
+        let summary: Vec<_> = listed
+            .iter()
+            .map(|(name, metadata)| (name.to_str().unwrap(), metadata.kind, metadata.size))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("a.txt", FileKind::File, 11),
+                ("link", FileKind::Symlink, 5),
+                ("sub", FileKind::Directory, BLOCK_SIZE),
+            ]
+        );
+        assert_eq!(listed[0].1.mode, 0o644);
+        assert_eq!(
+            listed[0].1.inode,
+            lis.obj_from_path(Path::new("/dir/a.txt"))
+                .unwrap()
+                .attrs
+                .inode
+        );
+
+        assert!(lis.list_with_metadata(Path::new("/missing")).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn punch_hole() {
+        let tmp_dir = TempDir::new().unwrap();
+        let mut lis = setup_lis(&tmp_dir).await;
+
+        let file_path = Path::new("/file");
+        lis.touch(file_path, None, None, None).await.unwrap();
+        lis.write(file_path, b"0123456789", 0).await.unwrap();
+
+        lis.punch_hole(file_path, 2, 3).await.unwrap();
+        assert_eq!(lis.read(file_path).await.unwrap(), b"01\0\0\x0056789"[..]);
+
+        // a range running past the end doesn't grow the file
+        lis.punch_hole(file_path, 8, 100).await.unwrap();
+        assert_eq!(lis.read(file_path).await.unwrap(), b"01\0\0\x00567\0\0"[..]);
+        lis.punch_hole(file_path, 50, 10).await.unwrap();
+        assert_eq!(lis.obj_from_path(file_path).unwrap().attrs.size, 10);
+    }
+
+    #[tokio::test]
+    async fn compare_and_write() {
+        let tmp_dir = TempDir::new().unwrap();
+        let lis = setup_lis(&tmp_dir).await;
+        let lock_path = Path::new("/app.lock");
+        let lis = std::sync::Arc::new(tokio::sync::Mutex::new(lis));
+
+        let created = lis
+            .lock()
+            .await
+            .compare_and_write(lock_path, None, b"free")
+            .await
+            .unwrap();
+        let e = lis
+            .lock()
+            .await
+            .compare_and_write(lock_path, None, b"taken")
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            e.downcast_ref::<LisError>(),
+            Some(LisError::Conflict(_))
+        ));
+
+        let writers: Vec<_> = ["taken by 1", "taken by 2"]
+            .into_iter()
+            .map(|content| {
+                let lis = lis.clone();
+                tokio::spawn(async move {
+                    lis.lock()
+                        .await
+                        .compare_and_write(lock_path, Some(created), content.as_bytes())
+                        .await
+                })
+            })
+            .collect();
+        let mut won = Vec::new();
+        let mut conflicts = 0;
+        for writer in writers {
+            match writer.await.unwrap() {
+                Ok(hash) => won.push(hash),
+                Err(e) => {
+                    assert!(matches!(
+                        e.downcast_ref::<LisError>(),
+                        Some(LisError::Conflict(_))
+                    ));
+                    conflicts += 1;
+                }
+            }
+        }
+        assert_eq!((won.len(), conflicts), (1, 1));
+
+        let mut lis = lis.lock().await;
+        let content = lis.read(lock_path).await.unwrap();
+        assert!(content.starts_with(b"taken by "));
+        assert_eq!(won[0], Hash::new(&content));
+        lis.compare_and_write(lock_path, Some(won[0]), b"")
+            .await
+            .unwrap();
+        assert_eq!(lis.read(lock_path).await.unwrap(), "");
+    }
+
+    #[tokio::test]
+    async fn copy_range() {
+        let tmp_dir = TempDir::new().unwrap();
+        let mut lis = setup_lis(&tmp_dir).await;
+
+        let src = Path::new("/src");
+        let dst = Path::new("/dst");
+        lis.touch(src, None, None, None).await.unwrap();
+        lis.write(src, b"hello world", 0).await.unwrap();
+        lis.touch(dst, None, None, None).await.unwrap();
+
+        // the whole file ends up pointing to the same blob
+        assert_eq!(lis.copy_range(src, 0, dst, 0, u64::MAX).await.unwrap(), 11);
+        assert_eq!(lis.read(dst).await.unwrap(), "hello world");
+        assert_eq!(lis.obj_from_path(dst).unwrap().attrs.size, 11);
+        let (doc, key) = lis.doc_and_key(src).await.unwrap();
+        let src_hash = doc
+            .get_one(Query::key_exact(key))
+            .await
+            .unwrap()
+            .unwrap()
+            .content_hash();
+        let (doc, key) = lis.doc_and_key(dst).await.unwrap();
+        let dst_hash = doc
+            .get_one(Query::key_exact(key))
+            .await
+            .unwrap()
+            .unwrap()
+            .content_hash();
+        assert_eq!(src_hash, dst_hash);
+
+        // a range is read and written
+        assert_eq!(lis.copy_range(src, 6, dst, 0, 5).await.unwrap(), 5);
+        assert_eq!(lis.read(dst).await.unwrap(), "world world");
+        assert_eq!(lis.copy_range(src, 0, dst, 11, 100).await.unwrap(), 11);
+        assert_eq!(lis.read(dst).await.unwrap(), "world worldhello world");
+        assert_eq!(lis.obj_from_path(dst).unwrap().attrs.size, 22);
+        assert_eq!(lis.copy_range(src, 50, dst, 0, 10).await.unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn create_symlink() {
+        let tmp_dir = TempDir::new().unwrap();
+        let mut lis = setup_lis(&tmp_dir).await;
+
+        let link = Path::new("/link");
+        let target = Path::new("../outside/file.txt");
+        lis.create_symlink(link, target, None, None).await.unwrap();
+
+        let attrs = &lis.obj_from_path(link).unwrap().attrs;
+        assert!(matches!(attrs.kind, FileKind::Symlink));
+        assert_eq!(attrs.size, target.as_os_str().len() as u64);
+        assert_eq!(lis.read_link(link).await.unwrap(), target);
+
+        let e = lis
+            .create_symlink(link, Path::new("other"), None, None)
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            e.downcast_ref::<LisError>(),
+            Some(LisError::AlreadyExists(_))
+        ));
+
+        lis.touch(Path::new("/file"), None, None, None)
+            .await
+            .unwrap();
+        let e = lis.read_link(Path::new("/file")).await.unwrap_err();
+        assert_eq!(
+            e.downcast_ref::<LisError>().map(LisError::errno),
+            Some(libc::EINVAL)
+        );
+    }
+
+    #[tokio::test]
+    async fn rename_overwrite() {
+        let tmp_dir = TempDir::new().unwrap();
+        let mut lis = setup_lis(&tmp_dir).await;
+
+        let a = Path::new("/a");
+        let b = Path::new("/b");
+        lis.touch(a, None, None, None).await.unwrap();
+        lis.write(a, b"from a", 0).await.unwrap();
+        lis.touch(b, None, None, None).await.unwrap();
+        lis.write(b, b"from b", 0).await.unwrap();
+        let a_ino = lis.manifest.inodes[a];
+
+        // without overwrite, an existing destination is an error
+        let e = lis.rename(a, b, false).await.unwrap_err();
+        assert!(matches!(
+            e.downcast_ref::<LisError>(),
+            Some(LisError::AlreadyExists(_))
+        ));
+
+        lis.rename(a, b, true).await.unwrap();
+        assert_eq!(lis.read(b).await.unwrap(), "from a");
+        assert!(lis.read(a).await.is_err());
+        assert_eq!(lis.list(Path::new("/")).await.unwrap().len(), 1);
+        assert_eq!(lis.manifest.inodes.get(b), Some(&a_ino));
+        assert!(lis.obj_from_path(a).is_none());
+
+        // a directory with entries can't be replaced
+        lis.mkdir(Path::new("/dir"), None, None, None, false)
+            .await
+            .unwrap();
+        lis.mkdir(Path::new("/full"), None, None, None, false)
+            .await
+            .unwrap();
+        lis.touch(Path::new("/full/file"), None, None, None)
+            .await
+            .unwrap();
+        let e = lis
+            .rename(Path::new("/dir"), Path::new("/full"), true)
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            e.downcast_ref::<LisError>(),
+            Some(LisError::NotEmpty(_))
+        ));
+
+        // moving a directory takes its children along
+        lis.rename(Path::new("/full"), Path::new("/dir"), true)
+            .await
+            .unwrap();
+        assert_eq!(lis.read(Path::new("/dir/file")).await.unwrap(), "null");
+        assert!(lis.obj_from_path(Path::new("/dir/file")).is_some());
+        assert!(lis.obj_from_path(Path::new("/full/file")).is_none());
+    }
+
+    #[tokio::test]
+    async fn exchange() {
+        use iroh::client::docs::LiveEvent;
+
+        let tmp_dir = TempDir::new().unwrap();
+        let mut lis = setup_lis(&tmp_dir).await;
+
+        let a = Path::new("/a");
+        let b = Path::new("/b");
+        lis.touch(a, None, None, None).await.unwrap();
+        lis.write(a, b"from a", 0).await.unwrap();
+        lis.touch(b, None, None, None).await.unwrap();
+        lis.write(b, b"from b", 0).await.unwrap();
+        let (a_ino, b_ino) = (lis.manifest.inodes[a], lis.manifest.inodes[b]);
+
+        let mut events = Box::pin(lis.root_doc.subscribe().await.unwrap());
+        lis.exchange(a, b).await.unwrap();
+        assert_eq!(lis.read(a).await.unwrap(), "from b");
+        assert_eq!(lis.read(b).await.unwrap(), "from a");
+        assert_eq!(lis.manifest.inodes[a], b_ino);
+        assert_eq!(lis.manifest.inodes[b], a_ino);
+        assert_eq!(lis.obj_from_path(a).unwrap().attrs.inode, b_ino);
+
+        // both keys were only ever replaced, never deleted
+        let mut inserted = 0;
+        while let Ok(Some(event)) =
+            tokio::time::timeout(Duration::from_millis(200), events.next()).await
+        {
+            if let LiveEvent::InsertLocal { entry } = event.unwrap() {
+                assert!(entry.content_len() > 0);
+                inserted += 1;
+            }
+        }
+        assert_eq!(inserted, 2);
+
+        // a directory swaps with a file, taking its children along
+        lis.mkdir(Path::new("/dir"), None, None, None, false)
+            .await
+            .unwrap();
+        lis.touch(Path::new("/dir/file"), None, None, None)
+            .await
+            .unwrap();
+        lis.exchange(Path::new("/dir"), a).await.unwrap();
+        assert!(lis.is_dir(a));
+        assert_eq!(lis.read(Path::new("/dir")).await.unwrap(), "from b");
+        assert!(lis.obj_from_path(Path::new("/a/file")).is_some());
+        assert!(lis.obj_from_path(Path::new("/dir/file")).is_none());
+        assert_eq!(lis.list(a).await.unwrap().len(), 1);
+
+        let e = lis.exchange(a, Path::new("/missing")).await.unwrap_err();
+        assert!(matches!(
+            e.downcast_ref::<LisError>(),
+            Some(LisError::NotFound(_))
+        ));
+        assert!(lis.exchange(a, Path::new("/a/file")).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn move_into() {
+        let tmp_dir = TempDir::new().unwrap();
+        let mut lis = setup_lis(&tmp_dir).await;
+
+        let dest_dir = Path::new("/dest");
+        lis.mkdir(dest_dir, None, None, None, false).await.unwrap();
+        let sources = [Path::new("/a"), Path::new("/b"), Path::new("/c")];
+        for src in sources {
+            lis.touch(src, None, None, None).await.unwrap();
+        }
+        // collides with /a
+        lis.touch(Path::new("/dest/a"), None, None, None)
+            .await
+            .unwrap();
+
+        let results = lis.move_into(&sources, dest_dir).await.unwrap();
+        assert!(results[0].is_err());
+        assert_eq!(results[1].as_ref().unwrap(), Path::new("/dest/b"));
+        assert_eq!(results[2].as_ref().unwrap(), Path::new("/dest/c"));
+
+        assert_eq!(lis.list(dest_dir).await.unwrap().len(), 3);
+        assert!(lis.obj_from_path(Path::new("/a")).is_some());
+        assert!(lis.obj_from_path(Path::new("/b")).is_none());
+
+        // destination must be a directory
+        assert!(lis
+            .move_into(&[Path::new("/a")], Path::new("/dest/b"))
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn crtime() {
         let tmp_dir = TempDir::new().unwrap();
         let mut lis = setup_lis(&tmp_dir).await;
 
-        // Create empty file (touch) in lis
         let file_path = Path::new("/myfile.txt");
-        lis.touch(&file_path.to_path_buf(), None, None, None)
+        let before = SystemTime::now();
+        lis.touch(file_path, None, None, None).await.unwrap();
+        let after = SystemTime::now();
+
+        let created = lis.obj_from_path(file_path).unwrap().attrs.created;
+        assert!(before <= created && created <= after);
+
+        // a write updates mtime and ctime, never the creation time
+        lis.write(file_path, b"hello", 0).await.unwrap();
+
+        let file_attr = fuser::FileAttr::from(lis.obj_from_path(file_path).unwrap().attrs.clone());
+        assert_eq!(file_attr.crtime, created);
+        assert!(file_attr.mtime > created);
+        assert!(file_attr.ctime > created);
+    }
+
+    #[tokio::test]
+    async fn publish() {
+        let tmp_dir = TempDir::new().unwrap();
+        let mut lis = setup_lis(&tmp_dir).await;
+
+        let staging = Path::new("/.staging/release");
+        let public = Path::new("/releases/v1");
+        lis.mkdir(Path::new("/.staging"), None, None, None, false)
+            .await
+            .unwrap();
+        lis.mkdir(staging, None, None, None, false).await.unwrap();
+        let names = ["a.txt", "b.txt", "c.txt"];
+        for name in names {
+            let path = staging.join(name);
+            lis.touch(&path, None, None, None).await.unwrap();
+            lis.write(&path, name.as_bytes(), 0).await.unwrap();
+        }
+
+        // nothing is visible while staging
+        assert!(lis.list(public).await.is_err());
+
+        lis.publish(staging, public).await.unwrap();
+
+        assert_eq!(lis.list(public).await.unwrap().len(), names.len());
+        for name in names {
+            assert_eq!(lis.read(&public.join(name)).await.unwrap(), name);
+        }
+        assert!(lis.list(staging).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn invalid_names() {
+        let tmp_dir = TempDir::new().unwrap();
+        let mut lis = setup_lis(&tmp_dir).await;
+
+        let too_long = PathBuf::from(format!("/{}", "a".repeat(MAX_NAME_LENGTH as usize + 1)));
+        for path in [too_long.as_path(), Path::new("/a/"), Path::new("/a/../b")] {
+            let e = lis.touch(path, None, None, None).await.unwrap_err();
+            assert!(
+                matches!(e.downcast_ref::<LisError>(), Some(LisError::InvalidName(_))),
+                "{}",
+                path.display()
+            );
+            assert!(lis
+                .mkdir(path, None, None, None, false)
+                .await
+                .is_err_and(|e| matches!(
+                    e.downcast_ref::<LisError>(),
+                    Some(LisError::InvalidName(_))
+                )));
+        }
+
+        // nothing was created
+        assert_eq!(lis.list(Path::new("/")).await.unwrap().len(), 0);
+    }
+
+    #[tokio::test]
+    async fn set_metadata_many() {
+        let tmp_dir = TempDir::new().unwrap();
+        let mut lis = setup_lis(&tmp_dir).await;
+
+        lis.mkdir(Path::new("/dir"), None, None, None, false)
+            .await
+            .unwrap();
+        let mut paths = Vec::new();
+        for i in 0..200 {
+            let path = PathBuf::from(format!("/dir/{i}.txt"));
+            lis.touch(&path, None, None, None).await.unwrap();
+            paths.push(path);
+        }
+
+        let mtime = UNIX_EPOCH + Duration::from_secs(1_000_000);
+        let patch = MetadataPatch {
+            mtime: Some(mtime),
+            ..Default::default()
+        };
+        let updates: Vec<_> = paths.iter().map(|p| (p.clone(), patch.clone())).collect();
+        lis.set_metadata_many(&updates).unwrap();
+
+        for path in &paths {
+            let attrs = &lis.obj_from_path(path).unwrap().attrs;
+            assert_eq!(attrs.last_modified, mtime);
+            assert_eq!(attrs.mode, 0o744);
+        }
+
+        // a missing path fails the whole batch
+        let updates = vec![
+            (
+                paths[0].clone(),
+                MetadataPatch {
+                    mode: Some(0o600),
+                    ..Default::default()
+                },
+            ),
+            (PathBuf::from("/missing"), MetadataPatch::default()),
+        ];
+        assert!(lis.set_metadata_many(&updates).is_err());
+        assert_eq!(lis.obj_from_path(&paths[0]).unwrap().attrs.mode, 0o744);
+    }
+
+    #[tokio::test]
+    async fn directory_full() {
+        let tmp_dir = TempDir::new().unwrap();
+        let mut lis = setup_lis(&tmp_dir).await;
+        lis.max_dir_entries = 3;
+
+        lis.mkdir(Path::new("/dir"), None, None, None, false)
+            .await
+            .unwrap();
+        for i in 0..3 {
+            lis.touch(&PathBuf::from(format!("/dir/{i}")), None, None, None)
+                .await
+                .unwrap();
+        }
+
+        let e = lis
+            .touch(Path::new("/dir/3"), None, None, None)
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            e.downcast_ref::<LisError>(),
+            Some(LisError::DirectoryFull(path)) if path == Path::new("/dir")
+        ));
+        assert!(lis
+            .mkdir(Path::new("/dir/sub"), None, None, None, false)
+            .await
+            .is_err());
+        assert_eq!(lis.list(Path::new("/dir")).await.unwrap().len(), 3);
+
+        // existing entries can still be replaced
+        lis.touch(Path::new("/dir/0"), None, None, None)
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn create_invite() {
+        let tmp_dir = TempDir::new().unwrap();
+        let lis = setup_lis(&tmp_dir).await;
+        let node_id = lis.iroh_node.node_id();
+
+        for read_only in [false, true] {
+            let ticket = lis.create_invite(read_only).await.unwrap().to_string();
+            let parsed = DocTicket::from_str(&ticket).unwrap();
+            assert_eq!(parsed.capability.id(), lis.root_doc.id());
+            assert_eq!(
+                matches!(parsed.capability.kind(), CapabilityKind::Read),
+                read_only
+            );
+            assert_eq!(parsed.nodes[0].node_id, node_id);
+        }
+    }
+
+    #[tokio::test]
+    async fn serve() {
+        let tmp_dir = TempDir::new().unwrap();
+        let lis = setup_lis(&tmp_dir).await;
+        let hash = lis
+            .iroh_node
+            .blobs()
+            .add_bytes("served content")
+            .await
+            .unwrap()
+            .hash;
+        let node_addr = lis.iroh_node.net().node_addr().await.unwrap();
+
+        let (stop_tx, stop_rx) = tokio::sync::oneshot::channel();
+        let server = tokio::spawn(lis.serve(async {
+            let _ = stop_rx.await;
+        }));
+
+        let client = Node::memory().spawn().await.unwrap();
+        client
+            .blobs()
+            .download(hash, node_addr)
+            .await
+            .unwrap()
+            .finish()
             .await
             .unwrap();
+        assert_eq!(
+            client.blobs().read_to_bytes(hash).await.unwrap(),
+            "served content"
+        );
+
+        stop_tx.send(()).unwrap();
+        server.await.unwrap().unwrap();
+    }
+
+    #[tokio::test]
+    async fn remove() {
+        let tmp_dir = TempDir::new().unwrap();
+        let mut lis = setup_lis(&tmp_dir).await;
+
+        // Create empty file (touch) in lis
+        let file_path = Path::new("/myfile.txt");
+        lis.touch(file_path, None, None, None).await.unwrap();
 
         // retrieve content from the file (should be b"null")
         assert_eq!(lis.read(file_path).await.unwrap(), "null");
 
         // remove file
-        let _ = lis.remove(file_path).await.unwrap();
+        lis.remove(file_path).await.unwrap();
 
         // ensure file no longer exists
         assert_eq!(lis.list(Path::new("/")).await.unwrap().len(), 0);
+        assert!(lis.obj_from_path(file_path).is_none());
+
+        let e = lis.remove(file_path).await.unwrap_err();
+        assert!(matches!(
+            e.downcast_ref::<LisError>(),
+            Some(LisError::NotFound(_))
+        ));
+
+        // directories go through rmdir
+        let dir_path = Path::new("/dir");
+        lis.mkdir(dir_path, None, None, None, false).await.unwrap();
+        let e = lis.remove(dir_path).await.unwrap_err();
+        assert!(e.to_string().contains("rmdir"));
+        assert_eq!(errno_from_error(&e, libc::EIO), libc::EISDIR);
+        assert!(lis.is_dir(dir_path));
     }
 }
@@ -0,0 +1,144 @@
+use iroh::docs::store::Query;
+
+use crate::{audit::AuditOp, fuse::FileKind, prelude::*, util::validate_path, LisError};
+
+impl Lis {
+    /// Adds `new_path` as another name for the file at `existing`, sharing its inode and content
+    /// Writes through either path show up in both. The inode is released when the last one goes
+    pub async fn link(&mut self, existing: &Path, new_path: &Path) -> Result<()> {
+        self.check_writable(new_path)?;
+        validate_path(new_path)?;
+        let mut attrs = match self.obj_from_path(existing) {
+            Some(obj) if matches!(obj.attrs.kind, FileKind::Directory) => {
+                return Err(LisError::IsADirectory(existing.to_path_buf()).into())
+            }
+            Some(obj) => obj.attrs.clone(),
+            None => return Err(LisError::NotFound(existing.to_path_buf()).into()),
+        };
+
+        let (existing_doc, existing_key) = self.doc_and_key(existing).await?;
+        let entry = existing_doc
+            .get_one(Query::key_exact(existing_key))
+            .await?
+            .ok_or_else(|| LisError::NotFound(existing.to_path_buf()))?;
+
+        let (doc, key) = self.doc_and_key(new_path).await?;
+        if doc.get_one(Query::key_exact(key.clone())).await?.is_some() {
+            return Err(LisError::AlreadyExists(new_path.to_path_buf()).into());
+        }
+        self.check_dir_capacity(&doc, new_path).await?;
+
+        let author = self.iroh_node.authors().default().await?;
+        doc.set_hash(author, key, entry.content_hash(), entry.content_len())
+            .await?;
+
+        self.manifest
+            .inodes
+            .insert(new_path.to_path_buf(), attrs.inode);
+        attrs.hardlinks += 1;
+        attrs.last_metadata_changed = SystemTime::now();
+        self.write_inode(&attrs)?;
+        self.audit(AuditOp::Create, new_path).await?;
+
+        Ok(())
+    }
+
+    /// Every path pointing to inode `ino`
+    pub(crate) fn linked_paths(&self, ino: Inode) -> Vec<PathBuf> {
+        self.manifest
+            .inodes
+            .iter()
+            .filter(|(_, linked)| **linked == ino)
+            .map(|(path, _)| path.clone())
+            .collect()
+    }
+
+    /// Points the other hardlinks of `full_path` to its current content, after it changed
+    pub(crate) async fn sync_links(&mut self, full_path: &Path) -> Result<()> {
+        let Some(obj) = self.obj_from_path(full_path) else {
+            return Ok(());
+        };
+        if obj.attrs.hardlinks < 2 {
+            return Ok(());
+        }
+        let others: Vec<PathBuf> = self
+            .linked_paths(obj.attrs.inode)
+            .into_iter()
+            .filter(|path| path != full_path)
+            .collect();
+
+        let (doc, key) = self.doc_and_key(full_path).await?;
+        let entry = doc
+            .get_one(Query::key_exact(key))
+            .await?
+            .ok_or_else(|| LisError::NotFound(full_path.to_path_buf()))?;
+        let author = self.iroh_node.authors().default().await?;
+        for path in others {
+            let (doc, key) = self.doc_and_key(&path).await?;
+            doc.set_hash(author, key, entry.content_hash(), entry.content_len())
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// Drops `full_path` from the manifest while other hardlinks keep its inode alive, moving the
+    /// object to one of them if it was known by this path
+    pub(crate) fn forget_link(&mut self, full_path: &Path, ino: Inode) {
+        self.manifest.inodes.remove(full_path);
+        let Some(other) = self.linked_paths(ino).into_iter().next() else {
+            return;
+        };
+        if let Some(obj) = self.manifest.objects.get_mut(&ino) {
+            if obj.full_path == full_path {
+                obj.full_path = other;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[tokio::test]
+    async fn hardlinks_share_content() {
+        let tmp_dir = TempDir::new().unwrap();
+        let mut lis = Lis::new(&tmp_dir.path().to_path_buf(), true).await.unwrap();
+
+        let a = Path::new("/a.txt");
+        let b = Path::new("/dir/b.txt");
+        lis.mkdir(Path::new("/dir"), None, None, None, false)
+            .await
+            .unwrap();
+        lis.touch(a, None, None, None).await.unwrap();
+        lis.write(a, b"hello", 0).await.unwrap();
+        lis.link(a, b).await.unwrap();
+
+        let ino = lis.obj_from_path(a).unwrap().attrs.inode;
+        assert_eq!(lis.obj_from_path(b).unwrap().attrs.inode, ino);
+        assert_eq!(lis.obj_from_path(b).unwrap().attrs.hardlinks, 2);
+        assert_eq!(lis.read(b).await.unwrap(), "hello");
+
+        // a write through one name shows up through the other
+        lis.write(b, b"HELLO again", 0).await.unwrap();
+        assert_eq!(lis.read(a).await.unwrap(), "HELLO again");
+
+        // removing the name the object was created with keeps the data
+        lis.remove(a).await.unwrap();
+        assert!(lis.obj_from_path(a).is_none());
+        let obj = lis.obj_from_path(b).unwrap();
+        assert_eq!(obj.attrs.hardlinks, 1);
+        assert_eq!(obj.full_path, b);
+        assert_eq!(lis.read(b).await.unwrap(), "HELLO again");
+
+        lis.remove(b).await.unwrap();
+        assert!(!lis.manifest.objects.contains_key(&ino));
+
+        let e = lis.link(Path::new("/dir"), a).await.unwrap_err();
+        assert!(matches!(
+            e.downcast_ref::<LisError>(),
+            Some(LisError::IsADirectory(_))
+        ));
+    }
+}
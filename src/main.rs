@@ -1,23 +1,109 @@
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 use clap::Parser;
 #[allow(unused)]
 use log::{debug, error, info, warn, LevelFilter};
 use std::{
     io::Write,
-    path::Path,
-    sync::{
-        atomic::{AtomicBool, Ordering},
-        Arc,
-    },
+    os::unix::{fs::MetadataExt, process::CommandExt},
+    path::{Path, PathBuf},
+    process::Stdio,
+    sync::Arc,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
 };
 
-use lis::{Cli, Commands, Lis, Manifest};
+use futures_lite::StreamExt;
+use tokio::sync::Notify;
+
+use lis::{
+    write_completions, CheckStatus, Cli, Commands, Config, FileKind, Format, Lis, LisError,
+    Metadata, Span,
+};
 
 #[tokio::main]
 async fn main() -> Result<()> {
     let cli = Cli::parse();
+    let format = cli.format;
+
+    match run(cli).await {
+        Ok(()) => Ok(()),
+        Err(e) if format == Format::Json => {
+            eprintln!("{}", error_json(&e));
+            std::process::exit(1);
+        }
+        Err(e) => Err(e),
+    }
+}
+
+/// Runs `lis mount --foreground` detached from the terminal, and returns once it's mounted
+fn mount_in_background(mountpoint: &Path) -> Result<()> {
+    let unmounted = std::fs::metadata(mountpoint)?.dev();
+    let mut child = std::process::Command::new(std::env::current_exe()?)
+        .args(std::env::args_os().skip(1))
+        .arg("--foreground")
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        // its own process group, so Ctrl-C in this terminal doesn't reach it
+        .process_group(0)
+        .spawn()?;
+
+    let deadline = Instant::now() + Duration::from_secs(10);
+    loop {
+        if let Some(status) = child.try_wait()? {
+            return Err(anyhow!(
+                "Could not mount {}, lis exited with {status} (--foreground shows why)",
+                mountpoint.display()
+            ));
+        }
+        // a mountpoint shows the device of what's mounted on it
+        if std::fs::metadata(mountpoint)?.dev() != unmounted {
+            println!(
+                "Mounted {} (pid {}), unmount with `umount {0}`",
+                mountpoint.display(),
+                child.id()
+            );
+            return Ok(());
+        }
+        if Instant::now() > deadline {
+            child.kill()?;
+            return Err(anyhow!("Timed out mounting {}", mountpoint.display()));
+        }
+        std::thread::sleep(Duration::from_millis(50));
+    }
+}
+
+/// Formats an error as `{ "error": { "kind": ..., "message": ..., "path": ... } }`
+fn error_json(e: &anyhow::Error) -> serde_json::Value {
+    let lis_error = e.chain().find_map(|cause| cause.downcast_ref::<LisError>());
+    let kind = match lis_error {
+        Some(lis_error) => lis_error.kind(),
+        None if e.chain().any(|cause| cause.is::<std::io::Error>()) => "io",
+        None => "other",
+    };
+    let path = lis_error
+        .and_then(|lis_error| lis_error.path())
+        .map(|path| path.display().to_string());
+
+    serde_json::json!({
+        "error": {
+            "kind": kind,
+            "message": format!("{e:#}"),
+            "path": path,
+        }
+    })
+}
+
+async fn run(cli: Cli) -> Result<()> {
+    // no node needed, don't create one
+    if let Commands::Completions { shell } = cli.command {
+        write_completions(shell, &mut std::io::stdout());
+        return Ok(());
+    }
+
+    let mut config = Config::load(&cli.root)?;
+    config.merge_cli(&cli);
 
-    let log_level = match cli.verbosity {
+    let log_level = match config.verbosity {
         0 => LevelFilter::Error,
         1 => LevelFilter::Warn,
         2 => LevelFilter::Info,
@@ -31,126 +117,295 @@ async fn main() -> Result<()> {
     log_builder.filter(None, log::LevelFilter::Off);
     log_builder.init();
 
+    if let Commands::Join { ticket } = &cli.command {
+        Lis::join(&cli.root, ticket, |path| {
+            println!("Synced {}", path.display())
+        })
+        .await?;
+        return Ok(());
+    }
+
+    // the node is opened by the foreground process, it can't be open here too
+    if let Commands::Mount {
+        mountpoint,
+        foreground: false,
+        ..
+    } = &cli.command
+    {
+        return mount_in_background(mountpoint);
+    }
+
     let mut lis = Lis::new(&cli.root, cli.overwrite).await?;
+    lis.direct_io = config.direct_io;
+    lis.max_dir_entries = config.max_dir_entries;
+    lis.noatime = config.noatime;
+    lis.trash = config.trash;
+    lis.max_open_files = config.max_open_files;
 
     match &cli.command {
-        Commands::ImportFile { paths } => {
+        Commands::ImportFile {
+            paths,
+            overwrite,
+            mtime,
+        } => {
+            lis.import_mtime = *mtime;
             for path in paths {
-                info!(
-                    "Added {} (keys: {:#?})",
-                    path.display(),
-                    lis.import_file(path.as_path(), Path::new(path.file_name().unwrap()))
-                        .await?
-                );
-            }
-        }
-        Commands::Mkdir { path } => {
-            info!(
-                "Created {} (id: {:#?})",
-                path.display(),
-                lis.mkdir(path, None, None, None).await?
-            );
+                let name = path
+                    .file_name()
+                    .ok_or_else(|| anyhow!("{} has no file name", path.display()))?;
+                let imported = lis.import_file(path, Path::new(name), *overwrite).await?;
+                for (src, key) in imported {
+                    println!("Imported {} to {key}", src.display());
+                }
+            }
+        }
+        Commands::ImportUrl {
+            url,
+            dst,
+            overwrite,
+        } => {
+            let hash = lis.import_from_url(url, dst, *overwrite).await?;
+            println!("Imported {url} to {} ({hash})", dst.display());
+        }
+        Commands::Mkdir { path, parents } => {
+            if *parents {
+                lis.mkdir_parents(path).await?;
+            }
+            // not inside info!(), which skips its arguments when the level is off
+            let id = lis.mkdir(path, None, None, None, *parents).await?;
+            info!("Created {} (id: {:#?})", path.display(), id);
+        }
+        Commands::List {
+            path,
+            recursive: true,
+        } => {
+            let path = path.as_deref().unwrap_or(Path::new("/"));
+            let mut walk = Box::pin(lis.walk_stream(path).await?);
+            let mut entries = Vec::new();
+            while let Some(next) = walk.next().await {
+                let (full_path, kind) = next?;
+                if cli.format == Format::Json {
+                    let metadata = lis
+                        .obj_from_path(&full_path)
+                        .map(|obj| Metadata::from(&obj.attrs));
+                    entries.push(serde_json::json!({ "name": full_path, "metadata": metadata }));
+                } else if kind == FileKind::Directory {
+                    println!("{}/", full_path.display());
+                } else {
+                    println!("{}", full_path.display());
+                }
+            }
+            if cli.format == Format::Json {
+                println!("{}", serde_json::to_string_pretty(&entries)?);
+            }
+        }
+        Commands::List { path, .. } if cli.format == Format::Json => {
+            let path = path.as_deref().unwrap_or(Path::new("/"));
+            let entries: Vec<_> = lis
+                .list_with_metadata(path)
+                .await?
+                .into_iter()
+                .map(|(name, metadata)| serde_json::json!({ "name": name, "metadata": metadata }))
+                .collect();
+            println!("{}", serde_json::to_string_pretty(&entries)?);
         }
-        Commands::List { path } => {
+        Commands::List { path, .. } => {
             let entries = match path {
                 Some(path) => lis.list(path).await?,
                 None => lis.list(Path::new("/")).await?,
             };
-            for entry in entries {
-                if let Ok(entry) = entry {
-                    let key = entry.key();
-                    let hash = entry.content_hash();
-                    println!("{} ({})", std::str::from_utf8(key)?, hash.fmt_short());
+            for entry in entries.into_iter().flatten() {
+                let key = entry.key();
+                let hash = entry.content_hash();
+                println!("{} ({})", std::str::from_utf8(key)?, hash.fmt_short());
+            }
+        }
+        Commands::Stat { path } => {
+            let stat = lis
+                .stat(path)
+                .await?
+                .ok_or_else(|| LisError::NotFound(path.clone()))?;
+            if cli.format == Format::Json {
+                println!("{}", serde_json::to_string_pretty(&stat)?);
+            } else {
+                let secs = |time: SystemTime| {
+                    time.duration_since(UNIX_EPOCH)
+                        .map_or(0, |since| since.as_secs())
+                };
+                println!("kind: {:?}", stat.kind);
+                println!("size: {}", stat.size);
+                println!("mode: {:o}", stat.mode);
+                println!("uid: {}, gid: {}", stat.uid, stat.gid);
+                println!("modified: {}", secs(stat.modified));
+                println!("changed: {}", secs(stat.changed));
+                for (name, value) in [
+                    ("content hash", &stat.content_hash),
+                    ("original filename", &stat.original_filename),
+                    ("imported by", &stat.imported_by),
+                    ("description", &stat.description),
+                ] {
+                    if let Some(value) = value {
+                        println!("{name}: {value}");
+                    }
                 }
             }
         }
-        Commands::Read { paths } => {
+        Commands::Read {
+            paths,
+            lines,
+            bytes,
+        } => {
+            // concatenated in order, like cat
+            let mut stdout = std::io::stdout().lock();
             for path in paths {
-                let content = lis.read(path.as_path()).await?;
-                // Convert to &str
-                let ascii_content = std::str::from_utf8(&content)?;
-                println!("{}\n\n{}", path.display(), ascii_content);
+                if let Some(span) = lines {
+                    stdout.write_all(&lis.read_lines(path, *span).await?)?;
+                    continue;
+                }
+                if let Some(span) = bytes {
+                    stdout.write_all(&lis.read_bytes(path, *span).await?)?;
+                    continue;
+                }
+                let mut chunks = lis.read_stream(path).await?;
+                while let Some(chunk) = chunks.next().await {
+                    stdout.write_all(&chunk?)?;
+                }
+            }
+            stdout.flush()?;
+        }
+        Commands::Tail {
+            path,
+            lines,
+            follow,
+        } => {
+            let mut stdout = std::io::stdout().lock();
+            let span = Span {
+                start: Some(-i64::from(*lines)),
+                end: None,
+            };
+            stdout.write_all(&lis.read_lines(path, span).await?)?;
+            stdout.flush()?;
+            if *follow {
+                let mut appended = lis.follow(path, lis.file_size(path).await?).await?;
+                while let Some(bytes) = appended.next().await {
+                    stdout.write_all(&bytes?)?;
+                    stdout.flush()?;
+                }
             }
         }
         Commands::Rm { paths } => {
+            let mut failed = 0;
             for path in paths {
-                let _ = lis.remove(path.as_path()).await?;
-                println!("Removed {}", path.display());
+                match lis.remove(path).await {
+                    Ok(()) => println!("Removed {}", path.display()),
+                    Err(e) => {
+                        error!("Could not remove {}: {e:#}", path.display());
+                        failed += 1;
+                    }
+                }
+            }
+            if failed > 0 {
+                return Err(anyhow!("{failed} path(s) could not be removed"));
             }
         }
         Commands::Rmdir { paths } => {
+            let mut failed = 0;
             for path in paths {
-                let _ = lis.rmdir(path).await?;
-                println!("Removed {}", path.display());
-            }
-        }
-        Commands::Join { ticket } => {
-            lis.join(ticket)?;
-
-            debug!("Connecting...");
-            std::io::stdout().flush()?;
-            let conn = lis
-                .iroh_node
-                .endpoint()
-                .connect(ticket.node_addr().clone(), iroh::blobs::protocol::ALPN)
-                .await?;
-            debug!("connected!");
-
-            debug!("Receiving manifest...");
-            std::io::stdout().flush()?;
-            let mut stream = conn.accept_uni().await?;
-            let manifest: Manifest = serde_json::from_slice(&stream.read_to_end(2048).await?)?;
-            debug!("ok.");
-            debug!("{:#?}", manifest);
-
-            debug!("Saving manifest...");
-            // TODO
-            debug!("ok.");
-
-            debug!("All done.");
-        }
-        Commands::Invite {} => {
-            let ticket = lis.invite().await?;
-            let endpoint = lis.iroh_node.endpoint().clone();
-            // TODO: timeout
-            let handle = tokio::spawn(async move {
-                if let Some(conn) = endpoint.accept().await {
-                    debug!("Connecting with {}", conn.remote_address());
-                    let conn = conn.await.unwrap();
-
-                    debug!("Updating manifest...");
-                    // TODO update manifest
-                    debug!("ok.");
-
-                    debug!("Sending manifest...");
-                    let serialized_manifest = serde_json::to_string(&lis.manifest).unwrap();
-                    let mut stream = conn.open_uni().await.unwrap();
-                    stream
-                        .write_all(&serialized_manifest.as_bytes())
-                        .await
-                        .unwrap();
-                    stream.finish().await.unwrap();
-                    debug!("ok.");
-
-                    debug!("All done.");
+                match lis.rmdir(path).await {
+                    Ok(()) => println!("Removed {}", path.display()),
+                    Err(e) => {
+                        error!("Could not remove {}: {e:#}", path.display());
+                        failed += 1;
+                    }
                 }
-            });
-
-            println!("\n\n\tlis <lis_root> join {ticket}\n");
-            handle.await?;
+            }
+            if failed > 0 {
+                return Err(anyhow!("{failed} path(s) could not be removed"));
+            }
         }
-        Commands::Mount { mountpoint } => {
-            lis.root = mountpoint.clone();
-            let _handle = fuser::spawn_mount2(lis, &mountpoint, &[])?;
-            let stop = Arc::new(AtomicBool::new(false));
-
+        Commands::Mv { paths } => {
+            let (dest, sources) = paths.split_last().expect("clap requires two paths");
+            if sources.len() == 1 && !lis.is_dir(dest) {
+                lis.rename(&sources[0], dest, true).await?;
+                println!("Moved {} to {}", sources[0].display(), dest.display());
+            } else {
+                let sources: Vec<&Path> = sources.iter().map(PathBuf::as_path).collect();
+                let results = lis.move_into(&sources, dest).await?;
+                let mut failed = 0;
+                for (src, result) in sources.iter().zip(results) {
+                    match result {
+                        Ok(new_path) => {
+                            println!("Moved {} to {}", src.display(), new_path.display())
+                        }
+                        Err(e) => {
+                            error!("Could not move {}: {e}", src.display());
+                            failed += 1;
+                        }
+                    }
+                }
+                if failed > 0 {
+                    return Err(anyhow!("{failed} path(s) could not be moved"));
+                }
+            }
+        }
+        Commands::Invite { read_only } => {
+            let ticket = lis.create_invite(*read_only).await?;
+            println!("Serving, join with:\n\n\tlis <lis_root> join {ticket}\n");
+            let stop = Arc::new(Notify::new());
             let stop_clone = stop.clone();
+            ctrlc::set_handler(move || stop_clone.notify_one())?;
+            return lis.serve(stop.notified()).await;
+        }
+        Commands::Mount { mountpoint, .. } => {
+            let mut session = fuser::Session::new(lis, mountpoint, &config.mount_options())?;
+            let mut unmounter = session.unmount_callable();
             let mountpoint_clone = mountpoint.clone();
             ctrlc::set_handler(move || {
                 println!("unmounting {}", mountpoint_clone.display());
-                stop_clone.store(true, Ordering::SeqCst);
+                if let Err(e) = unmounter.unmount() {
+                    error!("Could not unmount {}: {e}", mountpoint_clone.display());
+                }
             })?;
-            while !stop.load(Ordering::SeqCst) {}
+            // callbacks block on the runtime, so they can't run on one of its worker threads
+            tokio::task::spawn_blocking(move || session.run()).await??;
+        }
+        Commands::Serve {} => {
+            println!(
+                "Serving, join read-only with:\n\n\tlis <lis_root> join {}\n",
+                lis.create_invite(true).await?
+            );
+            let stop = Arc::new(Notify::new());
+            let stop_clone = stop.clone();
+            ctrlc::set_handler(move || stop_clone.notify_one())?;
+            return lis.serve(stop.notified()).await;
+        }
+        // handled before the node is created
+        Commands::Completions { .. } | Commands::Join { .. } => {}
+        Commands::Config {} => {
+            print!("{}", config.to_toml()?);
+        }
+        Commands::Doctor {} => {
+            let checks = lis.doctor().await;
+            for check in &checks {
+                println!("{check}");
+            }
+            let failed = checks
+                .iter()
+                .filter(|check| check.status == CheckStatus::Fail)
+                .count();
+            if failed > 0 {
+                return Err(anyhow!("{failed} check(s) failed"));
+            }
+        }
+        Commands::Audit { path, enable } => {
+            if *enable {
+                lis.enable_audit_log().await?;
+            }
+            let path = path.as_deref().unwrap_or(Path::new("/"));
+            for entry in lis.audit_log(path, ..).await? {
+                println!("{entry}");
+            }
+            lis.verify_audit_log().await?;
         }
     }
 
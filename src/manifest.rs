@@ -1,10 +1,13 @@
 use std::{
     collections::BTreeMap,
-    fs,
+    fs, io,
     sync::atomic::{AtomicU64, Ordering},
 };
 
-use crate::{fuse::FileKind, object::Object, prelude::*};
+use crate::{
+    audit::AuditHead, fsync::CheckpointId, fuse::FileKind, hash::HashAlgorithm, object::Object,
+    prelude::*,
+};
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Manifest {
@@ -16,6 +19,15 @@ pub struct Manifest {
     pub inodes: BTreeMap<PathBuf, Inode>, // key -> inode
     pub cur_ino: AtomicU64,
     pub cur_fh: AtomicU64,
+    /// Algorithm of the secondary content hash recorded for each file
+    #[serde(default)]
+    pub hash_algorithm: HashAlgorithm,
+    /// Head of the audit journal, if it's enabled
+    #[serde(default)]
+    pub audit: Option<AuditHead>,
+    /// Last checkpoint recorded with `Lis::checkpoint`
+    #[serde(default)]
+    pub checkpoint: Option<CheckpointId>,
 }
 
 impl Manifest {
@@ -45,24 +57,104 @@ impl Manifest {
             inodes,
             cur_ino,
             cur_fh,
+            hash_algorithm: HashAlgorithm::default(),
+            audit: None,
+            checkpoint: None,
         })
     }
 
+    /// Path to the manifest.json file backing this manifest
+    pub fn path(&self) -> &Path {
+        &self.manifest_path
+    }
+
+    /// Writes the manifest to a temporary file and renames it over manifest.json, so a node
+    /// killed mid-save leaves either the old manifest or the new one, never half of one. The
+    /// previous one is kept as manifest.json.bak for `load` to fall back on
     pub fn save(&self) -> Result<()> {
-        // write to manifest.json file
         let json_string = serde_json::to_string(self)?;
-        fs::write(self.manifest_path.clone(), json_string)?;
+        let tmp_path = self.manifest_path.with_extension("json.tmp");
+        fs::write(&tmp_path, json_string)?;
+
+        if self.manifest_path.exists() {
+            let bak_path = backup_path(&self.manifest_path);
+            // fine if there was none, but a stale one left behind would make the link fail too
+            match fs::remove_file(&bak_path) {
+                Err(e) if e.kind() != io::ErrorKind::NotFound => {
+                    return Err(anyhow::Error::new(e)
+                        .context(format!("Could not remove {}", bak_path.display())))
+                }
+                _ => {}
+            }
+            // a link is enough, the rename below gives manifest.json a new file
+            if fs::hard_link(&self.manifest_path, &bak_path).is_err() {
+                fs::copy(&self.manifest_path, &bak_path)?;
+            }
+        }
+        fs::rename(&tmp_path, &self.manifest_path)?;
         Ok(())
     }
 
+    /// Loads the manifest at `manifest_path`, or the backup `save` left if it can't be read
+    /// `None` if there's neither, a new node
     pub fn load(manifest_path: &Path) -> Result<Option<Self>> {
-        if manifest_path.exists() {
-            // load manifest
-            let file_content = fs::read_to_string(manifest_path)?;
-            let manifest: Manifest = serde_json::from_str(&file_content)?;
-            Ok(Some(manifest))
-        } else {
-            Ok(None)
+        let bak_path = backup_path(manifest_path);
+        if !manifest_path.exists() && !bak_path.exists() {
+            return Ok(None);
         }
+        match Manifest::read(manifest_path) {
+            Ok(manifest) => Ok(Some(manifest)),
+            Err(e) => {
+                let manifest = Manifest::read(&bak_path).map_err(|_| {
+                    e.context(format!("Could not load {}", manifest_path.display()))
+                })?;
+                warn!(
+                    "Could not load {}, using the previous one in {}",
+                    manifest_path.display(),
+                    bak_path.display()
+                );
+                Ok(Some(manifest))
+            }
+        }
+    }
+
+    /// Parses the manifest file at `path`, without falling back to the backup
+    pub(crate) fn read(path: &Path) -> Result<Self> {
+        let file_content = fs::read_to_string(path)?;
+        Ok(serde_json::from_str(&file_content)?)
+    }
+}
+
+/// Where `Manifest::save` keeps the previous manifest
+fn backup_path(manifest_path: &Path) -> PathBuf {
+    manifest_path.with_extension("json.bak")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn save_keeps_a_backup_to_load() {
+        let tmp_dir = TempDir::new().unwrap();
+        let manifest_path = tmp_dir.path().join("manifest.json");
+        assert!(Manifest::load(&manifest_path).unwrap().is_none());
+
+        let mut manifest = Manifest::new(manifest_path.clone(), "first".to_string()).unwrap();
+        manifest.save().unwrap();
+        manifest.root_doc_id = "second".to_string();
+        manifest.save().unwrap();
+        assert!(!manifest_path.with_extension("json.tmp").exists());
+        let loaded = Manifest::load(&manifest_path).unwrap().unwrap();
+        assert_eq!(loaded.root_doc_id, "second");
+
+        // killed halfway through writing it
+        fs::write(&manifest_path, r#"{"manifest_path": "/tmp/manif"#).unwrap();
+        let loaded = Manifest::load(&manifest_path).unwrap().unwrap();
+        assert_eq!(loaded.root_doc_id, "first");
+
+        fs::write(backup_path(&manifest_path), "").unwrap();
+        assert!(Manifest::load(&manifest_path).is_err());
     }
 }
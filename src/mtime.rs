@@ -0,0 +1,34 @@
+use clap::ValueEnum;
+use iroh::blobs::Hash;
+
+use crate::prelude::*;
+
+/// What modification time imported files get
+/// `Now` is what a copy would get. The other two don't depend on when the import ran, so
+/// importing the same files again stores the same attributes, for reproducible builds
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum ImportMtime {
+    /// The time of the import
+    #[default]
+    Now,
+    /// The Unix epoch, 1970-01-01
+    Epoch,
+    /// A time derived from the content's hash, so files with different content still differ
+    Content,
+}
+
+impl ImportMtime {
+    /// Modification time for imported content with `hash`
+    pub fn mtime(&self, hash: &Hash) -> SystemTime {
+        match self {
+            ImportMtime::Now => SystemTime::now(),
+            ImportMtime::Epoch => UNIX_EPOCH,
+            ImportMtime::Content => {
+                // 32 bits of seconds, somewhere between 1970 and 2106
+                let bytes = hash.as_bytes();
+                let secs = u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+                UNIX_EPOCH + Duration::from_secs(secs.into())
+            }
+        }
+    }
+}
@@ -12,6 +12,27 @@ pub struct Object {
     pub full_path: PathBuf,
     //TODO: pub parent: Inode, (actually put this in attr)
     pub attrs: InodeAttributes,
+    /// URL the content was downloaded from, for objects imported with `import_from_url`
+    #[serde(default)]
+    pub source_url: Option<String>,
+    /// Content type reported by the server the content was downloaded from
+    #[serde(default)]
+    pub content_type: Option<String>,
+    /// Hex digest of the content in the store's `HashAlgorithm`, if it keeps one
+    #[serde(default)]
+    pub content_hash: Option<String>,
+    /// The content changed since `content_hash` was computed, see `Lis::content_hash`
+    #[serde(default)]
+    pub content_hash_stale: bool,
+    /// Name of the file or download it was imported from
+    #[serde(default)]
+    pub original_filename: Option<String>,
+    /// Node id of the node that imported it
+    #[serde(default)]
+    pub imported_by: Option<String>,
+    /// Free-form description, see `Lis::set_description`
+    #[serde(default)]
+    pub description: Option<String>,
 }
 
 impl Object {
@@ -32,7 +53,8 @@ impl Object {
                 last_accessed: SystemTime::now(),
                 last_modified: SystemTime::now(),
                 last_metadata_changed: SystemTime::now(),
-                kind: kind.into(),
+                created: SystemTime::now(),
+                kind,
                 mode: mode.unwrap_or(0o744),
                 hardlinks: 1,
                 uid: uid.unwrap_or(unsafe { libc::getuid() }),
@@ -46,18 +68,132 @@ impl Object {
                 last_accessed: SystemTime::now(),
                 last_modified: SystemTime::now(),
                 last_metadata_changed: SystemTime::now(),
-                kind: kind.into(),
+                created: SystemTime::now(),
+                kind,
                 mode: mode.unwrap_or(0o755),
                 hardlinks: 2, // Directories start with link count of 2, since they have a self link
                 uid: uid.unwrap_or_else(|| unsafe { libc::getuid() }),
                 gid: gid.unwrap_or_else(|| unsafe { libc::getgid() }),
                 xattrs: Default::default(),
             },
-            FileKind::Symlink => unimplemented!(),
+            FileKind::Symlink => InodeAttributes {
+                inode,
+                open_file_handles: 0,
+                size: size.unwrap_or(0), // length of the target path
+                last_accessed: SystemTime::now(),
+                last_modified: SystemTime::now(),
+                last_metadata_changed: SystemTime::now(),
+                created: SystemTime::now(),
+                kind,
+                mode: mode.unwrap_or(0o777), // permissions of a symlink are never checked
+                hardlinks: 1,
+                uid: uid.unwrap_or_else(|| unsafe { libc::getuid() }),
+                gid: gid.unwrap_or_else(|| unsafe { libc::getgid() }),
+                xattrs: Default::default(),
+            },
         };
         Ok(Object {
             full_path: full_path.to_path_buf(),
             attrs,
+            source_url: None,
+            content_type: None,
+            content_hash: None,
+            content_hash_stale: false,
+            original_filename: None,
+            imported_by: None,
+            description: None,
         })
     }
 }
+
+/// What `Lis::list_with_metadata` reports about each entry
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Metadata {
+    pub inode: Inode,
+    pub kind: FileKind,
+    pub size: u64,
+    pub mode: u16,
+    pub uid: u32,
+    pub gid: u32,
+    pub modified: SystemTime,
+}
+
+impl From<&InodeAttributes> for Metadata {
+    fn from(attrs: &InodeAttributes) -> Self {
+        Metadata {
+            inode: attrs.inode,
+            kind: attrs.kind,
+            size: attrs.size,
+            mode: attrs.mode,
+            uid: attrs.uid,
+            gid: attrs.gid,
+            modified: attrs.last_modified,
+        }
+    }
+}
+
+/// What `Lis::stat` reports about a path, straight from the manifest
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct StatInfo {
+    pub kind: FileKind,
+    pub size: u64,
+    pub mode: u16,
+    pub uid: u32,
+    pub gid: u32,
+    pub modified: SystemTime,
+    /// Last change of the metadata or the content
+    pub changed: SystemTime,
+    /// Hex digest in the store's `HashAlgorithm`, if it keeps one
+    pub content_hash: Option<String>,
+    pub original_filename: Option<String>,
+    pub imported_by: Option<String>,
+    pub description: Option<String>,
+}
+
+impl From<&Object> for StatInfo {
+    fn from(obj: &Object) -> Self {
+        StatInfo {
+            kind: obj.attrs.kind,
+            size: obj.attrs.size,
+            mode: obj.attrs.mode,
+            uid: obj.attrs.uid,
+            gid: obj.attrs.gid,
+            modified: obj.attrs.last_modified,
+            changed: obj.attrs.last_metadata_changed,
+            content_hash: obj.content_hash.clone(),
+            original_filename: obj.original_filename.clone(),
+            imported_by: obj.imported_by.clone(),
+            description: obj.description.clone(),
+        }
+    }
+}
+
+/// Metadata changes for `Lis::set_metadata_many`. `None` fields are left as they are
+#[derive(Debug, Clone, Default)]
+pub struct MetadataPatch {
+    pub mode: Option<u16>,
+    pub uid: Option<u32>,
+    pub gid: Option<u32>,
+    pub mtime: Option<SystemTime>,
+}
+
+impl MetadataPatch {
+    /// Applies the patch, bumping ctime if anything changed
+    pub fn apply(&self, attrs: &mut InodeAttributes) {
+        if let Some(mode) = self.mode {
+            attrs.mode = mode;
+        }
+        if let Some(uid) = self.uid {
+            attrs.uid = uid;
+        }
+        if let Some(gid) = self.gid {
+            attrs.gid = gid;
+        }
+        if let Some(mtime) = self.mtime {
+            attrs.last_modified = mtime;
+        }
+        if self.mode.is_some() || self.uid.is_some() || self.gid.is_some() || self.mtime.is_some() {
+            attrs.last_metadata_changed = SystemTime::now();
+        }
+    }
+}
@@ -0,0 +1,82 @@
+use std::collections::BTreeSet;
+
+use futures_lite::StreamExt;
+use iroh::{blobs::Hash, docs::store::Query};
+
+use crate::prelude::*;
+
+/// Blobs in the local store that nothing references anymore
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct OrphanReport {
+    /// Hash and size of each orphaned blob
+    pub blobs: Vec<(Hash, u64)>,
+    /// Total size of the orphaned blobs
+    pub bytes: u64,
+}
+
+impl Lis {
+    /// Lists the complete blobs in the local store that no doc entry or tag points to
+    /// These are left behind by overwritten or removed files, crashes and aborted writes, and
+    /// take up space until Iroh's GC collects them
+    pub async fn find_orphans(&self) -> Result<OrphanReport> {
+        let mut referenced = BTreeSet::new();
+
+        // every doc: directories, the audit journal and docs joined from peers
+        let docs = self.iroh_node.docs();
+        let ids: Vec<_> = docs.list().await?.collect().await;
+        for id in ids {
+            let (id, _) = id?;
+            let Some(doc) = docs.open(id).await? else {
+                continue;
+            };
+            let mut entries = doc.get_many(Query::all().build()).await?;
+            while let Some(entry) = entries.next().await {
+                referenced.insert(entry?.content_hash());
+            }
+        }
+
+        // tagged blobs are kept on purpose, e.g. exported collections
+        let mut tags = self.iroh_node.tags().list().await?;
+        while let Some(tag) = tags.next().await {
+            referenced.insert(tag?.hash);
+        }
+
+        let mut report = OrphanReport::default();
+        let mut blobs = self.iroh_node.blobs().list().await?;
+        while let Some(blob) = blobs.next().await {
+            let blob = blob?;
+            if !referenced.contains(&blob.hash) {
+                report.bytes += blob.size;
+                report.blobs.push((blob.hash, blob.size));
+            }
+        }
+        Ok(report)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[tokio::test]
+    async fn find_orphans_reports_stray_blob() {
+        let tmp_dir = TempDir::new().unwrap();
+        let mut lis = Lis::new(&tmp_dir.path().join("root"), true).await.unwrap();
+
+        let src = tmp_dir.path().join("a.txt");
+        std::fs::write(&src, b"hello").unwrap();
+        lis.import_file(&src, Path::new("/a.txt"), false)
+            .await
+            .unwrap();
+        assert_eq!(lis.find_orphans().await.unwrap(), OrphanReport::default());
+
+        // like a write that was aborted before its entry was set
+        let stray = lis.iroh_node.blobs().add_bytes("stray").await.unwrap();
+        lis.iroh_node.tags().delete(stray.tag).await.unwrap();
+
+        let report = lis.find_orphans().await.unwrap();
+        assert_eq!(report.blobs, vec![(stray.hash, 5)]);
+        assert_eq!(report.bytes, 5);
+    }
+}
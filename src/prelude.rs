@@ -22,11 +22,17 @@ pub type FileHandle = u64;
 
 pub const BLOCK_SIZE: u64 = 512;
 pub const MAX_NAME_LENGTH: u32 = 255;
+pub const DEFAULT_MAX_DIR_ENTRIES: u64 = 1_000_000;
+pub const DEFAULT_MAX_OPEN_FILES: u64 = 65_536;
+/// Removed entries go here when the trash is enabled
+pub const TRASH_DIR: &str = "/.trash";
 pub const MAX_FILE_SIZE: u64 = 1024 * 1024 * 1024 * 1024;
 
 // Top two file handle bits are used to store permissions
 // Note: This isn't safe, since the client can modify those bits.
 pub const FILE_HANDLE_READ_BIT: u64 = 1 << 63;
 pub const FILE_HANDLE_WRITE_BIT: u64 = 1 << 62;
+// Set for handles opened with O_NOATIME
+pub const FILE_HANDLE_NOATIME_BIT: u64 = 1 << 61;
 
 pub const FMODE_EXEC: i32 = 0x20;
@@ -0,0 +1,86 @@
+use std::{ffi::CString, os::unix::ffi::OsStrExt, sync::atomic::Ordering};
+
+use futures_lite::StreamExt;
+
+use crate::prelude::*;
+
+/// Filesystem usage, in the units `statfs(2)` reports
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FsStats {
+    /// Size of the filesystem, in `block_size` blocks
+    pub blocks: u64,
+    /// Free blocks
+    pub blocks_free: u64,
+    /// Free blocks available to unprivileged users
+    pub blocks_available: u64,
+    /// Inodes in use plus the ones still available
+    pub files: u64,
+    /// Inode numbers not handed out yet
+    pub files_free: u64,
+    pub block_size: u32,
+    pub max_name_length: u32,
+}
+
+impl Lis {
+    /// Usage of the filesystem: the blobs in the Iroh store count as used, and whatever the disk
+    /// holding `root` has left counts as free
+    pub async fn statfs(&self) -> Result<FsStats> {
+        let mut used = 0;
+        let mut blobs = self.iroh_node.blobs().list().await?;
+        while let Some(blob) = blobs.next().await {
+            used += blob?.size;
+        }
+
+        let root = CString::new(self.root.as_os_str().as_bytes())?;
+        let mut host: libc::statvfs = unsafe { std::mem::zeroed() };
+        if unsafe { libc::statvfs(root.as_ptr(), &mut host) } != 0 {
+            return Err(std::io::Error::last_os_error().into());
+        }
+        let fragment = host.f_frsize.max(1);
+        let free = host.f_bfree * fragment / BLOCK_SIZE;
+        let available = host.f_bavail * fragment / BLOCK_SIZE;
+
+        // inode numbers are never reused, so what's left is what can still be handed out
+        let files_free = u64::MAX - self.manifest.cur_ino.load(Ordering::SeqCst);
+        Ok(FsStats {
+            blocks: used.div_ceil(BLOCK_SIZE) + free,
+            blocks_free: free,
+            blocks_available: available,
+            files: self.manifest.objects.len() as u64 + files_free,
+            files_free,
+            block_size: BLOCK_SIZE as u32,
+            max_name_length: MAX_NAME_LENGTH,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[tokio::test]
+    async fn statfs_counts_blobs_and_inodes() {
+        let tmp_dir = TempDir::new().unwrap();
+        let mut lis = Lis::new(&tmp_dir.path().to_path_buf(), true).await.unwrap();
+
+        let before = lis.statfs().await.unwrap();
+        assert_eq!(before.block_size, BLOCK_SIZE as u32);
+        assert_eq!(before.max_name_length, MAX_NAME_LENGTH);
+        assert!(before.blocks_available <= before.blocks_free);
+        assert!(before.blocks_free <= before.blocks);
+
+        let file_path = Path::new("/big.bin");
+        lis.touch(file_path, None, None, None).await.unwrap();
+        lis.write(file_path, &vec![7; 64 * 1024], 0).await.unwrap();
+
+        let after = lis.statfs().await.unwrap();
+        assert_eq!(after.files_free, before.files_free - 1);
+        assert_eq!(
+            after.files - after.files_free,
+            before.files - before.files_free + 1
+        );
+        let used = |stats: &FsStats| stats.blocks - stats.blocks_free;
+        assert!(used(&after) >= used(&before) + 64 * 1024 / BLOCK_SIZE);
+    }
+}
@@ -0,0 +1,129 @@
+use crate::{prelude::*, LisError};
+
+/// Where `path` ends up when it's moved to the trash
+fn trash_path(full_path: &Path) -> PathBuf {
+    Path::new(TRASH_DIR).join(full_path.strip_prefix("/").unwrap_or(full_path))
+}
+
+impl Lis {
+    /// Whether removing `full_path` moves it to the trash instead of deleting it
+    /// Entries already in the trash are deleted for real
+    pub fn trashes(&self, full_path: &Path) -> bool {
+        self.trash && !full_path.starts_with(TRASH_DIR)
+    }
+
+    /// Moves `full_path` under `/.trash`, keeping its path below it and its inode
+    /// A file replaces an older trashed copy. A directory already in the trash is kept as it is
+    pub async fn move_to_trash(&mut self, full_path: &Path) -> Result<()> {
+        let trash_path = trash_path(full_path);
+        if self.is_dir(full_path) && self.is_dir(&trash_path) {
+            // nothing to move, rmdir only accepts empty directories
+            self.drop_dir(full_path).await?;
+            if let Some(ino) = self.manifest.inodes.remove(full_path) {
+                self.manifest.objects.remove(&ino);
+            }
+            return self.manifest.save();
+        }
+        self.mkdir_parents(&trash_path).await?;
+        self.rename(full_path, &trash_path, true).await?;
+        debug!("Moved {} to the trash", full_path.display());
+        Ok(())
+    }
+
+    /// Moves the trashed `full_path` back to where it was removed from
+    pub async fn restore_from_trash(&mut self, full_path: &Path) -> Result<()> {
+        let trash_path = trash_path(full_path);
+        if self.obj_from_path(&trash_path).is_none() {
+            return Err(LisError::NotFound(trash_path).into());
+        }
+        self.mkdir_parents(full_path).await?;
+        self.rename(&trash_path, full_path, false).await
+    }
+
+    /// Deletes everything in the trash. Their content is left to Iroh's GC
+    pub async fn empty_trash(&mut self) -> Result<()> {
+        let trash_dir = PathBuf::from(TRASH_DIR);
+        // children sort after their parents, go backwards so directories are empty when removed
+        let trashed: Vec<PathBuf> = self
+            .manifest
+            .inodes
+            .range(trash_dir.clone()..)
+            .take_while(|(path, _)| path.starts_with(&trash_dir))
+            .map(|(path, _)| path.clone())
+            .collect();
+        for path in trashed.into_iter().rev() {
+            if self.is_dir(&path) {
+                self.rmdir(&path).await?;
+            } else {
+                self.remove(&path).await?;
+            }
+            if let Some(ino) = self.manifest.inodes.remove(&path) {
+                self.manifest.objects.remove(&ino);
+            }
+        }
+        self.manifest.save()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures_lite::StreamExt;
+    use iroh::{blobs::Hash, docs::store::Query};
+    use tempfile::TempDir;
+
+    /// Whether any doc still has an entry pointing to `hash`, which keeps it from Iroh's GC
+    async fn referenced(lis: &Lis, hash: Hash) -> bool {
+        let docs = lis.iroh_node.docs();
+        let ids: Vec<_> = docs.list().await.unwrap().collect().await;
+        for (id, _) in ids.into_iter().map(Result::unwrap) {
+            let doc = docs.open(id).await.unwrap().unwrap();
+            let mut entries = doc.get_many(Query::all().build()).await.unwrap();
+            while let Some(entry) = entries.next().await {
+                if entry.unwrap().content_hash() == hash {
+                    return true;
+                }
+            }
+        }
+        false
+    }
+
+    #[tokio::test]
+    async fn trash_restore_and_empty() {
+        let tmp_dir = TempDir::new().unwrap();
+        let mut lis = Lis::new(&tmp_dir.path().to_path_buf(), true).await.unwrap();
+        lis.trash = true;
+
+        let dir = Path::new("/dir");
+        let file = Path::new("/dir/a.txt");
+        let hash = Hash::new(b"hello");
+        lis.mkdir(dir, None, None, None, false).await.unwrap();
+        lis.touch(file, None, None, None).await.unwrap();
+        lis.write(file, b"hello", 0).await.unwrap();
+        let ino = lis.obj_from_path(file).unwrap().attrs.inode;
+
+        lis.remove(file).await.unwrap();
+        assert!(lis.obj_from_path(file).is_none());
+        assert!(lis.read(file).await.is_err());
+        assert_eq!(
+            lis.read(Path::new("/.trash/dir/a.txt")).await.unwrap(),
+            "hello"
+        );
+
+        lis.restore_from_trash(file).await.unwrap();
+        assert_eq!(lis.obj_from_path(file).unwrap().attrs.inode, ino);
+        assert_eq!(lis.read(file).await.unwrap(), "hello");
+
+        lis.remove(file).await.unwrap();
+        lis.rmdir(dir).await.unwrap();
+        assert!(!lis.is_dir(dir));
+        assert!(lis.is_dir(Path::new("/.trash/dir")));
+        assert!(referenced(&lis, hash).await);
+
+        // nothing references the content anymore, so GC can collect it
+        lis.empty_trash().await.unwrap();
+        assert!(!referenced(&lis, hash).await);
+        assert!(lis.obj_from_path(Path::new(TRASH_DIR)).is_none());
+        assert!(lis.list(Path::new("/")).await.unwrap().is_empty());
+    }
+}
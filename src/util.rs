@@ -1,8 +1,11 @@
+use crate::{prelude::MAX_NAME_LENGTH, LisError};
 use anyhow::{anyhow, Result};
 use bytes::Bytes;
 use iroh::{docs::NamespaceId, util::fs::path_to_key};
 use std::{
-    fs,
+    ffi::OsStr,
+    fs, io,
+    os::{raw::c_int, unix::ffi::OsStrExt},
     path::{Path, PathBuf},
 };
 
@@ -18,6 +21,48 @@ pub fn bytes_to_namespaceid(bytes: Bytes) -> Result<NamespaceId> {
     Ok(array.into())
 }
 
+/// Whether `name` can be used as a single path component
+/// Rejects empty names, `.` and `..`, names with `/` or NUL, and names longer than
+/// `MAX_NAME_LENGTH` bytes
+pub fn is_valid_name(name: &OsStr) -> bool {
+    let bytes = name.as_bytes();
+    !bytes.is_empty()
+        && bytes != b"."
+        && bytes != b".."
+        && bytes.len() <= MAX_NAME_LENGTH as usize
+        && !bytes.iter().any(|b| *b == b'/' || *b == b'\0')
+}
+
+/// Whether a doc entry's `key` is an annotation (see `Lis::set_annotation`) rather than a file
+/// or directory. Annotation keys start with a NUL, which names can't have
+pub fn is_annotation_key(key: &[u8]) -> bool {
+    key.first() == Some(&0)
+}
+
+/// Checks every component of a path that's about to be created
+/// Works on the raw bytes, since `Path::components` silently drops `.` and repeated slashes
+pub fn validate_path(full_path: &Path) -> Result<(), LisError> {
+    let bytes = full_path.as_os_str().as_bytes();
+    let relpath = bytes.strip_prefix(b"/").unwrap_or(bytes);
+    if relpath
+        .split(|b| *b == b'/')
+        .all(|name| is_valid_name(OsStr::from_bytes(name)))
+    {
+        Ok(())
+    } else {
+        Err(LisError::InvalidName(full_path.to_path_buf()))
+    }
+}
+
+/// File name from a `Content-Disposition` header value, e.g. `attachment; filename="a.txt"`
+pub fn content_disposition_filename(value: &str) -> Option<String> {
+    value
+        .split(';')
+        .filter_map(|param| param.trim().strip_prefix("filename="))
+        .map(|name| name.trim_matches('"').to_string())
+        .find(|name| !name.is_empty())
+}
+
 pub fn add_leading_slash(path: &Path) -> PathBuf {
     if !path.starts_with("/") {
         let mut new_path = PathBuf::from("/");
@@ -54,6 +99,33 @@ pub fn key_to_string(key: Bytes) -> Result<String> {
     Ok(key_str.trim_end_matches('\0').to_string())
 }
 
+/// Picks the errno to report for an error, falling back to `default`
+/// `LisError`s map to their own errno. Out-of-space errors map to `ENOSPC` so applications can
+/// tell a full disk from other failures. Errors coming back over Iroh's RPC lose their type, so
+/// their message is checked too
+pub fn errno_from_error(e: &anyhow::Error, default: c_int) -> c_int {
+    if let Some(lis_error) = e.chain().find_map(|cause| cause.downcast_ref::<LisError>()) {
+        return lis_error.errno();
+    }
+
+    let out_of_space = e.chain().any(|cause| {
+        if let Some(io_error) = cause.downcast_ref::<io::Error>() {
+            if io_error.raw_os_error() == Some(libc::ENOSPC)
+                || io_error.kind() == io::ErrorKind::StorageFull
+            {
+                return true;
+            }
+        }
+        cause.to_string().contains("No space left on device")
+    });
+
+    if out_of_space {
+        libc::ENOSPC
+    } else {
+        default
+    }
+}
+
 #[allow(unused)]
 pub fn get_paths_in_dir(dir_path: &Path) -> Result<Vec<PathBuf>> {
     let mut paths = Vec::new();
@@ -78,6 +150,49 @@ pub fn get_paths_in_dir(dir_path: &Path) -> Result<Vec<PathBuf>> {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_validate_path() {
+        assert!(validate_path(Path::new("/dir/file.txt")).is_ok());
+        assert!(validate_path(Path::new("file.txt")).is_ok());
+
+        let too_long = format!("/{}", "a".repeat(MAX_NAME_LENGTH as usize + 1));
+        for invalid in [
+            too_long.as_str(),
+            "/",
+            "/dir//file",
+            "/dir/",
+            "/dir/../file",
+            "/./file",
+        ] {
+            assert!(
+                matches!(
+                    validate_path(Path::new(invalid)),
+                    Err(LisError::InvalidName(_))
+                ),
+                "{invalid}"
+            );
+        }
+
+        // can't be in a path without splitting it, but FUSE hands names over on their own
+        assert!(!is_valid_name(OsStr::new("a/b")));
+        assert!(!is_valid_name(OsStr::new("a\0b")));
+        assert!(!is_valid_name(OsStr::new("")));
+        assert!(is_valid_name(OsStr::new("..a")));
+    }
+
+    #[test]
+    fn test_content_disposition_filename() {
+        assert_eq!(
+            content_disposition_filename("attachment; filename=\"a b.txt\""),
+            Some("a b.txt".to_string())
+        );
+        assert_eq!(
+            content_disposition_filename("inline;filename=a.txt"),
+            Some("a.txt".to_string())
+        );
+        assert_eq!(content_disposition_filename("inline"), None);
+    }
+
     #[tokio::test]
     async fn test_bytes_to_namespaceid() {
         let node = iroh::node::Node::memory().spawn().await.unwrap();
@@ -96,6 +211,23 @@ mod tests {
         assert_eq!(Path::new("3"), converted_path);
     }
 
+    #[test]
+    fn test_errno_from_error() {
+        let e = anyhow::Error::new(io::Error::from_raw_os_error(libc::ENOSPC))
+            .context("could not add blob");
+        assert_eq!(errno_from_error(&e, libc::EIO), libc::ENOSPC);
+
+        // error that went through Iroh's RPC, only the message survives
+        let e = anyhow!("rpc error: No space left on device (os error 28)");
+        assert_eq!(errno_from_error(&e, libc::EIO), libc::ENOSPC);
+
+        let e = anyhow::Error::new(io::Error::from_raw_os_error(libc::EACCES));
+        assert_eq!(errno_from_error(&e, libc::EIO), libc::EIO);
+
+        let e = anyhow::Error::new(LisError::NotEmpty(PathBuf::from("/dir")));
+        assert_eq!(errno_from_error(&e, libc::EIO), libc::ENOTEMPTY);
+    }
+
     #[tokio::test]
     async fn test_add_leading_slash() {
         let path = Path::new("/3");
@@ -0,0 +1,172 @@
+use std::collections::HashSet;
+
+use futures_lite::{Stream, StreamExt};
+use iroh::{
+    client::docs::Doc,
+    docs::{store::Query, NamespaceId},
+};
+
+use crate::{fuse::FileKind, prelude::*, util::*, LisError};
+
+/// Paths still to be yielded by a walk, the next one last
+struct Walker<'a> {
+    lis: &'a Lis,
+    pending: Vec<(PathBuf, FileKind, Option<NamespaceId>)>,
+    /// Directory docs already descended into
+    visited: HashSet<NamespaceId>,
+    done: bool,
+}
+
+impl Walker<'_> {
+    /// Queues the entries of the directory `doc` at `dir_path`, so they're yielded next
+    async fn expand(&mut self, dir_path: &Path, doc: &Doc) -> Result<()> {
+        let mut entries = doc.get_many(Query::all().build()).await?;
+        let mut children = Vec::new();
+        while let Some(entry) = entries.next().await {
+            let entry = entry?;
+            if is_annotation_key(entry.key()) {
+                continue;
+            }
+            let full_path = dir_path.join(key_to_string(entry.key().to_vec().into())?);
+            let kind = self
+                .lis
+                .obj_from_path(&full_path)
+                .map_or(FileKind::File, |obj| obj.attrs.kind);
+            let id = match kind {
+                FileKind::Directory => Some(bytes_to_namespaceid(entry.content_bytes(doc).await?)?),
+                _ => None,
+            };
+            children.push((full_path, kind, id));
+        }
+        self.pending.extend(children.into_iter().rev());
+        Ok(())
+    }
+
+    async fn step(&mut self) -> Result<Option<(PathBuf, FileKind)>> {
+        let Some((full_path, kind, id)) = self.pending.pop() else {
+            return Ok(None);
+        };
+        // a directory reachable twice is only walked the first time
+        if let Some(id) = id.filter(|id| self.visited.insert(*id)) {
+            if let Some(doc) = self.lis.iroh_node.docs().open(id).await? {
+                self.expand(&full_path, &doc).await?;
+            }
+        }
+        Ok(Some((full_path, kind)))
+    }
+
+    async fn next(&mut self) -> Option<Result<(PathBuf, FileKind)>> {
+        if self.done {
+            return None;
+        }
+        match self.step().await {
+            Ok(next) => next.map(Ok),
+            Err(e) => {
+                self.done = true;
+                Some(Err(e))
+            }
+        }
+    }
+}
+
+impl Lis {
+    /// Every path below the directory `full_path`, depth first, with its kind. A directory comes
+    /// right before what's in it, and the entries of a directory are in name order
+    pub async fn walk(&self, full_path: &Path) -> Result<Vec<(PathBuf, FileKind)>> {
+        let mut walk = Box::pin(self.walk_stream(full_path).await?);
+        let mut walked = Vec::new();
+        while let Some(next) = walk.next().await {
+            walked.push(next?);
+        }
+        Ok(walked)
+    }
+
+    /// Like `walk`, but yields the paths as it goes, so only the directories on the way are
+    /// held in memory instead of the whole tree
+    pub async fn walk_stream(
+        &self,
+        full_path: &Path,
+    ) -> Result<impl Stream<Item = Result<(PathBuf, FileKind)>> + '_> {
+        let full_path = add_leading_slash(full_path);
+        if full_path != Path::new("/") && !self.is_dir(&full_path) {
+            return Err(match self.obj_from_path(&full_path) {
+                Some(_) => LisError::NotADirectory(full_path),
+                None => LisError::NotFound(full_path),
+            }
+            .into());
+        }
+        let doc = self.find_dir_doc(&full_path).await?;
+        let mut walker = Walker {
+            lis: self,
+            pending: Vec::new(),
+            visited: HashSet::from([doc.id()]),
+            done: false,
+        };
+        walker.expand(&full_path, &doc).await?;
+        Ok(futures_lite::stream::unfold(
+            walker,
+            |mut walker| async move {
+                let next = walker.next().await?;
+                Some((next, walker))
+            },
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[tokio::test]
+    async fn walk_tree() {
+        let tmp_dir = TempDir::new().unwrap();
+        let mut lis = Lis::new(&tmp_dir.path().to_path_buf(), true).await.unwrap();
+
+        lis.mkdir_parents(Path::new("/a/b/c/3.txt")).await.unwrap();
+        for path in ["/a/1.txt", "/a/b/2.txt", "/a/b/c/3.txt", "/z.txt"] {
+            lis.touch(Path::new(path), None, None, None).await.unwrap();
+        }
+
+        let walked = lis.walk(Path::new("/")).await.unwrap();
+        let expected = [
+            ("/a", FileKind::Directory),
+            ("/a/1.txt", FileKind::File),
+            ("/a/b", FileKind::Directory),
+            ("/a/b/2.txt", FileKind::File),
+            ("/a/b/c", FileKind::Directory),
+            ("/a/b/c/3.txt", FileKind::File),
+            ("/z.txt", FileKind::File),
+        ]
+        .map(|(path, kind)| (PathBuf::from(path), kind));
+        assert_eq!(walked, expected);
+
+        let walked = lis.walk(Path::new("/a/b")).await.unwrap();
+        assert_eq!(walked, expected[3..6]);
+
+        // the same directory doc linked in twice is only walked once
+        let (root, key) = lis.doc_and_key(Path::new("/again")).await.unwrap();
+        let a_id = lis.find_dir_doc(Path::new("/a")).await.unwrap().id();
+        let author = lis.iroh_node.authors().default().await.unwrap();
+        root.set_bytes(author, key, namespaceid_to_bytes(a_id))
+            .await
+            .unwrap();
+        lis.create_fs_objects(
+            Path::new("/again"),
+            FileKind::Directory,
+            None,
+            None,
+            None,
+            None,
+        )
+        .unwrap();
+        let walked = lis.walk(Path::new("/")).await.unwrap();
+        assert_eq!(walked.len(), expected.len() + 1);
+
+        let e = lis.walk(Path::new("/z.txt")).await.unwrap_err();
+        assert!(matches!(
+            e.downcast_ref::<LisError>(),
+            Some(LisError::NotADirectory(_))
+        ));
+    }
+}
@@ -0,0 +1,140 @@
+use std::process::Command;
+
+use tempfile::TempDir;
+
+fn lis() -> Command {
+    Command::new(env!("CARGO_BIN_EXE_lis"))
+}
+
+#[test]
+fn test_json_error() {
+    let tmp_root = TempDir::new().expect("Could not create temp dir");
+
+    let output = lis()
+        .arg(tmp_root.path())
+        .args(["--format", "json", "read", "/missing.txt"])
+        .output()
+        .expect("Could not run lis");
+
+    assert_eq!(output.status.code(), Some(1));
+    let error: serde_json::Value =
+        serde_json::from_slice(&output.stderr).expect("stderr is not json");
+    assert_eq!(error["error"]["kind"], "not_found");
+    assert_eq!(error["error"]["path"], "/missing.txt");
+}
+
+#[test]
+fn test_import_dir_and_read() {
+    let tmp_root = TempDir::new().expect("Could not create temp dir");
+    let src = TempDir::new().expect("Could not create temp dir");
+    let dir = src.path().join("docs");
+    std::fs::create_dir_all(dir.join("sub")).unwrap();
+    std::fs::write(dir.join("a.txt"), "hello ").unwrap();
+    std::fs::write(dir.join("sub").join("b.txt"), "world").unwrap();
+
+    let output = lis()
+        .arg(tmp_root.path())
+        .arg("import-file")
+        .arg(&dir)
+        .output()
+        .expect("Could not run lis");
+    assert!(output.status.success(), "{output:?}");
+
+    let output = lis()
+        .arg(tmp_root.path())
+        .args(["read", "/docs/a.txt", "/docs/sub/b.txt"])
+        .output()
+        .expect("Could not run lis");
+    assert!(output.status.success(), "{output:?}");
+    assert_eq!(output.stdout, b"hello world");
+}
+
+#[test]
+fn test_read_lines_and_bytes() {
+    let tmp_root = TempDir::new().expect("Could not create temp dir");
+    let src = TempDir::new().expect("Could not create temp dir");
+    let log = src.path().join("log.txt");
+    std::fs::write(&log, "one\ntwo\nthree\nfour\n").unwrap();
+
+    let output = lis()
+        .arg(tmp_root.path())
+        .arg("import-file")
+        .arg(&log)
+        .output()
+        .expect("Could not run lis");
+    assert!(output.status.success(), "{output:?}");
+
+    let read = |args: &[&str]| {
+        let output = lis()
+            .arg(tmp_root.path())
+            .args(["read", "/log.txt"])
+            .args(args)
+            .output()
+            .expect("Could not run lis");
+        assert!(output.status.success(), "{output:?}");
+        output.stdout
+    };
+    assert_eq!(read(&["--lines", "1:3"]), b"two\nthree\n");
+    assert_eq!(read(&["--lines", "-1:"]), b"four\n");
+    assert_eq!(read(&["--bytes", "0:3"]), b"one");
+
+    let output = lis()
+        .arg(tmp_root.path())
+        .args(["tail", "-n", "2", "/log.txt"])
+        .output()
+        .expect("Could not run lis");
+    assert!(output.status.success(), "{output:?}");
+    assert_eq!(output.stdout, b"three\nfour\n");
+}
+
+/// Waits until something is mounted on `mountpoint`, or not anymore
+fn wait_for_mount(mountpoint: &std::path::Path, unmounted_dev: u64, mounted: bool) {
+    use std::os::unix::fs::MetadataExt;
+
+    for _ in 0..200 {
+        let dev = std::fs::metadata(mountpoint).unwrap().dev();
+        if (dev != unmounted_dev) == mounted {
+            return;
+        }
+        std::thread::sleep(std::time::Duration::from_millis(50));
+    }
+    panic!("{} was never (un)mounted", mountpoint.display());
+}
+
+#[test]
+fn test_mount() {
+    use std::os::unix::fs::MetadataExt;
+
+    let tmp_root = TempDir::new().expect("Could not create temp dir");
+    let mountpoint = TempDir::new().expect("Could not create temp dir");
+    let unmounted_dev = std::fs::metadata(mountpoint.path()).unwrap().dev();
+
+    // in the foreground until Ctrl-C
+    let mut child = lis()
+        .arg(tmp_root.path())
+        .arg("mount")
+        .arg(mountpoint.path())
+        .arg("--foreground")
+        .spawn()
+        .expect("Could not run lis");
+    wait_for_mount(mountpoint.path(), unmounted_dev, true);
+    std::fs::write(mountpoint.path().join("hello.txt"), "hello").unwrap();
+    unsafe { libc::kill(child.id() as i32, libc::SIGINT) };
+    assert!(child.wait().unwrap().success());
+    wait_for_mount(mountpoint.path(), unmounted_dev, false);
+
+    // in the background, returning once mounted
+    let output = lis()
+        .arg(tmp_root.path())
+        .arg("mount")
+        .arg(mountpoint.path())
+        .output()
+        .expect("Could not run lis");
+    assert!(output.status.success(), "{output:?}");
+    assert_eq!(
+        std::fs::read_to_string(mountpoint.path().join("hello.txt")).unwrap(),
+        "hello"
+    );
+    let mountpoint_c = std::ffi::CString::new(mountpoint.path().to_str().unwrap()).unwrap();
+    assert_eq!(unsafe { libc::umount2(mountpoint_c.as_ptr(), 0) }, 0);
+}
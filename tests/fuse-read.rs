@@ -1,5 +1,6 @@
-use lis::Lis;
+use lis::{Lis, Manifest};
 use std::path::{Path, PathBuf};
+use std::time::SystemTime;
 use std::{fs, io::Write};
 use tempfile::{NamedTempFile, TempDir};
 use tokio::task;
@@ -12,6 +13,41 @@ async fn setup_lis(tmp_dir: &TempDir) -> Lis {
         .expect("Could not create new Lis node")
 }
 
+/// Access time of `file_name` as saved in the manifest on disk
+fn saved_atime(root: &Path, file_name: &Path) -> SystemTime {
+    let manifest = Manifest::load(&root.join("manifest.json"))
+        .unwrap()
+        .expect("manifest exists");
+    let ino = manifest.inodes[&Path::new("/").join(file_name)];
+    manifest.objects[&ino].attrs.last_accessed
+}
+
+/// Imports a file, mounts `lis`, reads the file through the mount and returns the saved atime
+/// before and after the read
+async fn read_and_get_atimes(tmp_root: &TempDir, mut lis: Lis) -> (SystemTime, SystemTime) {
+    let mut file = NamedTempFile::new_in("/tmp/").expect("Could not create named temp file");
+    write!(file, "atime").expect("Could not write to named temp file");
+    let file_name = PathBuf::from(file.path().file_name().unwrap());
+    lis.import_file(file.path(), &file_name, false)
+        .await
+        .expect("Could not import file");
+    let before = saved_atime(tmp_root.path(), &file_name);
+
+    let tmp_mountpoint = TempDir::new().expect("Could not create temp dir");
+    let handle = fuser::spawn_mount2(lis, &tmp_mountpoint, &[]).expect("could not mount Lis");
+
+    let path = tmp_mountpoint.path().join(&file_name);
+    let content = task::spawn_blocking(move || fs::read_to_string(path))
+        .await
+        .unwrap()
+        .expect("Could not read file");
+    assert_eq!(content, "atime");
+
+    // unmount so the manifest isn't being rewritten while it's loaded
+    task::spawn_blocking(move || handle.join()).await.unwrap();
+    (before, saved_atime(tmp_root.path(), &file_name))
+}
+
 #[tokio::test]
 async fn test_readdir_empty() {
     // Setup Lis
@@ -26,10 +62,7 @@ async fn test_readdir_empty() {
     let mut entries = tokio::fs::read_dir(mountpoint).await.unwrap();
     // Check if all entries are read-write
     while let Some(entry) = entries.next_entry().await.unwrap() {
-        assert_eq!(
-            false,
-            entry.metadata().await.unwrap().permissions().readonly()
-        );
+        assert!(!entry.metadata().await.unwrap().permissions().readonly());
     }
 }
 
@@ -44,9 +77,13 @@ async fn test_readdir_non_empty() {
     let mut file = NamedTempFile::new_in("/tmp/").expect("Could not create named temp file");
     let content = "Brian was here. Briefly.";
     write!(file, "{}", content).expect("Could not write to named temp file");
-    lis.import_file(file.path(), Path::new(file.path().file_name().unwrap()))
-        .await
-        .expect("Could not import file"); // should succeed
+    lis.import_file(
+        file.path(),
+        Path::new(file.path().file_name().unwrap()),
+        false,
+    )
+    .await
+    .expect("Could not import file"); // should succeed
 
     // Mount Lis
     let tmp_mountpoint = TempDir::new().expect("Could not create temp dir");
@@ -56,10 +93,8 @@ async fn test_readdir_non_empty() {
     let mountpoint = tmp_mountpoint.path().to_path_buf();
     let entries = task::spawn_blocking(move || {
         let mut results = vec![];
-        for entry in fs::read_dir(mountpoint).unwrap() {
-            if let Ok(entry) = entry {
-                results.push(entry);
-            }
+        for entry in fs::read_dir(mountpoint).unwrap().flatten() {
+            results.push(entry);
         }
         results
     })
@@ -70,10 +105,46 @@ async fn test_readdir_non_empty() {
 
     // Check if all entries are read-write
     for entry in entries {
-        assert_eq!(false, entry.metadata().unwrap().permissions().readonly());
+        assert!(!entry.metadata().unwrap().permissions().readonly());
     }
 }
 
+#[tokio::test]
+async fn test_readdirplus_saves_lookups() {
+    let tmp_root = TempDir::new().expect("Could not create temp dir");
+    let mut lis = setup_lis(&tmp_root).await;
+    let files = 20;
+    for i in 0..files {
+        lis.touch(Path::new(&format!("/file{i}")), None, None, None)
+            .await
+            .expect("Could not create file");
+    }
+    let counters = lis.fuse_counters.clone();
+
+    let tmp_mountpoint = TempDir::new().expect("Could not create temp dir");
+    let _handle = fuser::spawn_mount2(lis, &tmp_mountpoint, &[]).expect("could not mount Lis");
+
+    // `ls -l`: list, then stat every entry
+    let mountpoint = tmp_mountpoint.path().to_path_buf();
+    let stated = task::spawn_blocking(move || {
+        fs::read_dir(mountpoint)
+            .unwrap()
+            .filter(|entry| entry.as_ref().unwrap().metadata().unwrap().is_file())
+            .count()
+    })
+    .await
+    .unwrap();
+    assert_eq!(stated, files);
+
+    // the attributes came with the listing, a lookup or getattr per entry would be `files` calls
+    assert!(
+        counters.lookups() + counters.getattrs() < files as u64,
+        "{} lookups, {} getattrs",
+        counters.lookups(),
+        counters.getattrs()
+    );
+}
+
 #[tokio::test]
 async fn test_read() {
     // Setup Lis
@@ -85,9 +156,13 @@ async fn test_read() {
     let mut file = NamedTempFile::new_in("/tmp/").expect("Could not create named temp file");
     let content = "Brian was here. Briefly.";
     write!(file, "{}", content).expect("Could not write to named temp file");
-    lis.import_file(file.path(), Path::new(file.path().file_name().unwrap()))
-        .await
-        .expect("Could not import file"); // should succeed
+    lis.import_file(
+        file.path(),
+        Path::new(file.path().file_name().unwrap()),
+        false,
+    )
+    .await
+    .expect("Could not import file"); // should succeed
 
     // Mount Lis
     let tmp_mountpoint = TempDir::new().expect("Could not create temp dir");
@@ -98,3 +173,71 @@ async fn test_read() {
 
     assert_eq!(contents, "Brian was here. Briefly.");
 }
+
+#[tokio::test]
+async fn test_read_cached() {
+    // Setup Lis
+    let tmp_root = TempDir::new().expect("Could not create temp dir");
+    let mut lis = setup_lis(&tmp_root).await;
+    lis.direct_io = false;
+    let counters = lis.fuse_counters.clone();
+
+    // Add file to lis
+    let mut file = NamedTempFile::new_in("/tmp/").expect("Could not create named temp file");
+    let content = "Brian was here. Briefly.";
+    write!(file, "{}", content).expect("Could not write to named temp file");
+    let file_name = PathBuf::from(file.path().file_name().unwrap());
+    lis.import_file(file.path(), &file_name, false)
+        .await
+        .expect("Could not import file"); // should succeed
+
+    // Mount Lis
+    let tmp_mountpoint = TempDir::new().expect("Could not create temp dir");
+    let _handle = fuser::spawn_mount2(lis, &tmp_mountpoint, &[]).expect("could not mount Lis");
+
+    // Read the same file twice, the second read is served from the page cache
+    let path = tmp_mountpoint.path().join(file_name);
+    let read_twice = path.clone();
+    let (first, second, counters) = task::spawn_blocking(move || {
+        let first = fs::read_to_string(&read_twice).expect("Could not read file");
+        let reads = counters.reads();
+        let second = fs::read_to_string(&read_twice).expect("Could not read file");
+        assert_eq!(counters.reads(), reads);
+        (first, second, counters)
+    })
+    .await
+    .unwrap();
+    assert_eq!(first, "Brian was here. Briefly.");
+    assert_eq!(second, "Brian was here. Briefly.");
+
+    // Once the content changed, the next open drops the cached pages and reads it again
+    let before = counters.reads();
+    let changed = task::spawn_blocking(move || {
+        fs::write(&path, "Brian was here. Again.").expect("Could not write file");
+        fs::read_to_string(&path).expect("Could not read file")
+    })
+    .await
+    .unwrap();
+    assert_eq!(changed, "Brian was here. Again.");
+    assert!(counters.reads() > before);
+}
+
+#[tokio::test]
+async fn test_read_relatime() {
+    let tmp_root = TempDir::new().expect("Could not create temp dir");
+    let lis = setup_lis(&tmp_root).await;
+
+    // atime isn't newer than mtime yet, so the first read updates it
+    let (before, after) = read_and_get_atimes(&tmp_root, lis).await;
+    assert!(after > before);
+}
+
+#[tokio::test]
+async fn test_read_noatime() {
+    let tmp_root = TempDir::new().expect("Could not create temp dir");
+    let mut lis = setup_lis(&tmp_root).await;
+    lis.noatime = true;
+
+    let (before, after) = read_and_get_atimes(&tmp_root, lis).await;
+    assert_eq!(after, before);
+}
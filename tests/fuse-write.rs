@@ -194,3 +194,523 @@ async fn test_rm() {
     // remove file
     assert!(remove_file(&path).await.is_ok());
 }
+
+#[tokio::test]
+async fn test_rename() {
+    // Setup Lis
+    let tmp_root = TempDir::new().expect("Could not create temp dir");
+    let lis = setup_lis(&tmp_root).await;
+
+    // Mount Lis
+    let tmp_mountpoint = TempDir::new().expect("Could not create temp dir");
+    let _handle = fuser::spawn_mount2(lis, &tmp_mountpoint, &[]).expect("could not mount Lis");
+
+    let mountpoint = tmp_mountpoint.path().to_path_buf();
+
+    let path_a = mountpoint.join(Path::new("a.txt"));
+    let path_b = mountpoint.join(Path::new("b.txt"));
+    fs::write(&path_a, b"from a").await.unwrap();
+    fs::write(&path_b, b"from b").await.unwrap();
+
+    // rename(2) replaces an existing file
+    fs::rename(&path_a, &path_b).await.unwrap();
+
+    assert!(fs::metadata(&path_a).await.is_err());
+    let contents = fs::read_to_string(&path_b)
+        .await
+        .expect("Could not read file");
+    assert_eq!(contents, "from a");
+}
+
+#[tokio::test]
+async fn test_max_open_files() {
+    // Setup Lis
+    let tmp_root = TempDir::new().expect("Could not create temp dir");
+    let mut lis = setup_lis(&tmp_root).await;
+    lis.max_open_files = 2;
+
+    // Mount Lis
+    let tmp_mountpoint = TempDir::new().expect("Could not create temp dir");
+    let _handle = fuser::spawn_mount2(lis, &tmp_mountpoint, &[]).expect("could not mount Lis");
+
+    let mountpoint = tmp_mountpoint.path().to_path_buf();
+
+    let paths: Vec<PathBuf> = ["a.txt", "b.txt", "c.txt"]
+        .iter()
+        .map(|name| mountpoint.join(name))
+        .collect();
+    for path in &paths {
+        fs::write(path, b"hello").await.unwrap();
+    }
+    // the kernel sends release after close returns
+    sleep(Duration::from_millis(500)).await;
+
+    let file_a = File::open(&paths[0]).await.unwrap();
+    let _file_b = File::open(&paths[1]).await.unwrap();
+    let e = File::open(&paths[2]).await.unwrap_err();
+    assert_eq!(e.raw_os_error(), Some(libc::ENFILE));
+
+    // closing one makes room for another
+    drop(file_a);
+    let mut reopened = false;
+    for _ in 0..20 {
+        sleep(Duration::from_millis(100)).await;
+        if File::open(&paths[2]).await.is_ok() {
+            reopened = true;
+            break;
+        }
+    }
+    assert!(reopened);
+}
+
+#[tokio::test]
+async fn test_rename_across_dirs_keeps_inode() {
+    use std::os::unix::fs::MetadataExt;
+    use tokio::io::{AsyncReadExt, AsyncSeekExt};
+
+    // Setup Lis
+    let tmp_root = TempDir::new().expect("Could not create temp dir");
+    let lis = setup_lis(&tmp_root).await;
+
+    // Mount Lis
+    let tmp_mountpoint = TempDir::new().expect("Could not create temp dir");
+    let _handle = fuser::spawn_mount2(lis, &tmp_mountpoint, &[]).expect("could not mount Lis");
+
+    let mountpoint = tmp_mountpoint.path().to_path_buf();
+
+    let from = mountpoint.join("a").join("file.txt");
+    let to = mountpoint.join("b").join("moved.txt");
+    create_dir_all(mountpoint.join("a")).await.unwrap();
+    create_dir_all(mountpoint.join("b")).await.unwrap();
+    fs::write(&from, b"hello").await.unwrap();
+    let ino = fs::metadata(&from).await.unwrap().ino();
+
+    let mut file = fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open(&from)
+        .await
+        .unwrap();
+    fs::rename(&from, &to).await.unwrap();
+
+    assert!(fs::metadata(&from).await.is_err());
+    assert_eq!(fs::metadata(&to).await.unwrap().ino(), ino);
+
+    // the handle opened before the move still points to the same file
+    let mut content = String::new();
+    file.read_to_string(&mut content).await.unwrap();
+    assert_eq!(content, "hello");
+    file.seek(std::io::SeekFrom::End(0)).await.unwrap();
+    file.write_all(b" again").await.unwrap();
+    file.flush().await.unwrap();
+    drop(file);
+
+    assert_eq!(fs::read_to_string(&to).await.unwrap(), "hello again");
+}
+
+#[tokio::test]
+async fn test_rename_errors() {
+    use std::{ffi::CString, os::unix::ffi::OsStrExt};
+
+    // Setup Lis
+    let tmp_root = TempDir::new().expect("Could not create temp dir");
+    let lis = setup_lis(&tmp_root).await;
+
+    // Mount Lis
+    let tmp_mountpoint = TempDir::new().expect("Could not create temp dir");
+    let _handle = fuser::spawn_mount2(lis, &tmp_mountpoint, &[]).expect("could not mount Lis");
+
+    let mountpoint = tmp_mountpoint.path().to_path_buf();
+
+    let dir_a = mountpoint.join("a");
+    let dir_b = mountpoint.join("b");
+    create_dir_all(&dir_a).await.unwrap();
+    create_dir_all(&dir_b).await.unwrap();
+    fs::write(dir_b.join("file.txt"), b"keep").await.unwrap();
+
+    // a directory only replaces an empty one
+    let e = fs::rename(&dir_a, &dir_b).await.unwrap_err();
+    assert_eq!(e.raw_os_error(), Some(libc::ENOTEMPTY));
+
+    // RENAME_NOREPLACE refuses to replace anything
+    let from = mountpoint.join("x.txt");
+    let to = mountpoint.join("y.txt");
+    fs::write(&from, b"from x").await.unwrap();
+    fs::write(&to, b"from y").await.unwrap();
+    let errno = task_errno(move || {
+        let from = CString::new(from.as_os_str().as_bytes()).unwrap();
+        let to = CString::new(to.as_os_str().as_bytes()).unwrap();
+        unsafe {
+            libc::renameat2(
+                libc::AT_FDCWD,
+                from.as_ptr(),
+                libc::AT_FDCWD,
+                to.as_ptr(),
+                libc::RENAME_NOREPLACE,
+            )
+        }
+    })
+    .await;
+    assert_eq!(errno, Some(libc::EEXIST));
+    assert_eq!(
+        fs::read_to_string(mountpoint.join("y.txt")).await.unwrap(),
+        "from y"
+    );
+
+    // RENAME_EXCHANGE swaps the two
+    let (from, to) = (mountpoint.join("x.txt"), mountpoint.join("y.txt"));
+    let errno = task_errno(move || {
+        let from = CString::new(from.as_os_str().as_bytes()).unwrap();
+        let to = CString::new(to.as_os_str().as_bytes()).unwrap();
+        unsafe {
+            libc::renameat2(
+                libc::AT_FDCWD,
+                from.as_ptr(),
+                libc::AT_FDCWD,
+                to.as_ptr(),
+                libc::RENAME_EXCHANGE,
+            )
+        }
+    })
+    .await;
+    assert_eq!(errno, None);
+    assert_eq!(
+        fs::read_to_string(mountpoint.join("x.txt")).await.unwrap(),
+        "from y"
+    );
+    assert_eq!(
+        fs::read_to_string(mountpoint.join("y.txt")).await.unwrap(),
+        "from x"
+    );
+}
+
+#[tokio::test]
+async fn test_symlink() {
+    // Setup Lis
+    let tmp_root = TempDir::new().expect("Could not create temp dir");
+    let lis = setup_lis(&tmp_root).await;
+
+    // Mount Lis
+    let tmp_mountpoint = TempDir::new().expect("Could not create temp dir");
+    let _handle = fuser::spawn_mount2(lis, &tmp_mountpoint, &[]).expect("could not mount Lis");
+
+    let mountpoint = tmp_mountpoint.path().to_path_buf();
+
+    let target = mountpoint.join("file.txt");
+    let link = mountpoint.join("link");
+    fs::write(&target, b"through the link").await.unwrap();
+    fs::symlink("file.txt", &link).await.unwrap();
+
+    assert_eq!(fs::read_link(&link).await.unwrap(), Path::new("file.txt"));
+    let metadata = fs::symlink_metadata(&link).await.unwrap();
+    assert!(metadata.file_type().is_symlink());
+    assert_eq!(metadata.len(), "file.txt".len() as u64);
+    assert_eq!(fs::read_to_string(&link).await.unwrap(), "through the link");
+
+    let e = fs::symlink("elsewhere", &link).await.unwrap_err();
+    assert_eq!(e.raw_os_error(), Some(libc::EEXIST));
+
+    remove_file(&link).await.unwrap();
+    assert!(fs::symlink_metadata(&link).await.is_err());
+    assert!(fs::metadata(&target).await.is_ok());
+}
+
+#[tokio::test]
+async fn test_hardlink() {
+    use std::os::unix::fs::MetadataExt;
+
+    // Setup Lis
+    let tmp_root = TempDir::new().expect("Could not create temp dir");
+    let lis = setup_lis(&tmp_root).await;
+
+    // Mount Lis
+    let tmp_mountpoint = TempDir::new().expect("Could not create temp dir");
+    let _handle = fuser::spawn_mount2(lis, &tmp_mountpoint, &[]).expect("could not mount Lis");
+
+    let mountpoint = tmp_mountpoint.path().to_path_buf();
+
+    let a = mountpoint.join("a.txt");
+    let b = mountpoint.join("dir").join("b.txt");
+    create_dir_all(mountpoint.join("dir")).await.unwrap();
+    fs::write(&a, b"first").await.unwrap();
+    fs::hard_link(&a, &b).await.unwrap();
+
+    let metadata = fs::metadata(&b).await.unwrap();
+    assert_eq!(metadata.ino(), fs::metadata(&a).await.unwrap().ino());
+    assert_eq!(metadata.nlink(), 2);
+
+    // write through one name, read through the other
+    fs::write(&b, b"second version").await.unwrap();
+    assert_eq!(fs::read_to_string(&a).await.unwrap(), "second version");
+
+    remove_file(&a).await.unwrap();
+    assert!(fs::metadata(&a).await.is_err());
+    assert_eq!(fs::metadata(&b).await.unwrap().nlink(), 1);
+    assert_eq!(fs::read_to_string(&b).await.unwrap(), "second version");
+
+    let e = fs::hard_link(mountpoint.join("dir"), mountpoint.join("dir2"))
+        .await
+        .unwrap_err();
+    assert_eq!(e.raw_os_error(), Some(libc::EPERM));
+}
+
+#[tokio::test]
+async fn test_statfs() {
+    use std::{ffi::CString, os::unix::ffi::OsStrExt};
+
+    // Setup Lis
+    let tmp_root = TempDir::new().expect("Could not create temp dir");
+    let lis = setup_lis(&tmp_root).await;
+
+    // Mount Lis
+    let tmp_mountpoint = TempDir::new().expect("Could not create temp dir");
+    let _handle = fuser::spawn_mount2(lis, &tmp_mountpoint, &[]).expect("could not mount Lis");
+
+    let mountpoint = CString::new(tmp_mountpoint.path().as_os_str().as_bytes()).unwrap();
+    let stats = tokio::task::spawn_blocking(move || {
+        let mut stats: libc::statvfs = unsafe { std::mem::zeroed() };
+        let ret = unsafe { libc::statvfs(mountpoint.as_ptr(), &mut stats) };
+        assert_eq!(ret, 0, "{}", std::io::Error::last_os_error());
+        stats
+    })
+    .await
+    .unwrap();
+
+    assert_eq!(stats.f_bsize, 512);
+    assert_eq!(stats.f_namemax, 255);
+    assert!(stats.f_blocks > 0);
+    assert!(stats.f_bfree <= stats.f_blocks);
+    assert!(stats.f_ffree > 0);
+}
+
+#[tokio::test]
+async fn test_xattrs() {
+    use std::{ffi::CString, os::unix::ffi::OsStrExt};
+
+    // Setup Lis
+    let tmp_root = TempDir::new().expect("Could not create temp dir");
+    let lis = setup_lis(&tmp_root).await;
+
+    // Mount Lis
+    let tmp_mountpoint = TempDir::new().expect("Could not create temp dir");
+    let _handle = fuser::spawn_mount2(lis, &tmp_mountpoint, &[]).expect("could not mount Lis");
+
+    let file = tmp_mountpoint.path().join("file.txt");
+    fs::write(&file, b"hello").await.unwrap();
+
+    tokio::task::spawn_blocking(move || {
+        let path = CString::new(file.as_os_str().as_bytes()).unwrap();
+        let errno = || std::io::Error::last_os_error().raw_os_error();
+        let set = |name: &str, value: &[u8], flags: i32| {
+            let name = CString::new(name).unwrap();
+            unsafe {
+                libc::setxattr(
+                    path.as_ptr(),
+                    name.as_ptr(),
+                    value.as_ptr().cast(),
+                    value.len(),
+                    flags,
+                )
+            }
+        };
+        let get = |name: &str, buffer: &mut [u8]| {
+            let name = CString::new(name).unwrap();
+            unsafe {
+                libc::getxattr(
+                    path.as_ptr(),
+                    name.as_ptr(),
+                    buffer.as_mut_ptr().cast(),
+                    buffer.len(),
+                )
+            }
+        };
+
+        assert_eq!(set("user.color", b"blue", 0), 0);
+        assert_eq!(set("user.shape", b"round", 0), 0);
+
+        // a zero-sized buffer asks for the value's size
+        assert_eq!(get("user.color", &mut []), 4);
+        let mut buffer = [0; 16];
+        assert_eq!(get("user.color", &mut buffer), 4);
+        assert_eq!(&buffer[..4], b"blue");
+        let mut small = [0; 2];
+        assert_eq!(get("user.color", &mut small), -1);
+        assert_eq!(errno(), Some(libc::ERANGE));
+
+        assert_eq!(set("user.color", b"red", libc::XATTR_CREATE), -1);
+        assert_eq!(errno(), Some(libc::EEXIST));
+        assert_eq!(set("user.missing", b"x", libc::XATTR_REPLACE), -1);
+        assert_eq!(errno(), Some(libc::ENODATA));
+
+        let mut names = [0; 64];
+        let len = unsafe { libc::listxattr(path.as_ptr(), names.as_mut_ptr().cast(), 64) };
+        assert_eq!(&names[..len as usize], b"user.color\0user.shape\0");
+
+        let name = CString::new("user.color").unwrap();
+        assert_eq!(
+            unsafe { libc::removexattr(path.as_ptr(), name.as_ptr()) },
+            0
+        );
+        assert_eq!(get("user.color", &mut buffer), -1);
+        assert_eq!(errno(), Some(libc::ENODATA));
+    })
+    .await
+    .unwrap();
+}
+
+#[tokio::test]
+async fn test_fsync() {
+    // Setup Lis
+    let tmp_root = TempDir::new().expect("Could not create temp dir");
+    let lis = setup_lis(&tmp_root).await;
+
+    // Mount Lis
+    let tmp_mountpoint = TempDir::new().expect("Could not create temp dir");
+    let _handle = fuser::spawn_mount2(lis, &tmp_mountpoint, &[]).expect("could not mount Lis");
+
+    let mountpoint = tmp_mountpoint.path().to_path_buf();
+    let path = mountpoint.join("file.txt");
+    let mut file = File::create(&path).await.unwrap();
+    file.write_all(b"synced").await.unwrap();
+    file.sync_data().await.unwrap();
+    file.sync_all().await.unwrap();
+    drop(file);
+
+    File::open(&mountpoint)
+        .await
+        .unwrap()
+        .sync_all()
+        .await
+        .unwrap();
+    assert_eq!(fs::read_to_string(&path).await.unwrap(), "synced");
+}
+
+#[tokio::test]
+async fn test_fallocate() {
+    use std::os::fd::AsRawFd;
+
+    // Setup Lis
+    let tmp_root = TempDir::new().expect("Could not create temp dir");
+    let lis = setup_lis(&tmp_root).await;
+
+    // Mount Lis
+    let tmp_mountpoint = TempDir::new().expect("Could not create temp dir");
+    let _handle = fuser::spawn_mount2(lis, &tmp_mountpoint, &[]).expect("could not mount Lis");
+
+    let path = tmp_mountpoint.path().join("file.bin");
+    fs::write(&path, b"0123456789").await.unwrap();
+
+    let file = std::fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open(&path)
+        .unwrap();
+    let fd = file.as_raw_fd();
+    tokio::task::spawn_blocking(move || {
+        let fallocate = |mode, offset, len| unsafe { libc::fallocate(fd, mode, offset, len) };
+        // grows the file, the new range reads as zeros
+        assert_eq!(fallocate(0, 0, 4096), 0);
+        // reserves nothing past the end
+        assert_eq!(fallocate(libc::FALLOC_FL_KEEP_SIZE, 0, 1 << 20), 0);
+        assert_eq!(
+            fallocate(libc::FALLOC_FL_PUNCH_HOLE | libc::FALLOC_FL_KEEP_SIZE, 2, 3),
+            0
+        );
+        assert_eq!(fallocate(libc::FALLOC_FL_COLLAPSE_RANGE, 0, 4096), -1);
+        assert_eq!(
+            std::io::Error::last_os_error().raw_os_error(),
+            Some(libc::EOPNOTSUPP)
+        );
+    })
+    .await
+    .unwrap();
+    drop(file);
+
+    let content = fs::read(&path).await.unwrap();
+    assert_eq!(content.len(), 4096);
+    assert_eq!(&content[..10], b"01\0\0\x0056789");
+    assert!(content[10..].iter().all(|byte| *byte == 0));
+}
+
+#[tokio::test]
+async fn test_copy_file_range() {
+    use std::os::fd::AsRawFd;
+
+    // Setup Lis
+    let tmp_root = TempDir::new().expect("Could not create temp dir");
+    let lis = setup_lis(&tmp_root).await;
+
+    // Mount Lis
+    let tmp_mountpoint = TempDir::new().expect("Could not create temp dir");
+    let _handle = fuser::spawn_mount2(lis, &tmp_mountpoint, &[]).expect("could not mount Lis");
+
+    let src_path = tmp_mountpoint.path().join("src.txt");
+    let dst_path = tmp_mountpoint.path().join("dst.txt");
+    fs::write(&src_path, b"hello world").await.unwrap();
+    fs::write(&dst_path, b"").await.unwrap();
+
+    let src = std::fs::File::open(&src_path).unwrap();
+    let dst = std::fs::OpenOptions::new()
+        .write(true)
+        .open(&dst_path)
+        .unwrap();
+    let (src_fd, dst_fd) = (src.as_raw_fd(), dst.as_raw_fd());
+    tokio::task::spawn_blocking(move || {
+        let copy = |src_off: i64, dst_off: i64, len| {
+            let (mut src_off, mut dst_off) = (src_off, dst_off);
+            unsafe { libc::copy_file_range(src_fd, &mut src_off, dst_fd, &mut dst_off, len, 0) }
+        };
+        assert_eq!(copy(0, 0, 11), 11);
+        assert_eq!(copy(6, 11, 5), 5);
+        // nothing left to copy past the end
+        assert_eq!(copy(11, 0, 5), 0);
+    })
+    .await
+    .unwrap();
+    drop((src, dst));
+
+    assert_eq!(fs::read(&dst_path).await.unwrap(), b"hello worldworld");
+}
+
+#[tokio::test]
+async fn test_truncate() {
+    // Setup Lis
+    let tmp_root = TempDir::new().expect("Could not create temp dir");
+    let lis = setup_lis(&tmp_root).await;
+
+    // Mount Lis
+    let tmp_mountpoint = TempDir::new().expect("Could not create temp dir");
+    let _handle = fuser::spawn_mount2(lis, &tmp_mountpoint, &[]).expect("could not mount Lis");
+
+    let path = tmp_mountpoint.path().join("file.txt");
+    fs::write(&path, b"hello world").await.unwrap();
+
+    let file = fs::OpenOptions::new()
+        .write(true)
+        .open(&path)
+        .await
+        .unwrap();
+    file.set_len(5).await.unwrap();
+    assert_eq!(fs::read(&path).await.unwrap(), b"hello");
+    file.set_len(7).await.unwrap();
+    assert_eq!(fs::read(&path).await.unwrap(), b"hello\0\0");
+    drop(file);
+
+    // O_TRUNC drops the old content
+    fs::write(&path, b"hi").await.unwrap();
+    assert_eq!(fs::read(&path).await.unwrap(), b"hi");
+    assert_eq!(fs::metadata(&path).await.unwrap().len(), 2);
+}
+
+/// Runs a blocking libc call off the runtime and returns its errno, if it failed
+async fn task_errno(call: impl FnOnce() -> i32 + Send + 'static) -> Option<i32> {
+    tokio::task::spawn_blocking(move || {
+        if call() == 0 {
+            None
+        } else {
+            std::io::Error::last_os_error().raw_os_error()
+        }
+    })
+    .await
+    .unwrap()
+}